@@ -32,11 +32,7 @@ pub struct Action {
 #[pymethods]
 impl Action {
     fn __repr__(&self) -> String {
-        format!(
-            "(id: {}) {}",
-            self.id.to_string(),
-            self.bit_move.to_string()
-        )
+        format!("(id: {}) {}", self.id, self.bit_move)
     }
 }
 
@@ -78,111 +74,130 @@ lazy_static! {
     ]);
 }
 
-pub fn square_to_coords(square: &str) -> Result<(i16, i16), CustomError> {
-    let letter = square.chars().nth(0).unwrap();
-    let num = square.chars().nth(1).unwrap();
-    let x = match letter {
-        'a' => 0,
-        'b' => 1,
-        'c' => 2,
-        'd' => 3,
-        'e' => 4,
-        'f' => 5,
-        'g' => 6,
-        'h' => 7,
-        _ => return Err(CustomError::new("letter must be a to h")),
-    };
-    let y = num
-        .to_string()
-        .parse::<i16>()
-        .map_err(|_| CustomError::new(&format!("Unable to parse number {}", num)))?;
-
-    if y <= 0 || 8 < y {
+pub fn coords_to_square(x: i16, y: i16) -> Result<String, CustomError> {
+    if !(0..8).contains(&x) || !(0..8).contains(&y) {
         return Err(CustomError::new(&format!(
-            "Coordinate number {} is illegal",
-            y
+            "coordinates ({}, {}) are off the board",
+            x, y
         )));
     }
-    Ok((x, y - 1))
+    Ok(format!("{}{}", (b'a' + x as u8) as char, y + 1))
 }
 
-pub fn coords_to_square(x: i16, y: i16) -> Result<String, CustomError> {
-    let letter = match x {
-        0 => 'a',
-        1 => 'b',
-        2 => 'c',
-        3 => 'd',
-        4 => 'e',
-        5 => 'f',
-        6 => 'g',
-        7 => 'h',
-        _ => return Err(CustomError::new(&format!("coordinate {} is illegal", x))),
-    };
-    let num = format!("{}", y + 1);
-    Ok(format!("{}{}", letter, num))
-}
+pub fn action_to_move_string(action: ActionId) -> Result<String, CustomError> {
+    if action >= ACTION_SPACE_LEN {
+        return Err(CustomError::new(&format!(
+            "action id {} is outside the action space 0..{}",
+            action, ACTION_SPACE_LEN
+        )));
+    }
+    let invalid = || CustomError::new(&format!("action id {} does not encode a move", action));
 
-pub fn action_to_move_string(action: ActionId) -> String {
     let action = action as i16;
     let origin_sq = action / 73;
     let move_type = action % 73;
 
-    println!("origin_sq: {}, move_type: {}, ", origin_sq, move_type);
-    let from_x = origin_sq / 8;
-    let from_y = origin_sq % 8;
+    let from_x = origin_sq % 8;
+    let from_y = origin_sq / 8;
 
-    let from_sq = coords_to_square(from_x, from_y).unwrap();
-    println!("from_sq: {}", from_sq);
+    let from_sq = coords_to_square(from_x, from_y)?;
 
     if move_type >= 73 - 9 {
-        println!("it's a promo move!");
         let promo = move_type - (73 - 9);
-        let promo_target = promo / 4;
-        let promo_direction = promo % 4;
+        let promo_target = promo / 3;
+        let promo_direction = promo % 3;
 
-        let target_piece = PROMOTION_REVERSE_MAP.get(&promo_target).unwrap();
+        let target_piece = PROMOTION_REVERSE_MAP
+            .get(&promo_target)
+            .ok_or_else(invalid)?;
 
-        let to_y = from_y + 1;
-        let dx = match promo_direction {
-            0 => 1,
+        // Pawns promote from the seventh rank going up or from the second going down
+        let to_y = match from_y {
+            6 => 7,
             1 => 0,
-            2 => -1,
-            _ => unreachable!(),
+            _ => return Err(invalid()),
         };
-
-        let to_x = from_x + dx;
-        let to_sq = coords_to_square(to_x, to_y).unwrap();
-
-        return format!("{}{}{}", from_sq, to_sq, target_piece);
+        let to_x = from_x + promo_direction - 1;
+        let to_sq = coords_to_square(to_x, to_y).map_err(|_| invalid())?;
+
+        return Ok(format!(
+            "{}{}{}",
+            from_sq,
+            to_sq,
+            target_piece.to_ascii_lowercase()
+        ));
     }
 
     if move_type >= (73 - 9 - 8) {
-        println!("it's a knight move!");
         let knight_move = move_type - (73 - 9 - 8);
 
-        let (dx, dy) = KNIGHT_MOVE_MAP.get(&knight_move).unwrap().to_owned();
+        let (dx, dy) = KNIGHT_MOVE_MAP
+            .get(&knight_move)
+            .ok_or_else(invalid)?
+            .to_owned();
 
         let to_x = from_x + dx;
         let to_y = from_y + dy;
-        let to_sq = coords_to_square(to_x, to_y).unwrap();
-        return format!("{}{}", from_sq, to_sq);
+        let to_sq = coords_to_square(to_x, to_y).map_err(|_| invalid())?;
+        return Ok(format!("{}{}", from_sq, to_sq));
     }
 
-    println!("it's a directional move!");
-    let direction = move_type / 8;
-    let num_steps = move_type % 8;
-    println!("direction: {}", direction);
-    println!("num_steps: {}", num_steps);
+    let direction = move_type / 7;
+    let num_steps = move_type % 7 + 1;
 
-    let (x_dir, y_dir) = DIRECTIONAL_MOVE_MAP.get(&direction).unwrap().to_owned();
+    let (x_dir, y_dir) = DIRECTIONAL_MOVE_MAP
+        .get(&direction)
+        .ok_or_else(invalid)?
+        .to_owned();
 
     let to_x = from_x + x_dir * num_steps;
     let to_y = from_y + y_dir * num_steps;
-    let to_sq = coords_to_square(to_x, to_y).unwrap();
-    format!("{}{}", from_sq, to_sq)
+    let to_sq = coords_to_square(to_x, to_y).map_err(|_| invalid())?;
+    Ok(format!("{}{}", from_sq, to_sq))
 }
 
-pub fn build_action_space() -> Vec<String> {
-    let action_space = ACTION_SPACE.map(action_to_move_string).collect::<Vec<_>>();
-    todo!()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ChessEnv;
+
+    const FENS: [&str; 5] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+        "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N w - - 0 1",
+        "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+    ];
+
+    #[test]
+    fn legal_moves_round_trip() {
+        for fen in FENS {
+            let board = pleco::Board::from_fen(fen).unwrap();
+            let mut seen = HashMap::new();
+            for bit_move in board.generate_moves().iter() {
+                let action = ChessEnv::move_to_action(bit_move);
+                assert!(action < ACTION_SPACE_LEN);
+                if let Some(other) = seen.insert(action, *bit_move) {
+                    panic!(
+                        "{} and {} share action {} in {}",
+                        other, bit_move, action, fen
+                    );
+                }
+                // Queen promotions are plain pawn moves in the action space
+                let mut uci = bit_move.stringify();
+                if bit_move.is_promo() && bit_move.promo_piece() == pleco::PieceType::Q {
+                    uci.pop();
+                }
+                assert_eq!(action_to_move_string(action).unwrap(), uci, "in {}", fen);
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_squares_file_first() {
+        assert_eq!(action_to_move_string(877).unwrap(), "e2e4");
+        // h1 moving east leaves the board
+        assert!(action_to_move_string(7 * 73 + 2 * 7).is_err());
+        assert!(action_to_move_string(ACTION_SPACE_LEN).is_err());
+    }
 }
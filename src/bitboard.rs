@@ -1,14 +1,143 @@
+// pyo3 0.18 expands the operator methods into nested impls
+#![allow(non_local_definitions)]
+
+use numpy::PyArray2;
+use pyo3::basic::CompareOp;
 use pyo3::prelude::*;
 
+use crate::square::Square;
+
+/// A set of squares, bit `i` standing for square `i` (a1 = 0 .. h8 = 63).
 #[pyclass]
 #[derive(Copy, Clone, Debug)]
 pub struct BitBoard(pleco::BitBoard);
 
 #[pymethods]
 impl BitBoard {
+    #[new]
+    #[pyo3(signature = (bits=0))]
+    pub fn new(bits: u64) -> Self {
+        BitBoard(pleco::BitBoard(bits))
+    }
+
     fn __repr__(&self) -> String {
         self.0.to_string()
     }
+
+    #[staticmethod]
+    pub fn from_squares(squares: Vec<Square>) -> Self {
+        squares
+            .into_iter()
+            .fold(pleco::BitBoard(0), |bb, sq| {
+                bb | pleco::SQ::from(sq).to_bb()
+            })
+            .into()
+    }
+
+    fn __int__(&self) -> u64 {
+        self.0 .0
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.count_bits() as usize
+    }
+
+    fn __bool__(&self) -> bool {
+        self.0.is_not_empty()
+    }
+
+    fn __hash__(&self) -> u64 {
+        self.0 .0
+    }
+
+    fn __richcmp__(&self, other: &Self, op: CompareOp, py: Python<'_>) -> PyObject {
+        match op {
+            CompareOp::Eq => (self.0 == other.0).into_py(py),
+            CompareOp::Ne => (self.0 != other.0).into_py(py),
+            _ => py.NotImplemented(),
+        }
+    }
+
+    fn __contains__(&self, sq: Square) -> bool {
+        (self.0 & pleco::SQ::from(sq).to_bb()).is_not_empty()
+    }
+
+    fn __iter__(&self) -> BitBoardIterator {
+        BitBoardIterator { remaining: self.0 }
+    }
+
+    fn __and__(&self, other: &Self) -> Self {
+        BitBoard(self.0 & other.0)
+    }
+
+    fn __or__(&self, other: &Self) -> Self {
+        BitBoard(self.0 | other.0)
+    }
+
+    fn __xor__(&self, other: &Self) -> Self {
+        BitBoard(self.0 ^ other.0)
+    }
+
+    fn __sub__(&self, other: &Self) -> Self {
+        BitBoard(self.0 & !other.0)
+    }
+
+    fn __invert__(&self) -> Self {
+        BitBoard(!self.0)
+    }
+
+    // Bits shifted past either end are dropped
+    fn __lshift__(&self, shift: u32) -> Self {
+        Self::new(self.0 .0.checked_shl(shift).unwrap_or(0))
+    }
+
+    fn __rshift__(&self, shift: u32) -> Self {
+        Self::new(self.0 .0.checked_shr(shift).unwrap_or(0))
+    }
+
+    /// The lowest square in the set, or `None` if it is empty.
+    pub fn lsb(&self) -> Option<Square> {
+        self.0
+            .is_not_empty()
+            .then(|| self.0.bit_scan_forward().into())
+    }
+
+    /// The highest square in the set, or `None` if it is empty.
+    pub fn msb(&self) -> Option<Square> {
+        self.0.is_not_empty().then(|| self.0.msb().to_sq().into())
+    }
+
+    /// An 8x8 bool array indexed by `[rank][file]`, rank 0 being the first rank.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_numpy<'py>(&self, py: Python<'py>) -> PyResult<&'py PyArray2<bool>> {
+        // Surfaces a missing NumPy as an ImportError rather than a panic
+        py.import("numpy")?;
+        let grid = (0..8)
+            .map(|rank| {
+                (0..8)
+                    .map(|file| (self.0 .0 >> (rank * 8 + file)) & 1 == 1)
+                    .collect()
+            })
+            .collect::<Vec<Vec<bool>>>();
+        // Every row holds exactly 8 entries
+        Ok(PyArray2::from_vec2(py, &grid).unwrap())
+    }
+}
+
+#[pyclass]
+pub struct BitBoardIterator {
+    remaining: pleco::BitBoard,
+}
+
+#[pymethods]
+impl BitBoardIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self) -> Option<Square> {
+        self.remaining.next().map(|sq| sq.into())
+    }
 }
 
 impl From<pleco::BitBoard> for BitBoard {
@@ -22,3 +151,67 @@ impl From<BitBoard> for pleco::BitBoard {
         bit_board.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn squares(bb: &BitBoard) -> Vec<u8> {
+        let mut iter = bb.__iter__();
+        std::iter::from_fn(|| iter.__next__())
+            .map(|sq| sq.index())
+            .collect()
+    }
+
+    #[test]
+    fn combines_like_sets() {
+        let a = BitBoard::new(0b1100);
+        let b = BitBoard::new(0b1010);
+        assert_eq!(a.__and__(&b).__int__(), 0b1000);
+        assert_eq!(a.__or__(&b).__int__(), 0b1110);
+        assert_eq!(a.__xor__(&b).__int__(), 0b0110);
+        assert_eq!(a.__sub__(&b).__int__(), 0b0100);
+        assert_eq!(a.__invert__().__int__(), !0b1100);
+        assert_eq!(a.__len__(), 2);
+        assert!(a.__bool__());
+        assert!(!BitBoard::new(0).__bool__());
+        assert_eq!(a.__hash__(), 0b1100);
+    }
+
+    #[test]
+    fn shifts_drop_bits_off_the_ends() {
+        let h8 = BitBoard::new(1 << 63);
+        assert_eq!(h8.__lshift__(1).__int__(), 0);
+        assert_eq!(h8.__rshift__(63).__int__(), 1);
+        assert_eq!(h8.__rshift__(64).__int__(), 0);
+        assert_eq!(BitBoard::new(1).__lshift__(200).__int__(), 0);
+        assert_eq!(BitBoard::new(0xff).__lshift__(8).__int__(), 0xff00);
+    }
+
+    #[test]
+    fn iterates_squares_in_order() {
+        let bb = BitBoard::from_squares(vec![
+            Square::from_index(63).unwrap(),
+            Square::from_index(0).unwrap(),
+            Square::from_index(28).unwrap(),
+            Square::from_index(28).unwrap(),
+        ]);
+        assert_eq!(bb.__int__(), 1 << 63 | 1 << 28 | 1);
+        assert_eq!(squares(&bb), [0, 28, 63]);
+        assert!(bb.__contains__(Square::E4));
+        assert!(!bb.__contains__(Square::E5));
+        assert_eq!(bb.lsb(), Some(Square::A1));
+        assert_eq!(bb.msb(), Some(Square::H8));
+        assert_eq!(BitBoard::new(0).lsb(), None);
+        assert_eq!(BitBoard::new(0).msb(), None);
+        assert!(squares(&BitBoard::new(0)).is_empty());
+        assert_eq!(squares(&BitBoard::new(u64::MAX)).len(), 64);
+    }
+
+    #[test]
+    fn converts_to_and_from_pleco() {
+        let bb = BitBoard::from(pleco::BitBoard(0x8100));
+        assert_eq!(pleco::BitBoard::from(bb).0, 0x8100);
+        assert_eq!(bb.__repr__(), pleco::BitBoard(0x8100).to_string());
+    }
+}
@@ -1,36 +1,89 @@
 use crate::bitboard::BitBoard;
 use crate::core::{CastleType, GenTypes, Piece, PieceType, Player};
-use crate::error::FenBuildError;
+use crate::endgame::{parse_material, random_position};
+use crate::error::{
+    invalid_fen, ChessError, IllegalMoveError, InvalidArgumentError, InvalidPositionError,
+};
+use crate::evaluation;
+use crate::features;
+use crate::observation::{encode, unpack};
+use crate::pgn::parse_san;
 use crate::piece_move::{BitMove, ScoringMove};
-use crate::score::Score;
+use crate::render::svg::{board_svg, Arrow, SvgOptions, ARROW_COLOUR, ARROW_WIDTH};
+use crate::sampler::{entropy_seed, seeded_prng};
+use crate::score::{phase, Score};
+use crate::search::{self, Limits, SearchHandle, SearchResult};
 use crate::square::Square;
+use crate::tablebase::{Tablebase, Wdl};
+use crate::transposition::TranspositionTable;
+use pyo3::basic::CompareOp;
 use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::HashMap;
+use std::time::Duration;
 
 #[pyclass]
 pub struct Board {
     inner: pleco::Board,
+    // The position the history starts from, the moves played since and the zobrist key of every
+    // position reached, `keys[0]` being the root's
+    root_fen: String,
+    moves: Vec<pleco::BitMove>,
+    keys: Vec<u64>,
 }
 
 #[pymethods]
 impl Board {
     #[new]
     pub fn new() -> Self {
-        Self {
-            inner: pleco::Board::start_pos(),
-        }
+        pleco::Board::start_pos().into()
     }
 
     pub fn __repr__(&self) -> String {
         self.inner.pretty_string()
     }
 
-    #[staticmethod]
-    pub fn start_pos() -> Board {
+    /// Boards are equal when their FENs are, i.e. pieces, side to move, castling rights, en
+    /// passant square and move counters all match. Move history is not compared.
+    fn __richcmp__(&self, other: &Self, op: CompareOp, py: Python<'_>) -> PyObject {
+        match op {
+            CompareOp::Eq => (self == other).into_py(py),
+            CompareOp::Ne => (self != other).into_py(py),
+            _ => py.NotImplemented(),
+        }
+    }
+
+    fn __hash__(&self) -> u64 {
+        self.inner.zobrist()
+    }
+
+    // Copies keep the move history, so `undo_move` works on them as well
+    fn __copy__(&self) -> Board {
         Board {
-            inner: pleco::Board::start_pos(),
+            inner: self.inner.parallel_clone(),
+            root_fen: self.root_fen.clone(),
+            moves: self.moves.clone(),
+            keys: self.keys.clone(),
         }
     }
 
+    fn __deepcopy__(&self, _memo: &PyAny) -> Board {
+        self.__copy__()
+    }
+
+    /// The position with the board flipped vertically and the colours swapped.
+    pub fn mirror(&self) -> Board {
+        // A mirrored FEN of a valid position is always valid
+        pleco::Board::from_fen(&mirror_fen(&self.inner.fen()))
+            .unwrap()
+            .into()
+    }
+
+    #[staticmethod]
+    pub fn start_pos() -> Board {
+        pleco::Board::start_pos().into()
+    }
+
     pub fn state(&self) -> [[Piece; 8]; 8] {
         let piece_locations = self.inner.get_piece_locations();
 
@@ -43,83 +96,220 @@ impl Board {
         state
     }
 
-    // pub fn random(&self) -> PyResult<()> {
-    //     todo!();
-    // }
+    /// The observation planes used for training data, as `[plane][rank][file]` 0/1 values.
+    pub fn observation(&self) -> Vec<[[u8; 8]; 8]> {
+        unpack(&encode(&self.inner))
+    }
+
+    /// A standalone SVG image of the board, `size` pixels wide.
+    ///
+    /// `arrows` are `(from, to)` or `(from, to, colour)` tuples, `fills` maps squares to colours
+    /// and `check` highlights the square of a king in check.
+    #[pyo3(signature = (size=400, flipped=false, last_move=None, arrows=None, fills=None, check=None))]
+    pub fn to_svg(
+        &self,
+        size: u32,
+        flipped: bool,
+        last_move: Option<BitMove>,
+        arrows: Option<Vec<ArrowSpec>>,
+        fills: Option<HashMap<Square, String>>,
+        check: Option<Square>,
+    ) -> String {
+        let arrows = arrows
+            .unwrap_or_default()
+            .into_iter()
+            .map(|spec| {
+                let (from, to, colour) = match spec {
+                    ArrowSpec::Coloured(from, to, colour) => (from, to, colour),
+                    ArrowSpec::Plain(from, to) => (from, to, ARROW_COLOUR.to_string()),
+                };
+                Arrow {
+                    from: from.into(),
+                    to: to.into(),
+                    colour,
+                    width: ARROW_WIDTH,
+                }
+            })
+            .collect();
+        let options = SvgOptions {
+            size,
+            flipped,
+            last_move: last_move.map(|m| m.into()),
+            check: check.map(|sq| sq.into()),
+            fills: fills
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(sq, colour)| (sq.into(), colour))
+                .collect(),
+            arrows,
+        };
+        board_svg(&self.inner, &options)
+    }
+
+    /// Generates a random legal position with the given material, e.g. `"KRPvKR"`.
+    ///
+    /// The side to move is random unless given. With `require_undecided`, positions that are
+    /// already checkmate or stalemate are rejected. The GIL is released while placing pieces.
+    #[staticmethod]
+    #[pyo3(signature = (material, side_to_move=None, seed=None, require_undecided=false))]
+    pub fn random_endgame(
+        py: Python,
+        material: &str,
+        side_to_move: Option<Player>,
+        seed: Option<i64>,
+        require_undecided: bool,
+    ) -> PyResult<Board> {
+        let material = parse_material(material)
+            .map_err(|err| InvalidArgumentError::new_err(err.to_string()))?;
+        // Seeds are taken like `ChessEnv.reset`'s
+        let mut prng = seeded_prng(seed.map_or_else(entropy_seed, |seed| seed as u64));
+        let side_to_move = side_to_move.map(|p| p.into());
+        py.allow_threads(|| random_position(&material, side_to_move, require_undecided, &mut prng))
+            .map(|board| board.into())
+            .ok_or_else(|| InvalidPositionError::new_err("unable to generate a legal position"))
+    }
 
     #[staticmethod]
     pub fn from_fen(fen: &str) -> PyResult<Board> {
-        let x = pleco::Board::from_fen(fen)
-            .map(|board| Board { inner: board })
-            .map_err(|err| Into::<FenBuildError>::into(err).into());
-        x
+        pleco::Board::from_fen(fen)
+            .map(|board| board.into())
+            .map_err(|err| invalid_fen(fen, err))
     }
 
     pub fn fen(&self) -> String {
         self.inner.fen()
     }
 
-    pub fn apply_move(&mut self, bit_move: BitMove) {
-        self.inner.apply_move(bit_move.into());
+    /// Plays `bit_move`, raising `IllegalMoveError` if it is not legal in this position.
+    pub fn apply_move(&mut self, bit_move: BitMove) -> PyResult<()> {
+        let bit_move = pleco::BitMove::from(bit_move);
+        if !self.is_legal(bit_move) {
+            return Err(IllegalMoveError::new_err(format!(
+                "illegal move {} in position {}",
+                bit_move,
+                self.inner.fen()
+            )));
+        }
+        self.push(bit_move);
+        Ok(())
+    }
+
+    /// Plays `bit_move` without checking it. An illegal move corrupts the board.
+    pub fn apply_move_unchecked(&mut self, bit_move: BitMove) {
+        self.push(bit_move.into());
+    }
+
+    /// Plays a move given in UCI notation, e.g. `"e7e8q"`, and returns it.
+    pub fn push_uci(&mut self, uci: &str) -> PyResult<BitMove> {
+        let bit_move = self.find_uci(uci).ok_or_else(|| {
+            IllegalMoveError::new_err(format!(
+                "illegal move {} in position {}",
+                uci,
+                self.inner.fen()
+            ))
+        })?;
+        self.push(bit_move);
+        Ok(bit_move.into())
+    }
+
+    /// Plays a move given in SAN, e.g. `"Nf3"` or `"exd8=Q+"`, and returns it.
+    pub fn push_san(&mut self, san: &str) -> PyResult<BitMove> {
+        let bit_move = parse_san(&self.inner, san).ok_or_else(|| {
+            IllegalMoveError::new_err(format!(
+                "illegal move {} in position {}",
+                san,
+                self.inner.fen()
+            ))
+        })?;
+        self.push(bit_move);
+        Ok(bit_move.into())
+    }
+
+    /// Takes back the last move and returns it. Raises `ChessError` if there is none.
+    pub fn undo_move(&mut self) -> PyResult<BitMove> {
+        // A board built from a FEN or cloned starts without history
+        let bit_move = self
+            .moves
+            .pop()
+            .ok_or_else(|| ChessError::new_err("no move to undo"))?;
+        self.keys.pop();
+        self.inner.undo_move();
+        Ok(bit_move.into())
+    }
+
+    //  ------- History -------
+
+    /// The moves played since `root_fen()`, oldest first.
+    pub fn move_stack(&self) -> Vec<BitMove> {
+        self.moves.iter().map(|m| (*m).into()).collect()
+    }
+
+    /// The zobrist key of every position since `root_fen()`, including the root and the current
+    /// position.
+    pub fn position_history(&self) -> Vec<u64> {
+        self.keys.clone()
     }
 
-    pub fn undo_move(&mut self) {
-        self.inner.undo_move();
+    /// How many times the current position has occurred, counting this occurrence.
+    pub fn repetition_count(&self) -> usize {
+        let key = self.inner.zobrist();
+        self.keys.iter().filter(|k| **k == key).count()
+    }
+
+    /// Whether the current position has occurred at least `count` times.
+    #[pyo3(signature = (count=3))]
+    pub fn is_repetition(&self, count: usize) -> bool {
+        self.repetition_count() >= count
+    }
+
+    /// The FEN of the position the move history starts from.
+    pub fn root_fen(&self) -> String {
+        self.root_fen.clone()
     }
 
     pub fn generate_moves(&self) -> Vec<BitMove> {
-        let move_list = self
-            .inner
+        self.inner
             .generate_moves()
             .to_vec()
             .into_iter()
             .map(|m| m.into())
-            .collect::<Vec<_>>();
-        move_list
+            .collect::<Vec<_>>()
     }
 
     pub fn generate_scoring_moves(&self) -> Vec<ScoringMove> {
-        let move_list = self
-            .inner
+        self.inner
             .generate_scoring_moves()
             .to_vec()
             .into_iter()
             .map(|m| m.into())
-            .collect::<Vec<_>>();
-        move_list
+            .collect::<Vec<_>>()
     }
 
     pub fn generate_pseudolegal_moves(&self) -> Vec<BitMove> {
-        let move_list = self
-            .inner
+        self.inner
             .generate_pseudolegal_moves()
             .to_vec()
             .into_iter()
             .map(|m| m.into())
-            .collect::<Vec<_>>();
-        move_list
+            .collect::<Vec<_>>()
     }
 
     pub fn generate_moves_of_type(&self, gen_type: GenTypes) -> Vec<BitMove> {
-        let move_list = self
-            .inner
+        self.inner
             .generate_moves_of_type(gen_type.into())
             .to_vec()
             .into_iter()
             .map(|m| m.into())
-            .collect::<Vec<_>>();
-        move_list
+            .collect::<Vec<_>>()
     }
 
     pub fn generate_pseudolegal_moves_of_type(&self, gen_type: GenTypes) -> Vec<BitMove> {
-        let move_list = self
-            .inner
+        self.inner
             .generate_pseudolegal_moves_of_type(gen_type.into())
             .to_vec()
             .into_iter()
             .map(|m| m.into())
-            .collect::<Vec<_>>();
-        move_list
+            .collect::<Vec<_>>()
     }
 
     pub fn turn(&self) -> Player {
@@ -158,6 +348,81 @@ impl Board {
         self.inner.psq().into()
     }
 
+    /// The game phase used to interpolate a `Score`, from 0 (endgame) to 128 (middlegame).
+    pub fn phase(&self) -> i32 {
+        phase(&self.inner)
+    }
+
+    /// Searches for the best move with `random`, `minimax`, `alphabeta` or `iterative`
+    /// (alpha-beta with move ordering and a quiescence search) on pleco's evaluator, like the
+    /// pleco searchers of the same names. pleco's parallel `jamboree` is not available here.
+    ///
+    /// The search deepens one ply at a time until `depth` plies, `movetime_ms` milliseconds or
+    /// `nodes` nodes are reached, or `handle` is stopped from another thread, and returns the
+    /// last iteration that finished; with no limit it searches 4 plies. Repetitions of positions
+    /// in the move history are scored as draws. Passing a `TranspositionTable` reuses what
+    /// earlier searches stored in it. The GIL is released while searching.
+    #[pyo3(signature = (
+        algorithm,
+        depth=None,
+        movetime_ms=None,
+        nodes=None,
+        handle=None,
+        table=None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn search(
+        &self,
+        py: Python,
+        algorithm: &str,
+        depth: Option<u16>,
+        movetime_ms: Option<u64>,
+        nodes: Option<u64>,
+        handle: Option<SearchHandle>,
+        table: Option<TranspositionTable>,
+    ) -> PyResult<SearchResult> {
+        let algorithm =
+            search::parse_algorithm(algorithm).map_err(InvalidArgumentError::new_err)?;
+        let limits = Limits {
+            depth,
+            movetime: movetime_ms.map(Duration::from_millis),
+            nodes,
+        };
+        let handle = handle.unwrap_or_default();
+        let board = self.inner.shallow_clone();
+        let keys = self.keys.clone();
+        Ok(py.allow_threads(move || {
+            search::search(
+                &board,
+                &keys,
+                algorithm,
+                limits,
+                handle.stop_flag(),
+                table.as_ref(),
+            )
+        }))
+    }
+
+    /// pleco's static evaluation in centipawns, from the point of view of the side to move.
+    pub fn evaluate(&self) -> i32 {
+        evaluation::evaluate(&self.inner)
+    }
+
+    /// The terms of `evaluate()` as `{term: (white, black)}`, each side scored from its own
+    /// point of view in centipawns: `material`, `pawn_structure` and `king_safety`.
+    /// `evaluate()` is the side to move's sum of them less the opponent's.
+    pub fn evaluate_breakdown<'py>(&self, py: Python<'py>) -> &'py PyDict {
+        terms_dict(py, evaluation::breakdown(&self.inner))
+    }
+
+    /// Positional features pleco's evaluator leaves out, as `{feature: (white, black)}` in
+    /// centipawns: `psq`, `mobility`, `passed_pawns` and `threats`. They use Stockfish's
+    /// bonuses, blended by `phase()`; like `psq()`, the piece-square term includes the value of
+    /// the pieces.
+    pub fn evaluate_features<'py>(&self, py: Python<'py>) -> &'py PyDict {
+        terms_dict(py, evaluation::features(&self.inner))
+    }
+
     pub fn ep_square(&self) -> Square {
         self.inner.ep_square().into()
     }
@@ -314,14 +579,38 @@ impl Board {
             .into()
     }
 
+    /// How many pieces of `player` attack each square, a1 first.
+    pub fn attack_map(&self, player: Player) -> [u8; 64] {
+        features::attack_map(&self.inner, player.into())
+    }
+
+    /// Pieces of `player`, other than the king, attacked by the opponent and left undefended.
+    pub fn hanging_pieces(&self, player: Player) -> BitBoard {
+        features::hanging_pieces(&self.inner, player.into()).into()
+    }
+
+    /// The squares attacked by each piece type of `player` that it does not occupy itself, as
+    /// `{"knight": n, "bishop": n, "rook": n, "queen": n, "king": n}`.
+    ///
+    /// Pawns have no entry: the squares a pawn attacks are not the squares it can move to, so
+    /// counting them would not measure how freely it moves. `attack_map` includes pawns.
+    pub fn mobility<'py>(&self, py: Python<'py>, player: Player) -> &'py PyDict {
+        let mobility = PyDict::new(py);
+        for (piece_type, squares) in features::mobility(&self.inner, player.into()) {
+            // Setting items on a fresh dict cannot fail
+            mobility.set_item(piece_type, squares).unwrap();
+        }
+        mobility
+    }
+
     pub fn pawn_passed(&self, player: Player, sq: Square) -> bool {
-        self.inner.pawn_passed(player.into(), sq.into()).into()
+        self.inner.pawn_passed(player.into(), sq.into())
     }
 
     //  ------- Move Testing -------
 
     pub fn legal_move(&self, m: BitMove) -> bool {
-        self.inner.legal_move(m.into())
+        self.is_legal(m.into())
     }
 
     pub fn pseudo_legal_move(&self, m: BitMove) -> bool {
@@ -352,6 +641,13 @@ impl Board {
         self.inner.see_ge(mov.into(), threshold)
     }
 
+    /// The material `mov` wins, or loses if negative, once the exchange on its destination
+    /// square is played out, in the piece values of `see_ge` (a pawn is 171). Castling, en
+    /// passant and promotions are valued at 0.
+    pub fn see(&self, mov: BitMove) -> i32 {
+        features::see(&self.inner, mov.into())
+    }
+
     pub fn moved_piece(&self, m: BitMove) -> Piece {
         self.inner.moved_piece(m.into()).into()
     }
@@ -364,6 +660,16 @@ impl Board {
         self.inner.key_after(m.into())
     }
 
+    //  ------- Tablebases -------
+
+    pub fn probe_wdl(&self, tablebase: &Tablebase) -> PyResult<Wdl> {
+        tablebase.probe_wdl(self)
+    }
+
+    pub fn probe_dtz(&self, tablebase: &Tablebase) -> PyResult<i32> {
+        tablebase.probe_dtz(self)
+    }
+
     pub fn pretty_string(&self) -> String {
         self.inner.pretty_string()
     }
@@ -380,3 +686,216 @@ impl Board {
         self.inner.fancy_print();
     }
 }
+
+impl Board {
+    fn push(&mut self, bit_move: pleco::BitMove) {
+        self.inner.apply_move(bit_move);
+        self.moves.push(bit_move);
+        self.keys.push(self.inner.zobrist());
+    }
+
+    pub(crate) fn inner(&self) -> &pleco::Board {
+        &self.inner
+    }
+
+    pub(crate) fn is_legal(&self, bit_move: pleco::BitMove) -> bool {
+        // `legal_move` assumes a pseudo-legal move
+        self.inner.pseudo_legal_move(bit_move) && self.inner.legal_move(bit_move)
+    }
+
+    pub(crate) fn find_uci(&self, uci: &str) -> Option<pleco::BitMove> {
+        let uci = uci.to_ascii_lowercase();
+        self.inner
+            .generate_moves()
+            .iter()
+            .find(|m| m.stringify() == uci)
+            .copied()
+    }
+}
+
+#[derive(FromPyObject)]
+pub enum ArrowSpec {
+    Coloured(Square, Square, String),
+    Plain(Square, Square),
+}
+
+impl PartialEq for Board {
+    fn eq(&self, other: &Board) -> bool {
+        self.inner.fen() == other.inner.fen()
+    }
+}
+
+impl From<pleco::Board> for Board {
+    fn from(board: pleco::Board) -> Board {
+        Board {
+            root_fen: board.fen(),
+            keys: vec![board.zobrist()],
+            moves: vec![],
+            inner: board,
+        }
+    }
+}
+
+fn terms_dict<'py>(py: Python<'py>, terms: Vec<(&'static str, [i32; 2])>) -> &'py PyDict {
+    let dict = PyDict::new(py);
+    for (term, [white, black]) in terms {
+        // Keys are plain strings and values are tuples of ints
+        dict.set_item(term, (white, black)).unwrap();
+    }
+    dict
+}
+
+fn mirror_fen(fen: &str) -> String {
+    let fields = fen.split_whitespace().collect::<Vec<_>>();
+    let swap_case = |c: char| {
+        if c.is_ascii_uppercase() {
+            c.to_ascii_lowercase()
+        } else {
+            c.to_ascii_uppercase()
+        }
+    };
+
+    let placement = fields[0]
+        .split('/')
+        .rev()
+        .map(|rank| rank.chars().map(swap_case).collect::<String>())
+        .collect::<Vec<_>>()
+        .join("/");
+    let turn = if fields[1] == "w" { "b" } else { "w" };
+    let castling = match fields[2] {
+        "-" => "-".to_string(),
+        rights => {
+            let swapped = rights.chars().map(swap_case).collect::<String>();
+            "KQkq".chars().filter(|c| swapped.contains(*c)).collect()
+        }
+    };
+    let ep_square = fields[3]
+        .chars()
+        .map(|c| match c {
+            '3' => '6',
+            '6' => '3',
+            c => c,
+        })
+        .collect::<String>();
+
+    let mut mirrored = vec![placement.as_str(), turn, &castling, &ep_square];
+    mirrored.extend(&fields[4..]);
+    mirrored.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn played(ucis: &[&str]) -> Board {
+        let mut board = Board::new();
+        for uci in ucis {
+            board.push_uci(uci).unwrap();
+        }
+        board
+    }
+
+    fn board(fen: &str) -> Board {
+        pleco::Board::from_fen(fen).unwrap().into()
+    }
+
+    #[test]
+    fn transpositions_are_equal_and_hash_alike() {
+        let knights_first = played(&["g1f3", "g8f6", "b1c3", "b8c6"]);
+        let queen_side_first = played(&["b1c3", "b8c6", "g1f3", "g8f6"]);
+        assert!(knights_first == queen_side_first);
+        assert_eq!(knights_first.__hash__(), queen_side_first.__hash__());
+        assert_ne!(knights_first.moves, queen_side_first.moves);
+
+        // Equal boards hash alike, though the halfmove clock tells these two apart
+        let pawn_last = played(&["g1f3", "g8f6", "e2e4"]);
+        let knight_last = played(&["e2e4", "g8f6", "g1f3"]);
+        assert!(pawn_last != knight_last);
+        assert_eq!(pawn_last.__hash__(), knight_last.__hash__());
+
+        let copy = knights_first.__copy__();
+        assert!(copy == knights_first);
+        assert_eq!(copy.moves, knights_first.moves);
+    }
+
+    #[test]
+    fn mirror_round_trips() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k3/8/8/8/8/8/8/4K2R b Kq - 7 30",
+            "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2",
+        ] {
+            let original = board(fen);
+            let mirrored = original.mirror();
+            assert!(mirrored != original);
+            assert!(mirrored.mirror() == original, "{}", fen);
+            assert_eq!(mirrored.inner.turn(), !original.inner.turn());
+        }
+        assert_eq!(
+            board("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2").mirror().fen(),
+            "4k3/8/8/8/3Pp3/8/8/4K3 b - d3 0 2"
+        );
+        assert_eq!(
+            board("r3k3/8/8/8/8/8/8/4K2R b Kq - 7 30").mirror().fen(),
+            "4k2r/8/8/8/8/8/8/R3K3 w Qk - 7 30"
+        );
+    }
+
+    #[test]
+    fn pushes_uci_and_san() {
+        let mut board = Board::new();
+        assert_eq!(board.push_uci("E2E4").unwrap().to_string(), "e2e4");
+        assert_eq!(board.push_san("e5").unwrap().to_string(), "e7e5");
+        assert_eq!(board.push_san("Nf3+").unwrap().to_string(), "g1f3");
+        assert_eq!(board.move_stack().len(), 3);
+
+        // Illegal, malformed and ambiguous moves leave the board as it was
+        let before = board.fen();
+        for uci in ["e2e4", "e1g1", "e7e5", "a1a9", ""] {
+            assert!(board.push_uci(uci).is_err(), "{}", uci);
+        }
+        for san in ["Nf3", "Ke2", "Qxf7", "e4", "O-O", "exd5", "", "Z"] {
+            assert!(board.push_san(san).is_err(), "{}", san);
+        }
+        assert_eq!(board.fen(), before);
+        assert_eq!(board.move_stack().len(), 3);
+
+        let mut knights = self::board("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1");
+        assert!(knights.push_san("Nd2").is_err());
+        assert_eq!(knights.push_san("Nbd2").unwrap().to_string(), "b1d2");
+    }
+
+    #[test]
+    fn undo_needs_a_move() {
+        let mut board = self::board("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        assert!(board.undo_move().is_err());
+        board.push_uci("e2e4").unwrap();
+        assert_eq!(board.undo_move().unwrap().to_string(), "e2e4");
+        assert!(board.undo_move().is_err());
+        assert_eq!(board.fen(), "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        assert_eq!(board.position_history().len(), 1);
+    }
+
+    #[test]
+    fn detects_threefold_repetition() {
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        let mut board = played(&shuffle);
+        assert_eq!(board.repetition_count(), 2);
+        assert!(!board.is_repetition(3));
+        for uci in shuffle {
+            board.push_uci(uci).unwrap();
+        }
+        assert_eq!(board.repetition_count(), 3);
+        assert!(board.is_repetition(3));
+        let history = board.position_history();
+        assert_eq!(history.len(), 9);
+        assert_eq!(history[0], history[8]);
+        assert_eq!(history.last(), Some(&board.zobrist()));
+
+        board.undo_move().unwrap();
+        assert_eq!(board.repetition_count(), 2);
+        assert!(board.is_repetition(2));
+        assert_eq!(board.root_fen(), pleco::Board::start_pos().fen());
+    }
+}
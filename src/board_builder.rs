@@ -0,0 +1,428 @@
+use pleco::helper::prelude::{
+    bishop_moves, king_moves, knight_moves, pawn_attacks_from, queen_moves, rook_moves,
+};
+use pyo3::prelude::*;
+
+use crate::board::Board;
+use crate::core::{CastleType, Piece, Player};
+use crate::error::invalid_position;
+use crate::square::Square;
+
+// pleco counts plies from the fullmove number in a u16
+const MAX_FULLMOVE_NUMBER: u16 = u16::MAX / 2 + 1;
+
+/// Builds a position square by square.
+///
+/// Nothing is checked until `build()`, which reports every problem with the position at once.
+#[pyclass]
+#[derive(Clone)]
+pub struct BoardBuilder {
+    squares: [Option<pleco::Piece>; 64],
+    turn: pleco::Player,
+    // Indexed by [player][castle type]
+    castling: [[bool; 2]; 2],
+    ep_square: Option<pleco::SQ>,
+    halfmove_clock: u16,
+    fullmove_number: u16,
+}
+
+#[pymethods]
+impl BoardBuilder {
+    /// An empty board with white to move.
+    #[new]
+    pub fn new() -> Self {
+        Self {
+            squares: [None; 64],
+            turn: pleco::Player::White,
+            castling: [[false; 2]; 2],
+            ep_square: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("BoardBuilder({})", self.fen())
+    }
+
+    /// Starts from an existing position.
+    #[staticmethod]
+    pub fn from_board(board: &Board) -> Self {
+        let board = board.inner();
+        let mut builder = Self::new();
+        for (sq, piece) in board.get_piece_locations() {
+            builder.squares[sq.0 as usize] = Some(piece);
+        }
+        builder.turn = board.turn();
+        for player in [pleco::Player::White, pleco::Player::Black] {
+            for castle_type in [
+                pleco::core::CastleType::KingSide,
+                pleco::core::CastleType::QueenSide,
+            ] {
+                builder.castling[player as usize][castle_type as usize] =
+                    board.can_castle(player, castle_type);
+            }
+        }
+        let ep_square = board.ep_square();
+        builder.ep_square = (ep_square != pleco::SQ::NONE).then_some(ep_square);
+        // The move counters are only tracked through the FEN
+        let fen = board.fen();
+        let counters = fen.split_whitespace().skip(4).collect::<Vec<_>>();
+        builder.halfmove_clock = counters[0].parse().unwrap_or_default();
+        builder.fullmove_number = counters[1].parse().unwrap_or(1);
+        builder
+    }
+
+    /// Puts `piece` on `sq`, replacing whatever was there. `Piece.None` empties the square.
+    pub fn set_piece(&mut self, sq: Square, piece: Piece) {
+        let piece = pleco::Piece::from(piece);
+        self.squares[pleco::SQ::from(sq).0 as usize] =
+            (piece != pleco::Piece::None).then_some(piece);
+    }
+
+    /// Empties `sq`, returning the piece that was on it.
+    pub fn remove_piece(&mut self, sq: Square) -> Piece {
+        self.squares[pleco::SQ::from(sq).0 as usize]
+            .take()
+            .unwrap_or(pleco::Piece::None)
+            .into()
+    }
+
+    pub fn piece_at(&self, sq: Square) -> Piece {
+        self.squares[pleco::SQ::from(sq).0 as usize]
+            .unwrap_or(pleco::Piece::None)
+            .into()
+    }
+
+    /// Removes every piece and castling right and the en passant square.
+    pub fn clear(&mut self) {
+        self.squares = [None; 64];
+        self.castling = [[false; 2]; 2];
+        self.ep_square = None;
+    }
+
+    pub fn set_turn(&mut self, player: Player) {
+        self.turn = player.into();
+    }
+
+    pub fn set_castling(&mut self, player: Player, castle_type: CastleType, allowed: bool) {
+        let player = pleco::Player::from(player);
+        let castle_type = pleco::core::CastleType::from(castle_type);
+        self.castling[player as usize][castle_type as usize] = allowed;
+    }
+
+    #[pyo3(signature = (sq=None))]
+    pub fn set_ep_square(&mut self, sq: Option<Square>) {
+        self.ep_square = sq.map(|sq| sq.into());
+    }
+
+    pub fn set_halfmove_clock(&mut self, halfmove_clock: u16) {
+        self.halfmove_clock = halfmove_clock;
+    }
+
+    pub fn set_fullmove_number(&mut self, fullmove_number: u16) {
+        self.fullmove_number = fullmove_number;
+    }
+
+    /// The FEN of the position as it stands, whether or not it is valid.
+    pub fn fen(&self) -> String {
+        let turn = match self.turn {
+            pleco::Player::White => "w",
+            pleco::Player::Black => "b",
+        };
+
+        let mut castling = String::new();
+        for (player, rights) in [(pleco::Player::White, "KQ"), (pleco::Player::Black, "kq")] {
+            for (castle_type, right) in rights.chars().enumerate() {
+                if self.castling[player as usize][castle_type] {
+                    castling.push(right);
+                }
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+
+        let ep_square = self.ep_square.map_or("-".to_string(), |sq| sq.to_string());
+
+        format!(
+            "{} {} {} {} {} {}",
+            placement_field(&self.squares),
+            turn,
+            castling,
+            ep_square,
+            self.halfmove_clock,
+            self.fullmove_number
+        )
+    }
+
+    /// Validates the position and builds it. Raises `InvalidPositionError` listing every problem.
+    pub fn build(&self) -> PyResult<Board> {
+        let mut problems = self.problems();
+        // problems() makes every check pleco does, and pleco panics on a board without both
+        // kings, so it is only asked once the position is known to be valid
+        if problems.is_empty() {
+            match pleco::Board::from_fen(&self.fen()) {
+                Ok(board) => return Ok(board.into()),
+                Err(err) => problems.push(format!("{:?}", err)),
+            }
+        }
+        Err(invalid_position(problems))
+    }
+}
+
+impl BoardBuilder {
+    fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        let piece_at = |sq: pleco::SQ| self.squares.get(sq.0 as usize).copied().flatten();
+
+        for player in [pleco::Player::White, pleco::Player::Black] {
+            let count = |piece_type| {
+                let piece = pleco::Piece::make_lossy(player, piece_type);
+                self.squares.iter().filter(|sq| **sq == Some(piece)).count()
+            };
+            let kings = count(pleco::PieceType::K);
+            if kings != 1 {
+                problems.push(format!("{} has {} kings", player, kings));
+            }
+            let pawns = count(pleco::PieceType::P);
+            if pawns > 8 {
+                problems.push(format!("{} has {} pawns", player, pawns));
+            }
+            let pieces = self
+                .squares
+                .iter()
+                .flatten()
+                .filter(|piece| piece.player_lossy() == player)
+                .count();
+            if pieces > 16 {
+                problems.push(format!("{} has {} pieces", player, pieces));
+            }
+        }
+
+        for (idx, piece) in self.squares.iter().enumerate() {
+            let sq = pleco::SQ(idx as u8);
+            if piece.map(|piece| piece.type_of()) == Some(pleco::PieceType::P)
+                && matches!(sq.rank_idx_of_sq(), 0 | 7)
+            {
+                problems.push(format!("pawn on back rank at {}", sq));
+            }
+        }
+
+        for (player, back_rank) in [(pleco::Player::White, 0), (pleco::Player::Black, 56)] {
+            let king = pleco::Piece::make_lossy(player, pleco::PieceType::K);
+            let rook = pleco::Piece::make_lossy(player, pleco::PieceType::R);
+            for (castle_type, rook_file, name) in [(0, 7, "king side"), (1, 0, "queen side")] {
+                if !self.castling[player as usize][castle_type] {
+                    continue;
+                }
+                if piece_at(pleco::SQ(back_rank + 4)) != Some(king)
+                    || piece_at(pleco::SQ(back_rank + rook_file)) != Some(rook)
+                {
+                    problems.push(format!(
+                        "{} cannot castle {} without king and rook on their starting squares",
+                        player, name
+                    ));
+                }
+            }
+        }
+
+        if let Some(ep_square) = self.ep_square {
+            // The square a pawn skipped over, the pawn now being one rank further
+            let (ep_rank, pawn_offset, mover) = match self.turn {
+                pleco::Player::White => (5, -8, pleco::Player::Black),
+                pleco::Player::Black => (2, 8, pleco::Player::White),
+            };
+            let pawn_sq = pleco::SQ((ep_square.0 as i8 + pawn_offset) as u8);
+            let origin_sq = pleco::SQ((ep_square.0 as i8 - pawn_offset) as u8);
+            if ep_square.rank_idx_of_sq() != ep_rank
+                || piece_at(ep_square).is_some()
+                || piece_at(origin_sq).is_some()
+                || piece_at(pawn_sq) != Some(pleco::Piece::make_lossy(mover, pleco::PieceType::P))
+            {
+                problems.push(format!("{} is not a valid en passant square", ep_square));
+            }
+        }
+
+        let turn = self.turn;
+        let waiting = turn.other_player();
+        if !self.checkers(waiting).is_empty() {
+            problems.push(format!("{} is in check but not to move", waiting));
+        }
+        let checkers = self.checkers(turn);
+        if checkers.len() > 2 {
+            problems.push(format!("{} is in check by {} pieces", turn, checkers.len()));
+        } else if checkers.len() == 2 {
+            // No single move uncovers one of these checks while giving the other
+            use pleco::PieceType::{B, N, P};
+            if let [P, P | B | N] | [B | N, P] | [B, B] | [N, N] = checkers[..] {
+                problems.push(format!(
+                    "{} cannot be in check by a {} and a {} at once",
+                    turn,
+                    checkers[0].to_string().to_lowercase(),
+                    checkers[1].to_string().to_lowercase()
+                ));
+            }
+        }
+
+        if self.halfmove_clock > i16::MAX as u16 {
+            problems.push(format!("halfmove clock must be at most {}", i16::MAX));
+        }
+        if self.fullmove_number == 0 {
+            problems.push("fullmove number must be at least 1".to_string());
+        } else if self.fullmove_number > MAX_FULLMOVE_NUMBER {
+            problems.push(format!(
+                "fullmove number must be at most {}",
+                MAX_FULLMOVE_NUMBER
+            ));
+        }
+        problems
+    }
+
+    // The pieces giving check to the king of `player`, if it has exactly one
+    fn checkers(&self, player: pleco::Player) -> Vec<pleco::PieceType> {
+        let king = Some(pleco::Piece::make_lossy(player, pleco::PieceType::K));
+        let mut kings = (0..64u8).filter(|&idx| self.squares[idx as usize] == king);
+        let king_sq = match (kings.next(), kings.next()) {
+            (Some(idx), None) => pleco::SQ(idx).to_bb(),
+            _ => return vec![],
+        };
+
+        let occupied = (0..64u8)
+            .filter(|&idx| self.squares[idx as usize].is_some())
+            .fold(pleco::BitBoard(0), |bb, idx| bb | pleco::SQ(idx).to_bb());
+        let mut checkers = vec![];
+        for (idx, piece) in self.squares.iter().enumerate() {
+            let piece = match piece {
+                Some(piece) if piece.player_lossy() != player => *piece,
+                _ => continue,
+            };
+            let sq = pleco::SQ(idx as u8);
+            let attacks = match piece.type_of() {
+                pleco::PieceType::P => pawn_attacks_from(sq, !player),
+                pleco::PieceType::N => knight_moves(sq),
+                pleco::PieceType::B => bishop_moves(occupied, sq),
+                pleco::PieceType::R => rook_moves(occupied, sq),
+                pleco::PieceType::Q => queen_moves(occupied, sq),
+                _ => king_moves(sq),
+            };
+            if (attacks & king_sq).is_not_empty() {
+                checkers.push(piece.type_of());
+            }
+        }
+        checkers
+    }
+}
+
+impl Default for BoardBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The piece placement field of a FEN, rank 8 first.
+pub fn placement_field(squares: &[Option<pleco::Piece>; 64]) -> String {
+    let mut ranks = vec![];
+    for rank in (0..8).rev() {
+        let mut row = String::new();
+        let mut empty = 0;
+        for file in 0..8 {
+            match squares[rank * 8 + file].and_then(|piece| piece.character()) {
+                Some(c) => {
+                    if empty > 0 {
+                        row.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    row.push(c);
+                }
+                None => empty += 1,
+            }
+        }
+        if empty > 0 {
+            row.push_str(&empty.to_string());
+        }
+        ranks.push(row);
+    }
+    ranks.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pleco::Piece::{
+        BlackKing, BlackKnight, BlackPawn, BlackQueen, BlackRook, WhiteKing, WhitePawn, WhiteRook,
+    };
+
+    fn builder(pieces: &[(&str, pleco::Piece)]) -> BoardBuilder {
+        let mut builder = BoardBuilder::new();
+        for (sq, piece) in pieces {
+            let sq = sq.as_bytes();
+            let idx = (sq[0] - b'a') + 8 * (sq[1] - b'1');
+            builder.squares[idx as usize] = Some(*piece);
+        }
+        builder
+    }
+
+    #[test]
+    fn builds_valid_positions() {
+        let mut builder = builder(&[("e1", WhiteKing), ("e2", WhitePawn), ("e8", BlackKing)]);
+        builder.castling[1][0] = false;
+        builder.turn = pleco::Player::Black;
+        builder.fullmove_number = 12;
+        assert!(builder.problems().is_empty());
+        let board = builder.build().unwrap();
+        assert_eq!(board.inner().fen(), "4k3/8/8/8/8/8/4P3/4K3 b - - 0 12");
+    }
+
+    #[test]
+    fn reports_every_problem_at_once() {
+        let mut builder = builder(&[("e1", WhiteKing), ("a8", WhitePawn), ("e8", BlackKing)]);
+        // Black is not to move but in check from the rook on e4
+        builder.squares[28] = Some(WhiteRook);
+        builder.castling[1][1] = true;
+        builder.ep_square = Some(pleco::SQ(44));
+        builder.fullmove_number = 0;
+        builder.halfmove_clock = u16::MAX;
+        assert_eq!(
+            builder.problems(),
+            [
+                "pawn on back rank at a8",
+                "Black cannot castle queen side without king and rook on their starting squares",
+                "e6 is not a valid en passant square",
+                "Black is in check but not to move",
+                "halfmove clock must be at most 32767",
+                "fullmove number must be at least 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_positions_without_kings() {
+        let builder = builder(&[("e2", WhitePawn), ("e7", BlackPawn), ("d8", BlackKing)]);
+        assert_eq!(builder.problems(), ["White has 0 kings"]);
+        assert_eq!(
+            BoardBuilder::new().problems(),
+            ["White has 0 kings", "Black has 0 kings"]
+        );
+    }
+
+    #[test]
+    fn rejects_impossible_checks() {
+        let double = [("e1", WhiteKing), ("e8", BlackKing), ("d3", BlackKnight)];
+        let mut knights = builder(&double);
+        knights.squares[21] = Some(BlackKnight);
+        assert_eq!(
+            knights.problems(),
+            ["White cannot be in check by a knight and a knight at once"]
+        );
+
+        let mut discovered = builder(&double);
+        discovered.squares[4 + 8 * 4] = Some(BlackRook);
+        assert!(discovered.problems().is_empty());
+
+        let mut triple = builder(&double);
+        triple.squares[4 + 8 * 4] = Some(BlackRook);
+        triple.squares[0] = Some(BlackQueen);
+        assert_eq!(triple.problems(), ["White is in check by 3 pieces"]);
+    }
+}
@@ -0,0 +1,249 @@
+use clap::Args;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::PathBuf;
+
+use gym_chess_pleco::dataset::{game_records, DatasetWriter, Outcome};
+use gym_chess_pleco::pgn::{PgnGame, PgnReader};
+
+// Moves assumed per game when turning a `base+increment` time control into a duration
+const ESTIMATED_MOVES: u32 = 40;
+
+#[derive(Args)]
+pub struct ConvertArgs {
+    /// PGN files to read
+    #[arg(required = true)]
+    pgn: Vec<PathBuf>,
+
+    /// Directory the shards are written to
+    #[arg(short, long)]
+    output: String,
+
+    #[arg(long, default_value = "shard")]
+    prefix: String,
+
+    #[arg(long, default_value_t = 100_000)]
+    records_per_shard: usize,
+
+    /// Skip games where either player is rated below this (or unrated)
+    #[arg(long)]
+    min_elo: Option<u32>,
+
+    /// Skip games where either player is rated above this (or unrated)
+    #[arg(long)]
+    max_elo: Option<u32>,
+
+    /// Keep only games with one of these `TimeControl` tags, e.g. `600+5` (repeatable)
+    #[arg(long)]
+    time_control: Vec<String>,
+
+    /// Skip games whose estimated duration (base + 40 * increment) is shorter than this
+    #[arg(long)]
+    min_seconds: Option<u32>,
+}
+
+#[derive(Default)]
+struct Stats {
+    games: usize,
+    converted: usize,
+    filtered: usize,
+    unfinished: usize,
+    illegal: usize,
+    unreadable: usize,
+    positions: usize,
+}
+
+pub fn run(args: ConvertArgs) -> io::Result<()> {
+    let mut writer = DatasetWriter::create(&args.output, &args.prefix, args.records_per_shard)?;
+    let mut stats = Stats::default();
+
+    for path in &args.pgn {
+        let file = File::open(path)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+        for game in PgnReader::new(BufReader::new(file)) {
+            let game = match game {
+                Ok(game) => game,
+                Err(err) => {
+                    // The reader stops at the error, so the rest of this file is lost
+                    stats.unreadable += 1;
+                    eprintln!("{}: stopped reading: {}", path.display(), err);
+                    continue;
+                }
+            };
+            stats.games += 1;
+
+            if !args.accepts(&game) {
+                stats.filtered += 1;
+                continue;
+            }
+            let outcome = match game.tag("Result").and_then(Outcome::from_pgn) {
+                Some(outcome) => outcome,
+                None => {
+                    stats.unfinished += 1;
+                    continue;
+                }
+            };
+
+            let mut moves = vec![];
+            let records = game
+                .replay(|_, bit_move| moves.push(bit_move))
+                .map_err(|err| err.to_string())
+                .and_then(|_| {
+                    let board = game.start_board().map_err(|err| err.to_string())?;
+                    game_records(board, &moves, outcome, None).map_err(|err| err.to_string())
+                });
+            let records = match records {
+                Ok(records) => records,
+                Err(err) => {
+                    stats.illegal += 1;
+                    eprintln!("{}: skipping game {}: {}", path.display(), stats.games, err);
+                    continue;
+                }
+            };
+
+            for record in &records {
+                writer.write(record)?;
+            }
+            stats.converted += 1;
+            stats.positions += records.len();
+        }
+    }
+    writer.finish()?;
+
+    println!(
+        "{} games read: {} converted, {} filtered, {} unfinished, {} with illegal moves",
+        stats.games, stats.converted, stats.filtered, stats.unfinished, stats.illegal
+    );
+    if stats.unreadable > 0 {
+        println!("{} files could not be read to the end", stats.unreadable);
+    }
+    println!(
+        "{} positions written to {} shards in {}",
+        stats.positions,
+        writer.shards.len(),
+        args.output
+    );
+    Ok(())
+}
+
+impl ConvertArgs {
+    fn accepts(&self, game: &PgnGame) -> bool {
+        if self.min_elo.is_some() || self.max_elo.is_some() {
+            for tag in ["WhiteElo", "BlackElo"] {
+                let elo = match game.tag(tag).and_then(|elo| elo.parse::<u32>().ok()) {
+                    Some(elo) => elo,
+                    None => return false,
+                };
+                if self.min_elo.is_some_and(|min| elo < min)
+                    || self.max_elo.is_some_and(|max| elo > max)
+                {
+                    return false;
+                }
+            }
+        }
+
+        let time_control = game.tag("TimeControl");
+        if !self.time_control.is_empty()
+            && !time_control.is_some_and(|tc| self.time_control.iter().any(|t| t == tc))
+        {
+            return false;
+        }
+        if let Some(min_seconds) = self.min_seconds {
+            match time_control.and_then(estimated_seconds) {
+                Some(seconds) if seconds >= min_seconds => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+fn estimated_seconds(time_control: &str) -> Option<u32> {
+    let (base, increment) = match time_control.split_once('+') {
+        Some((base, increment)) => (base, increment.parse::<u32>().ok()?),
+        None => (time_control, 0),
+    };
+    // The tag comes from the file, so sums too large for a u32 are treated as unreadable
+    ESTIMATED_MOVES
+        .checked_mul(increment)?
+        .checked_add(base.parse::<u32>().ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_filters() -> ConvertArgs {
+        ConvertArgs {
+            pgn: vec![],
+            output: String::new(),
+            prefix: String::new(),
+            records_per_shard: 1,
+            min_elo: None,
+            max_elo: None,
+            time_control: vec![],
+            min_seconds: None,
+        }
+    }
+
+    fn game(tags: &[(&str, &str)]) -> PgnGame {
+        PgnGame {
+            tags: tags
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            moves: vec![],
+        }
+    }
+
+    #[test]
+    fn estimates_time_controls() {
+        assert_eq!(estimated_seconds("600"), Some(600));
+        assert_eq!(estimated_seconds("180+2"), Some(260));
+        assert_eq!(estimated_seconds("0+1"), Some(40));
+        assert_eq!(estimated_seconds("-"), None);
+        assert_eq!(estimated_seconds("40/7200:3600"), None);
+        assert_eq!(estimated_seconds("600+"), None);
+        assert_eq!(estimated_seconds("600+200000000"), None);
+        assert_eq!(estimated_seconds("4294967295+1"), None);
+        assert_eq!(estimated_seconds("4294967255+1"), Some(u32::MAX));
+    }
+
+    #[test]
+    fn filters_by_rating() {
+        let args = ConvertArgs {
+            min_elo: Some(2000),
+            max_elo: Some(2500),
+            ..no_filters()
+        };
+        let rated = |white, black| game(&[("WhiteElo", white), ("BlackElo", black)]);
+        assert!(args.accepts(&rated("2000", "2500")));
+        assert!(!args.accepts(&rated("1999", "2200")));
+        assert!(!args.accepts(&rated("2200", "2501")));
+        assert!(!args.accepts(&rated("2200", "?")));
+        assert!(!args.accepts(&game(&[("WhiteElo", "2200")])));
+        assert!(no_filters().accepts(&game(&[])));
+    }
+
+    #[test]
+    fn filters_by_time_control() {
+        let listed = ConvertArgs {
+            time_control: vec!["600+5".to_string(), "180+2".to_string()],
+            ..no_filters()
+        };
+        assert!(listed.accepts(&game(&[("TimeControl", "180+2")])));
+        assert!(!listed.accepts(&game(&[("TimeControl", "180+0")])));
+        assert!(!listed.accepts(&game(&[])));
+
+        let long = ConvertArgs {
+            min_seconds: Some(600),
+            ..no_filters()
+        };
+        assert!(long.accepts(&game(&[("TimeControl", "600")])));
+        assert!(long.accepts(&game(&[("TimeControl", "300+8")])));
+        assert!(!long.accepts(&game(&[("TimeControl", "300+7")])));
+        assert!(!long.accepts(&game(&[("TimeControl", "600+200000000")])));
+        assert!(!long.accepts(&game(&[("TimeControl", "-")])));
+        assert!(!long.accepts(&game(&[])));
+    }
+}
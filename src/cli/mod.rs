@@ -0,0 +1,4 @@
+pub mod convert;
+pub mod play;
+pub mod sprt;
+pub mod tournament;
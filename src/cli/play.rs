@@ -0,0 +1,187 @@
+use clap::{Args, ValueEnum};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use gym_chess_pleco::dataset::Outcome;
+use gym_chess_pleco::engine::{Bot, Engine, EngineSpec, DEFAULT_DEPTH};
+use gym_chess_pleco::game::{today, GameState};
+use gym_chess_pleco::pgn::parse_san;
+use gym_chess_pleco::render::ansi;
+
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+#[derive(Args)]
+pub struct PlayArgs {
+    /// The side you play
+    #[arg(long, value_enum, default_value_t = Side::White)]
+    color: Side,
+
+    /// Position to start from instead of the standard one
+    #[arg(long)]
+    fen: Option<String>,
+
+    /// Built-in pleco searcher to play against: random, minimax, alphabeta, jamboree or iterative
+    #[arg(long, value_parser = str::parse::<Bot>, default_value = "alphabeta")]
+    bot: Bot,
+
+    /// Search depth of the built-in searcher
+    #[arg(long, default_value_t = DEFAULT_DEPTH)]
+    depth: u16,
+
+    /// Path to a UCI engine to play against instead of the built-in searcher
+    #[arg(long)]
+    engine: Option<PathBuf>,
+
+    /// Thinking time per move of the UCI engine, in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    movetime: u64,
+
+    /// Where the game is saved on exit
+    #[arg(long, default_value = "game.pgn")]
+    pgn: PathBuf,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Side {
+    White,
+    Black,
+}
+
+impl From<Side> for pleco::Player {
+    fn from(side: Side) -> pleco::Player {
+        match side {
+            Side::White => pleco::Player::White,
+            Side::Black => pleco::Player::Black,
+        }
+    }
+}
+
+pub fn run(args: PlayArgs) -> io::Result<()> {
+    let board = match &args.fen {
+        Some(fen) => pleco::Board::from_fen(fen).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid FEN: {}", format!("{:?}", err).trim()),
+            )
+        })?,
+        None => pleco::Board::start_pos(),
+    };
+    let spec = match &args.engine {
+        Some(path) => EngineSpec::Uci {
+            path: path.clone(),
+            movetime: args.movetime,
+        },
+        None => EngineSpec::Bot {
+            bot: args.bot,
+            depth: args.depth,
+        },
+    };
+    let mut opponent = spec.spawn()?;
+    let human = pleco::Player::from(args.color);
+    let mut game = GameState::new(board);
+    // The game is saved however it ends, even when the opponent or the terminal fails
+    let played = play(
+        &mut game,
+        opponent.as_mut(),
+        human,
+        args.color == Side::Black,
+    );
+    let result = *played.as_ref().unwrap_or(&"*");
+
+    let opponent_name = opponent.name();
+    let (white, black) = match human {
+        pleco::Player::White => ("Human", opponent_name.as_str()),
+        pleco::Player::Black => (opponent_name.as_str(), "Human"),
+    };
+    let tags = vec![
+        ("Event".to_string(), "Casual game".to_string()),
+        ("Site".to_string(), "gym-chess-pleco".to_string()),
+        ("Date".to_string(), today()),
+        ("White".to_string(), white.to_string()),
+        ("Black".to_string(), black.to_string()),
+        ("Result".to_string(), result.to_string()),
+    ];
+    fs::write(&args.pgn, game.to_pgn(tags).to_string())?;
+    println!("Result {}. Game saved to {}", result, args.pgn.display());
+    played.map(|_| ())
+}
+
+// Plays until the game ends or the human stops, returning the PGN result
+fn play(
+    game: &mut GameState,
+    opponent: &mut dyn Engine,
+    human: pleco::Player,
+    mut flipped: bool,
+) -> io::Result<&'static str> {
+    let mut message = String::from("Enter moves in SAN or UCI. Type help for commands.");
+    let mut input = io::stdin().lock();
+
+    loop {
+        print!(
+            "{}{}\n{}\n",
+            CLEAR_SCREEN,
+            ansi(&game.board, game.moves.last().copied(), flipped),
+            status(game)
+        );
+        if let Some((outcome, reason)) = game.outcome() {
+            println!("Game over: {} ({})", outcome.to_pgn(), reason);
+            return Ok(outcome.to_pgn());
+        }
+
+        if game.board.turn() != human {
+            println!("{} is thinking...", opponent.name());
+            let bit_move = opponent.best_move(game)?;
+            game.push(bit_move);
+            continue;
+        }
+
+        println!("{}", message);
+        print!("> ");
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            // End of input leaves the game unfinished
+            return Ok("*");
+        }
+        message.clear();
+        match line.trim() {
+            "" => {}
+            "help" => {
+                message = "Commands: undo, flip, resign, quit. Anything else is read as a move."
+                    .to_string()
+            }
+            "flip" => flipped = !flipped,
+            "quit" => return Ok("*"),
+            "resign" => {
+                return Ok(match human {
+                    pleco::Player::White => Outcome::BlackWins.to_pgn(),
+                    pleco::Player::Black => Outcome::WhiteWins.to_pgn(),
+                })
+            }
+            "undo" => {
+                // Takes back the opponent's reply too, so it is the human's turn again
+                if !game.undo() {
+                    message = "No move to undo.".to_string();
+                } else if game.board.turn() != human {
+                    game.undo();
+                }
+            }
+            text => match game.find_uci(text).or_else(|| parse_san(&game.board, text)) {
+                Some(bit_move) => game.push(bit_move),
+                None => message = format!("Illegal or unreadable move: {}", text),
+            },
+        }
+    }
+}
+
+fn status(game: &GameState) -> String {
+    let mut status = format!("{} to move", game.board.turn());
+    if game.board.in_check() {
+        status.push_str(", in check");
+    }
+    if let Some(san) = game.sans.last() {
+        status.push_str(&format!(". Last move: {}", san));
+    }
+    status
+}
@@ -0,0 +1,137 @@
+use clap::Args;
+use std::io;
+use std::path::PathBuf;
+
+use gym_chess_pleco::engine::EngineSpec;
+use gym_chess_pleco::pgn::PgnWriter;
+use gym_chess_pleco::tablebase::Tablebase;
+use gym_chess_pleco::tournament::sprt::Sprt;
+use gym_chess_pleco::tournament::{setup, Participant};
+
+#[derive(Args)]
+pub struct SprtArgs {
+    /// The engine under test, as `bot:<searcher>[:<depth>]` or `uci:<path>`; `name=` in front
+    /// renames it
+    #[arg(long)]
+    candidate: String,
+
+    /// The engine it is compared against, in the same form
+    #[arg(long)]
+    baseline: String,
+
+    /// Elo difference under the null hypothesis
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    elo0: f64,
+
+    /// Elo difference under the alternative hypothesis
+    #[arg(long, default_value_t = 5.0, allow_negative_numbers = true)]
+    elo1: f64,
+
+    /// False positive rate
+    #[arg(long, default_value_t = 0.05)]
+    alpha: f64,
+
+    /// False negative rate
+    #[arg(long, default_value_t = 0.05)]
+    beta: f64,
+
+    /// Game pairs played at once
+    #[arg(long, default_value_t = 1)]
+    threads: usize,
+
+    /// Stop without a decision after this many game pairs
+    #[arg(long)]
+    max_pairs: Option<usize>,
+
+    /// Thinking time per move of UCI engines, in milliseconds
+    #[arg(long, default_value_t = 100)]
+    movetime: u64,
+
+    /// Opening positions, from an EPD file or a PGN book
+    #[arg(long)]
+    openings: Option<String>,
+
+    /// Moves kept from every game of a PGN book
+    #[arg(long, default_value_t = 8)]
+    book_plies: usize,
+
+    /// Games reaching this many plies are drawn
+    #[arg(long, default_value_t = 400)]
+    max_plies: usize,
+
+    /// Directory of Syzygy tables used to decide games once few pieces are left
+    #[arg(long)]
+    tablebase: Option<String>,
+
+    /// Where the games are saved
+    #[arg(long, default_value = "sprt.pgn")]
+    pgn: PathBuf,
+}
+
+pub fn run(args: SprtArgs) -> io::Result<()> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidInput, msg);
+    let participant = |player: &str| -> io::Result<Participant> {
+        let (name, spec) = match player.split_once('=') {
+            Some((name, spec)) => (Some(name.to_string()), spec),
+            None => (None, player),
+        };
+        let mut spec = spec.parse::<EngineSpec>().map_err(invalid)?;
+        if let EngineSpec::Uci { movetime, .. } = &mut spec {
+            *movetime = args.movetime;
+        }
+        Ok(Participant::new(spec, name))
+    };
+
+    let tablebase = args.tablebase.as_deref().map(Tablebase::open).transpose()?;
+    let (openings, adjudication) = setup(
+        args.openings.as_deref(),
+        args.book_plies,
+        args.max_plies,
+        tablebase,
+        None,
+    )?;
+    let sprt = Sprt::new(
+        participant(&args.candidate)?,
+        participant(&args.baseline)?,
+        (args.elo0, args.elo1),
+        (args.alpha, args.beta),
+        openings,
+        args.threads,
+        args.max_pairs,
+        adjudication,
+    )
+    .map_err(invalid)?;
+
+    let (lower, upper) = sprt.bounds();
+    let mut writer = PgnWriter::create(&args.pgn)?;
+    let result = sprt.run(|status, games| {
+        println!(
+            "Pairs {}: +{} ={} -{}, LLR {:.2} ({:.2}, {:.2})",
+            status.pairs(),
+            status.wins,
+            status.draws,
+            status.losses,
+            status.llr,
+            lower,
+            upper
+        );
+        games.iter().try_for_each(|game| writer.write(game))
+    })?;
+
+    let status = &result.status;
+    let (elo, error) = status.elo();
+    println!();
+    println!("Decision: {}", result.decision.name());
+    println!("LLR: {:.2} ({:.2}, {:.2})", status.llr, lower, upper);
+    println!("Elo: {:+.1} +/- {:.1}", elo, error);
+    println!(
+        "Games: {} (+{} ={} -{})",
+        2 * status.pairs(),
+        status.wins,
+        status.draws,
+        status.losses
+    );
+    println!("Pentanomial [0, 0.5, 1, 1.5, 2]: {:?}", status.pentanomial);
+    println!("Games saved to {}", args.pgn.display());
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use clap::Args;
+use std::io;
+use std::path::PathBuf;
+
+use gym_chess_pleco::engine::EngineSpec;
+use gym_chess_pleco::pgn::PgnWriter;
+use gym_chess_pleco::tablebase::Tablebase;
+use gym_chess_pleco::tournament::{setup, Participant, Tournament};
+
+#[derive(Args)]
+pub struct TournamentArgs {
+    /// A participant, as `bot:<searcher>[:<depth>]` or `uci:<path>`; `name=` in front renames
+    /// it (repeatable)
+    #[arg(long = "player", required = true)]
+    players: Vec<String>,
+
+    /// Thinking time per move of UCI engines, in milliseconds
+    #[arg(long, default_value_t = 100)]
+    movetime: u64,
+
+    /// Opening positions, from an EPD file or a PGN book
+    #[arg(long)]
+    openings: Option<String>,
+
+    /// Moves kept from every game of a PGN book
+    #[arg(long, default_value_t = 8)]
+    book_plies: usize,
+
+    /// Times every pairing plays every opening with both colours
+    #[arg(long, default_value_t = 1)]
+    rounds: usize,
+
+    /// Play the first participant against each of the others instead of a round robin
+    #[arg(long)]
+    gauntlet: bool,
+
+    /// Games reaching this many plies are drawn
+    #[arg(long, default_value_t = 400)]
+    max_plies: usize,
+
+    /// Directory of Syzygy tables used to decide games once few pieces are left
+    #[arg(long)]
+    tablebase: Option<String>,
+
+    /// Where the games are saved
+    #[arg(long, default_value = "tournament.pgn")]
+    pgn: PathBuf,
+}
+
+pub fn run(args: TournamentArgs) -> io::Result<()> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidInput, msg);
+
+    let mut participants = vec![];
+    for player in &args.players {
+        let (name, spec) = match player.split_once('=') {
+            Some((name, spec)) => (Some(name.to_string()), spec),
+            None => (None, player.as_str()),
+        };
+        let mut spec = spec.parse::<EngineSpec>().map_err(invalid)?;
+        if let EngineSpec::Uci { movetime, .. } = &mut spec {
+            *movetime = args.movetime;
+        }
+        participants.push(Participant::new(spec, name));
+    }
+    let tablebase = args.tablebase.as_deref().map(Tablebase::open).transpose()?;
+    let (openings, adjudication) = setup(
+        args.openings.as_deref(),
+        args.book_plies,
+        args.max_plies,
+        tablebase,
+        None,
+    )?;
+    let tournament = Tournament::new(
+        participants,
+        openings,
+        args.rounds,
+        args.gauntlet,
+        adjudication,
+    )
+    .map_err(invalid)?;
+
+    let total = tournament.schedule().len();
+    let names = tournament.names();
+    let mut writer = PgnWriter::create(&args.pgn)?;
+    let mut played = 0;
+    let result = tournament.run(|game, pgn| {
+        played += 1;
+        println!(
+            "Game {} of {}: {} - {} {} ({})",
+            played,
+            total,
+            names[game.white],
+            names[game.black],
+            game.outcome.to_pgn(),
+            game.reason
+        );
+        writer.write(pgn)
+    })?;
+
+    println!("\n{}", result.crosstable());
+    println!("Games saved to {}", args.pgn.display());
+    Ok(())
+}
@@ -249,6 +249,13 @@ impl CastleType {
             pleco::core::CastleType::QueenSide => "QueenSide".to_string(),
         }
     }
+
+    #[classattr]
+    #[pyo3(name = "KingSide")]
+    pub const KING_SIDE: CastleType = CastleType(pleco::core::CastleType::KingSide);
+    #[classattr]
+    #[pyo3(name = "QueenSide")]
+    pub const QUEEN_SIDE: CastleType = CastleType(pleco::core::CastleType::QueenSide);
 }
 
 impl From<pleco::core::CastleType> for CastleType {
@@ -0,0 +1,531 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use pyo3::exceptions::PyIOError;
+use pyo3::prelude::*;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::action_space::ActionId;
+use crate::error::{invalid_fen, CustomError, InvalidArgumentError};
+use crate::observation::{encode, unpack, Planes, NUM_PLANES};
+use crate::piece_move::BitMove;
+use crate::ChessEnv;
+
+const SHARD_MAGIC: &[u8; 4] = b"GCPD";
+const SHARD_VERSION: u8 = 1;
+const SHARD_EXTENSION: &str = "gcpd.gz";
+const DEFAULT_RECORDS_PER_SHARD: usize = 100_000;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Outcome {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+impl Outcome {
+    /// Parses a PGN result tag. Unfinished games (`*`) have no outcome.
+    pub fn from_pgn(result: &str) -> Option<Outcome> {
+        match result.trim() {
+            "1-0" => Some(Outcome::WhiteWins),
+            "0-1" => Some(Outcome::BlackWins),
+            "1/2-1/2" => Some(Outcome::Draw),
+            _ => None,
+        }
+    }
+
+    pub fn to_pgn(&self) -> &'static str {
+        match self {
+            Outcome::WhiteWins => "1-0",
+            Outcome::BlackWins => "0-1",
+            Outcome::Draw => "1/2-1/2",
+        }
+    }
+
+    pub fn value_for(&self, player: pleco::Player) -> f32 {
+        match (self, player) {
+            (Outcome::Draw, _) => 0.0,
+            (Outcome::WhiteWins, pleco::Player::White) => 1.0,
+            (Outcome::BlackWins, pleco::Player::Black) => 1.0,
+            _ => -1.0,
+        }
+    }
+}
+
+/// One training example: the encoded position, a sparse policy target over `ActionId`s and the
+/// game result from the perspective of the side to move.
+#[pyclass]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrainingRecord {
+    pub planes: Planes,
+    #[pyo3(get)]
+    pub policy: Vec<(ActionId, f32)>,
+    #[pyo3(get)]
+    pub value: f32,
+}
+
+#[pymethods]
+impl TrainingRecord {
+    fn __repr__(&self) -> String {
+        format!(
+            "TrainingRecord(policy={} actions, value={})",
+            self.policy.len(),
+            self.value
+        )
+    }
+
+    /// The observation as `[plane][rank][file]` 0/1 values.
+    pub fn planes(&self) -> Vec<[[u8; 8]; 8]> {
+        unpack(&self.planes)
+    }
+}
+
+/// Replays `moves` from `board` and builds one record per position.
+///
+/// Without `visits` the policy target is the move that was played; otherwise each position's
+/// visit counts are normalized into a distribution, and every visited action must be legal.
+pub fn game_records(
+    mut board: pleco::Board,
+    moves: &[pleco::BitMove],
+    outcome: Outcome,
+    visits: Option<&[Vec<(ActionId, f32)>]>,
+) -> Result<Vec<TrainingRecord>, CustomError> {
+    if let Some(visits) = visits {
+        if visits.len() != moves.len() {
+            return Err(CustomError::new(&format!(
+                "got {} visit distributions for {} moves",
+                visits.len(),
+                moves.len()
+            )));
+        }
+    }
+
+    let mut records = Vec::with_capacity(moves.len());
+    for (ply, &bit_move) in moves.iter().enumerate() {
+        if !board.pseudo_legal_move(bit_move) || !board.legal_move(bit_move) {
+            return Err(CustomError::new(&format!(
+                "illegal move at ply {}: {}",
+                ply, bit_move
+            )));
+        }
+
+        let policy = match visits.map(|visits| &visits[ply]) {
+            Some(counts) => {
+                let legal = board
+                    .generate_moves()
+                    .iter()
+                    .map(ChessEnv::move_to_action)
+                    .collect::<Vec<_>>();
+                if let Some((action_id, _)) = counts.iter().find(|(id, _)| !legal.contains(id)) {
+                    return Err(CustomError::new(&format!(
+                        "visited action {} at ply {} is not legal in {}",
+                        action_id,
+                        ply,
+                        board.fen()
+                    )));
+                }
+                let total = counts.iter().map(|(_, count)| count).sum::<f32>();
+                // NaN fails every comparison, so it is ruled out by is_finite
+                let bad_count = |count: &f32| !count.is_finite() || *count < 0.0;
+                if counts.iter().any(|(_, count)| bad_count(count))
+                    || !total.is_finite()
+                    || total <= 0.0
+                {
+                    return Err(CustomError::new(&format!(
+                        "visit counts at ply {} must be finite and non-negative with a positive sum",
+                        ply
+                    )));
+                }
+                counts
+                    .iter()
+                    .map(|(action_id, count)| (*action_id, count / total))
+                    .collect()
+            }
+            None => vec![(ChessEnv::move_to_action(&bit_move), 1.0)],
+        };
+
+        records.push(TrainingRecord {
+            planes: encode(&board),
+            policy,
+            value: outcome.value_for(board.turn()),
+        });
+        board.apply_move(bit_move);
+    }
+    Ok(records)
+}
+
+/// Writes records into gzip-compressed shards named `<prefix>-<index>.gcpd.gz`.
+///
+/// Shard layout, little endian, after a `GCPD` magic and a version byte:
+///   planes u64 * 18 | value f32 | policy length u16 | (action u16, probability f32) * length
+#[pyclass]
+pub struct DatasetWriter {
+    directory: PathBuf,
+    prefix: String,
+    records_per_shard: usize,
+    shard: Option<GzEncoder<BufWriter<File>>>,
+    shard_records: usize,
+    #[pyo3(get)]
+    pub shards: Vec<String>,
+    #[pyo3(get)]
+    pub records_written: usize,
+}
+
+impl DatasetWriter {
+    pub fn create(directory: &str, prefix: &str, records_per_shard: usize) -> io::Result<Self> {
+        if records_per_shard == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "records_per_shard must be positive",
+            ));
+        }
+        fs::create_dir_all(directory)?;
+        Ok(Self {
+            directory: PathBuf::from(directory),
+            prefix: prefix.to_string(),
+            records_per_shard,
+            shard: None,
+            shard_records: 0,
+            shards: vec![],
+            records_written: 0,
+        })
+    }
+
+    pub fn write(&mut self, record: &TrainingRecord) -> io::Result<()> {
+        let policy_len = u16::try_from(record.policy.len()).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "a policy holds at most {} actions, got {}",
+                    u16::MAX,
+                    record.policy.len()
+                ),
+            )
+        })?;
+        if self.shard_records == self.records_per_shard {
+            self.finish()?;
+        }
+        let shard = match self.shard.as_mut() {
+            Some(shard) => shard,
+            None => self.open_shard()?,
+        };
+
+        for plane in &record.planes {
+            shard.write_all(&plane.to_le_bytes())?;
+        }
+        shard.write_all(&record.value.to_le_bytes())?;
+        shard.write_all(&policy_len.to_le_bytes())?;
+        for (action_id, probability) in &record.policy {
+            shard.write_all(&action_id.to_le_bytes())?;
+            shard.write_all(&probability.to_le_bytes())?;
+        }
+
+        self.shard_records += 1;
+        self.records_written += 1;
+        Ok(())
+    }
+
+    /// Closes the current shard, if any. The next write starts a new one.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if let Some(shard) = self.shard.take() {
+            shard.finish()?.flush()?;
+        }
+        self.shard_records = 0;
+        Ok(())
+    }
+
+    fn open_shard(&mut self) -> io::Result<&mut GzEncoder<BufWriter<File>>> {
+        let path = self.directory.join(format!(
+            "{}-{:05}.{}",
+            self.prefix,
+            self.shards.len(),
+            SHARD_EXTENSION
+        ));
+        let mut shard =
+            GzEncoder::new(BufWriter::new(File::create(&path)?), Compression::default());
+        shard.write_all(SHARD_MAGIC)?;
+        shard.write_all(&[SHARD_VERSION])?;
+        self.shards.push(path.to_string_lossy().into_owned());
+        Ok(self.shard.insert(shard))
+    }
+}
+
+#[pymethods]
+impl DatasetWriter {
+    #[new]
+    #[pyo3(signature = (directory, prefix="shard", records_per_shard=DEFAULT_RECORDS_PER_SHARD))]
+    pub fn new(directory: &str, prefix: &str, records_per_shard: usize) -> PyResult<Self> {
+        Self::create(directory, prefix, records_per_shard)
+            .map_err(|err| PyIOError::new_err(err.to_string()))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "DatasetWriter({} records in {} shards)",
+            self.records_written,
+            self.shards.len()
+        )
+    }
+
+    /// Writes every position of a finished game. `result` is a PGN result such as `"1-0"`, and
+    /// `visits` optionally holds one list of `(action_id, visit_count)` pairs per move.
+    ///
+    /// Returns the number of records written.
+    #[pyo3(signature = (moves, result, visits=None, start_fen=None))]
+    pub fn write_game(
+        &mut self,
+        moves: Vec<BitMove>,
+        result: &str,
+        visits: Option<Vec<Vec<(ActionId, f32)>>>,
+        start_fen: Option<&str>,
+    ) -> PyResult<usize> {
+        let outcome = Outcome::from_pgn(result).ok_or_else(|| {
+            InvalidArgumentError::new_err(format!("unknown game result: {}", result))
+        })?;
+        let board = match start_fen {
+            Some(fen) => pleco::Board::from_fen(fen).map_err(|err| invalid_fen(fen, err))?,
+            None => pleco::Board::start_pos(),
+        };
+        let moves = moves.into_iter().map(|m| m.into()).collect::<Vec<_>>();
+
+        let records = game_records(board, &moves, outcome, visits.as_deref())
+            .map_err(|err| InvalidArgumentError::new_err(err.to_string()))?;
+        for record in &records {
+            self.write(record)
+                .map_err(|err| PyIOError::new_err(err.to_string()))?;
+        }
+        Ok(records.len())
+    }
+
+    pub fn close(&mut self) -> PyResult<()> {
+        self.finish()
+            .map_err(|err| PyIOError::new_err(err.to_string()))
+    }
+}
+
+/// Streams records back out of a single shard or every shard in a directory, in name order.
+#[pyclass]
+pub struct DatasetReader {
+    paths: std::vec::IntoIter<PathBuf>,
+    shard: Option<GzDecoder<BufReader<File>>>,
+}
+
+impl DatasetReader {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let path = Path::new(path);
+        let mut paths = vec![];
+        if path.is_dir() {
+            for entry in fs::read_dir(path)? {
+                let entry = entry?.path();
+                if entry.to_string_lossy().ends_with(SHARD_EXTENSION) {
+                    paths.push(entry);
+                }
+            }
+            paths.sort();
+        } else {
+            paths.push(path.to_path_buf());
+        }
+        Ok(Self {
+            paths: paths.into_iter(),
+            shard: None,
+        })
+    }
+
+    fn open_shard(path: &Path) -> io::Result<GzDecoder<BufReader<File>>> {
+        let mut shard = GzDecoder::new(BufReader::new(File::open(path)?));
+        let mut header = [0; 5];
+        shard.read_exact(&mut header)?;
+        if &header[..4] != SHARD_MAGIC || header[4] != SHARD_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not a dataset shard", path.display()),
+            ));
+        }
+        Ok(shard)
+    }
+}
+
+fn read_record<R: Read>(reader: &mut R) -> io::Result<Option<TrainingRecord>> {
+    let mut planes = [0; NUM_PLANES];
+    for (idx, plane) in planes.iter_mut().enumerate() {
+        let mut bytes = [0; 8];
+        match reader.read_exact(&mut bytes) {
+            Err(err) if idx == 0 && err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            result => result?,
+        }
+        *plane = u64::from_le_bytes(bytes);
+    }
+
+    let mut value = [0; 4];
+    let mut policy_len = [0; 2];
+    reader.read_exact(&mut value)?;
+    reader.read_exact(&mut policy_len)?;
+
+    let policy_len = u16::from_le_bytes(policy_len) as usize;
+    let mut policy = Vec::with_capacity(policy_len);
+    for _ in 0..policy_len {
+        let mut action_id = [0; 2];
+        let mut probability = [0; 4];
+        reader.read_exact(&mut action_id)?;
+        reader.read_exact(&mut probability)?;
+        policy.push((
+            u16::from_le_bytes(action_id),
+            f32::from_le_bytes(probability),
+        ));
+    }
+
+    Ok(Some(TrainingRecord {
+        planes,
+        policy,
+        value: f32::from_le_bytes(value),
+    }))
+}
+
+impl Iterator for DatasetReader {
+    type Item = io::Result<TrainingRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.shard.is_none() {
+                let path = self.paths.next()?;
+                match Self::open_shard(&path) {
+                    Ok(shard) => self.shard = Some(shard),
+                    Err(err) => return Some(Err(err)),
+                }
+            }
+            match read_record(self.shard.as_mut().unwrap()).transpose() {
+                Some(record) => return Some(record),
+                None => self.shard = None,
+            }
+        }
+    }
+}
+
+#[pymethods]
+impl DatasetReader {
+    #[new]
+    pub fn new(path: &str) -> PyResult<Self> {
+        Self::open(path).map_err(|err| PyIOError::new_err(err.to_string()))
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self) -> PyResult<Option<TrainingRecord>> {
+        self.next()
+            .transpose()
+            .map_err(|err| PyIOError::new_err(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moves(board: &pleco::Board, ucis: &[&str]) -> Vec<pleco::BitMove> {
+        let mut board = board.clone();
+        ucis.iter()
+            .map(|uci| {
+                let legal = board.generate_moves();
+                let bit_move = *legal.iter().find(|m| m.stringify() == *uci).unwrap();
+                board.apply_move(bit_move);
+                bit_move
+            })
+            .collect()
+    }
+
+    fn directory(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("gym_chess_pleco_{}", name));
+        let _ = fs::remove_dir_all(&path);
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn shards_round_trip() {
+        let board = pleco::Board::start_pos();
+        let played = moves(&board, &["e2e4", "e7e5", "g1f3"]);
+        let e4 = ChessEnv::move_to_action(&played[0]);
+        let d4 = 11 * 73 + 1;
+        let e5 = ChessEnv::move_to_action(&played[1]);
+        let nf3 = ChessEnv::move_to_action(&played[2]);
+        let visits = vec![
+            vec![(e4, 30.0), (d4, 10.0)],
+            vec![(e5, 1.0)],
+            vec![(nf3, 4.0)],
+        ];
+
+        let mut records = game_records(board.clone(), &played, Outcome::WhiteWins, None).unwrap();
+        records.extend(game_records(board, &played, Outcome::Draw, Some(&visits)).unwrap());
+        assert_eq!(records[3].policy, vec![(e4, 0.75), (d4, 0.25)]);
+        assert_eq!(records[1].value, -1.0);
+
+        let dir = directory("shards");
+        let mut writer = DatasetWriter::create(&dir, "test", 4).unwrap();
+        for record in &records {
+            writer.write(record).unwrap();
+        }
+        writer.finish().unwrap();
+        assert_eq!(writer.shards.len(), 2);
+
+        let read = DatasetReader::open(&dir).unwrap();
+        assert_eq!(read.collect::<io::Result<Vec<_>>>().unwrap(), records);
+        let first = DatasetReader::open(&writer.shards[0]).unwrap();
+        assert_eq!(first.count(), 4);
+    }
+
+    #[test]
+    fn rejects_illegal_visits() {
+        let board = pleco::Board::start_pos();
+        let played = moves(&board, &["e2e4"]);
+        // e2e5 is a pawn three squares up
+        let e2e5 = 12 * 73 + 2;
+        let visits = vec![vec![
+            (ChessEnv::move_to_action(&played[0]), 1.0),
+            (e2e5, 1.0),
+        ]];
+        let err = game_records(board, &played, Outcome::Draw, Some(&visits)).unwrap_err();
+        assert!(err.to_string().contains("visited action 878 at ply 0"));
+    }
+
+    #[test]
+    fn rejects_visit_counts_that_are_not_finite() {
+        let board = pleco::Board::start_pos();
+        let played = moves(&board, &["e2e4"]);
+        let e4 = ChessEnv::move_to_action(&played[0]);
+        let d4 = 11 * 73 + 1;
+        for counts in [
+            vec![(e4, f32::NAN)],
+            vec![(e4, 1.0), (d4, f32::NAN)],
+            vec![(e4, f32::INFINITY)],
+            vec![(e4, f32::MAX), (d4, f32::MAX)],
+            vec![(e4, -1.0), (d4, 2.0)],
+            vec![(e4, 0.0)],
+        ] {
+            let visits = vec![counts.clone()];
+            let err = game_records(board.clone(), &played, Outcome::Draw, Some(&visits));
+            assert!(
+                err.unwrap_err()
+                    .to_string()
+                    .contains("visit counts at ply 0"),
+                "{:?}",
+                counts
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_policies_longer_than_the_format_holds() {
+        let record = TrainingRecord {
+            planes: [0; NUM_PLANES],
+            policy: vec![(0, 0.0); u16::MAX as usize + 1],
+            value: 0.0,
+        };
+        let mut writer = DatasetWriter::create(&directory("long_policy"), "test", 4).unwrap();
+        let err = writer.write(&record).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(writer.records_written, 0);
+    }
+}
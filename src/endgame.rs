@@ -0,0 +1,220 @@
+use pleco::tools::prng::PRNG;
+
+use crate::board_builder::placement_field;
+use crate::error::CustomError;
+
+// Upper bound on placement attempts before a material/constraint combination is deemed impossible
+const MAX_ATTEMPTS: usize = 1_000_000;
+
+/// Parses a material signature such as `"KRPvKR"` into the pieces to place, white side first.
+///
+/// Each side must hold exactly one king, at most eight pawns, and no more pieces than its
+/// starting set plus the promotions of its missing pawns.
+pub fn parse_material(material: &str) -> Result<Vec<pleco::Piece>, CustomError> {
+    let sides = material.split(['v', 'V']).collect::<Vec<_>>();
+    if sides.len() != 2 {
+        return Err(CustomError::new(&format!(
+            "material {:?} must have the form <white>v<black>, e.g. KRPvKR",
+            material
+        )));
+    }
+
+    let mut pieces = vec![];
+    for (side, player) in sides
+        .iter()
+        .zip([pleco::Player::White, pleco::Player::Black])
+    {
+        let mut side_pieces = vec![];
+        for c in side.chars() {
+            let piece_type = match c.to_ascii_uppercase() {
+                'K' => pleco::PieceType::K,
+                'Q' => pleco::PieceType::Q,
+                'R' => pleco::PieceType::R,
+                'B' => pleco::PieceType::B,
+                'N' => pleco::PieceType::N,
+                'P' => pleco::PieceType::P,
+                _ => {
+                    return Err(CustomError::new(&format!(
+                        "unknown piece {:?} in material {:?}",
+                        c, material
+                    )))
+                }
+            };
+            side_pieces.push(pleco::Piece::make_lossy(player, piece_type));
+        }
+
+        let count = |piece_type| {
+            side_pieces
+                .iter()
+                .filter(|piece| piece.type_of() == piece_type)
+                .count()
+        };
+        if count(pleco::PieceType::K) != 1 {
+            return Err(CustomError::new(&format!(
+                "{} must have exactly one king in material {:?}",
+                player, material
+            )));
+        }
+        if count(pleco::PieceType::P) > 8 || side_pieces.len() > 16 {
+            return Err(CustomError::new(&format!(
+                "too many pieces for {} in material {:?}",
+                player, material
+            )));
+        }
+        // Pieces beyond the starting set can only come from promoted pawns
+        let promoted = [
+            (pleco::PieceType::Q, 1),
+            (pleco::PieceType::R, 2),
+            (pleco::PieceType::B, 2),
+            (pleco::PieceType::N, 2),
+        ]
+        .iter()
+        .map(|&(piece_type, start)| count(piece_type).saturating_sub(start))
+        .sum::<usize>();
+        if promoted + count(pleco::PieceType::P) > 8 {
+            return Err(CustomError::new(&format!(
+                "{} has more promoted pieces than missing pawns in material {:?}",
+                player, material
+            )));
+        }
+        pieces.extend(side_pieces);
+    }
+
+    Ok(pieces)
+}
+
+/// Places `material` on random squares until the position is legal: no pawns on the back ranks,
+/// kings not adjacent and the side not to move not in check.
+///
+/// With `require_undecided`, positions that are already checkmate or stalemate are rejected as
+/// well. Returns `None` if no such position was found.
+pub fn random_position(
+    material: &[pleco::Piece],
+    side_to_move: Option<pleco::Player>,
+    require_undecided: bool,
+    prng: &mut PRNG,
+) -> Option<pleco::Board> {
+    for _ in 0..MAX_ATTEMPTS {
+        let mut squares: Vec<(pleco::SQ, pleco::Piece)> = vec![];
+        for &piece in material {
+            let sq = loop {
+                let sq = pleco::SQ((prng.rand() % 64) as u8);
+                if squares.iter().all(|(taken, _)| *taken != sq) {
+                    break sq;
+                }
+            };
+            squares.push((sq, piece));
+        }
+
+        let on_back_rank = squares.iter().any(|(sq, piece)| {
+            piece.type_of() == pleco::PieceType::P && matches!(sq.rank_idx_of_sq(), 0 | 7)
+        });
+        let king_sq = |player| {
+            squares
+                .iter()
+                .find(|(_, piece)| *piece == pleco::Piece::make_lossy(player, pleco::PieceType::K))
+                .map(|(sq, _)| *sq)
+                .unwrap()
+        };
+        let kings_adjacent =
+            king_sq(pleco::Player::White).distance(king_sq(pleco::Player::Black)) <= 1;
+        if on_back_rank || kings_adjacent {
+            continue;
+        }
+
+        let turn = side_to_move.unwrap_or(if prng.rand() & 1 == 0 {
+            pleco::Player::White
+        } else {
+            pleco::Player::Black
+        });
+        let board = match pleco::Board::from_fen(&placement_fen(&squares, turn)) {
+            Ok(board) => board,
+            Err(_) => continue,
+        };
+
+        let waiting = turn.other_player();
+        let attackers = board.attackers_to(board.king_sq(waiting), board.occupied())
+            & board.get_occupied_player(turn);
+        if attackers.is_not_empty() {
+            continue;
+        }
+        if require_undecided && (board.checkmate() || board.stalemate()) {
+            continue;
+        }
+        return Some(board);
+    }
+    None
+}
+
+fn placement_fen(squares: &[(pleco::SQ, pleco::Piece)], turn: pleco::Player) -> String {
+    let mut grid = [None; 64];
+    for (sq, piece) in squares {
+        grid[sq.0 as usize] = Some(*piece);
+    }
+
+    let turn = match turn {
+        pleco::Player::White => 'w',
+        pleco::Player::Black => 'b',
+    };
+    format!("{} {} - - 0 1", placement_field(&grid), turn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::seeded_prng;
+    use pleco::Piece::{
+        BlackKing, BlackKnight, BlackRook, WhiteKing, WhitePawn, WhiteQueen, WhiteRook,
+    };
+
+    #[test]
+    fn parses_material_white_first() {
+        assert_eq!(
+            parse_material("KRPvKR").unwrap(),
+            vec![WhiteKing, WhiteRook, WhitePawn, BlackKing, BlackRook]
+        );
+        assert_eq!(
+            parse_material("kqvkn").unwrap(),
+            vec![WhiteKing, WhiteQueen, BlackKing, BlackKnight]
+        );
+    }
+
+    #[test]
+    fn rejects_impossible_material() {
+        for material in [
+            "KRvKR vK",
+            "KR",
+            "KRKvK",
+            "RvK",
+            "KXvK",
+            "KPPPPPPPPPvK",
+            "KQQPPPPPPPPvK",
+            "KNNNNNNNNNNNvK",
+        ] {
+            assert!(parse_material(material).is_err(), "{}", material);
+        }
+        assert!(parse_material("KQQQQQQQQQvK").is_ok());
+    }
+
+    #[test]
+    fn places_legal_positions() {
+        let material = parse_material("KPPvKR").unwrap();
+        let mut prng = seeded_prng(7);
+        for _ in 0..200 {
+            let board = random_position(&material, None, true, &mut prng).unwrap();
+            let turn = board.turn();
+            let waiting = !turn;
+            assert!(
+                (board.attackers_to(board.king_sq(waiting), board.occupied())
+                    & board.get_occupied_player(turn))
+                .is_empty()
+            );
+            assert!(board.king_sq(turn).distance(board.king_sq(waiting)) > 1);
+            let back_ranks = pleco::BitBoard(0xff000000000000ff);
+            assert!((board.piece_bb_both_players(pleco::PieceType::P) & back_ranks).is_empty());
+            assert!(!board.checkmate() && !board.stalemate());
+        }
+        let board = random_position(&material, Some(pleco::Player::Black), false, &mut prng);
+        assert_eq!(board.unwrap().turn(), pleco::Player::Black);
+    }
+}
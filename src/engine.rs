@@ -0,0 +1,313 @@
+use pleco::tools::Searcher;
+use pyo3::prelude::*;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::str::FromStr;
+
+use crate::board::Board;
+use crate::game::GameState;
+use crate::piece_move::BitMove;
+
+pub const DEFAULT_DEPTH: u16 = 4;
+/// Milliseconds per move given to UCI engines.
+pub const DEFAULT_MOVETIME: u64 = 100;
+
+/// Something that picks moves: a pleco searcher, a UCI engine or a Python policy.
+pub trait Engine: Send {
+    fn name(&self) -> String;
+
+    /// The move to play in the current position of `game`, which is not over.
+    fn best_move(&mut self, game: &GameState) -> io::Result<pleco::BitMove>;
+}
+
+/// The searchers that ship with pleco.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Bot {
+    Random,
+    Minimax,
+    AlphaBeta,
+    Jamboree,
+    Iterative,
+}
+
+impl Bot {
+    pub const ALL: [Bot; 5] = [
+        Bot::Random,
+        Bot::Minimax,
+        Bot::AlphaBeta,
+        Bot::Jamboree,
+        Bot::Iterative,
+    ];
+
+    /// The short name used on the command line and in Python, e.g. `alphabeta`.
+    pub fn key(self) -> &'static str {
+        match self {
+            Bot::Random => "random",
+            Bot::Minimax => "minimax",
+            Bot::AlphaBeta => "alphabeta",
+            Bot::Jamboree => "jamboree",
+            Bot::Iterative => "iterative",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Bot::Random => pleco::bots::RandomBot::name(),
+            Bot::Minimax => pleco::bots::MiniMaxSearcher::name(),
+            Bot::AlphaBeta => pleco::bots::AlphaBetaSearcher::name(),
+            Bot::Jamboree => pleco::bots::JamboreeSearcher::name(),
+            Bot::Iterative => pleco::bots::IterativeSearcher::name(),
+        }
+    }
+
+    pub fn best_move(self, board: pleco::Board, depth: u16) -> pleco::BitMove {
+        match self {
+            Bot::Random => pleco::bots::RandomBot::best_move(board, depth),
+            Bot::Minimax => pleco::bots::MiniMaxSearcher::best_move(board, depth),
+            Bot::AlphaBeta => pleco::bots::AlphaBetaSearcher::best_move(board, depth),
+            Bot::Jamboree => pleco::bots::JamboreeSearcher::best_move(board, depth),
+            Bot::Iterative => pleco::bots::IterativeSearcher::best_move(board, depth),
+        }
+    }
+}
+
+impl FromStr for Bot {
+    type Err = String;
+
+    fn from_str(key: &str) -> Result<Bot, String> {
+        Bot::ALL
+            .into_iter()
+            .find(|bot| bot.key() == key.to_ascii_lowercase())
+            .ok_or_else(|| {
+                let keys = Bot::ALL.map(|bot| bot.key());
+                format!("unknown searcher {:?}, expected one of {:?}", key, keys)
+            })
+    }
+}
+
+pub struct BotEngine {
+    pub bot: Bot,
+    pub depth: u16,
+}
+
+impl Engine for BotEngine {
+    fn name(&self) -> String {
+        format!("{} (depth {})", self.bot.name(), self.depth)
+    }
+
+    fn best_move(&mut self, game: &GameState) -> io::Result<pleco::BitMove> {
+        Ok(self.bot.best_move(game.board.shallow_clone(), self.depth))
+    }
+}
+
+/// A UCI engine running as a subprocess, given a fixed time per move.
+pub struct UciEngine {
+    name: String,
+    movetime: u64,
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl UciEngine {
+    pub fn start(path: &PathBuf, movetime: u64) -> io::Result<Self> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+        // Both pipes were requested above
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut engine = UciEngine {
+            name: path.display().to_string(),
+            movetime,
+            child,
+            stdin,
+            stdout,
+        };
+
+        engine.send("uci")?;
+        for line in engine.read_until("uciok")? {
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = name.trim().to_string();
+            }
+        }
+        engine.send("ucinewgame")?;
+        engine.send("isready")?;
+        engine.read_until("readyok")?;
+        Ok(engine)
+    }
+
+    fn send(&mut self, command: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{}", command)?;
+        self.stdin.flush()
+    }
+
+    // Returns every line read up to the one starting with `token`, which is included
+    fn read_until(&mut self, token: &str) -> io::Result<Vec<String>> {
+        let mut lines = vec![];
+        loop {
+            let mut line = String::new();
+            if self.stdout.read_line(&mut line)? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("{} exited while waiting for {}", self.name, token),
+                ));
+            }
+            let done = line.starts_with(token);
+            lines.push(line);
+            if done {
+                return Ok(lines);
+            }
+        }
+    }
+}
+
+impl Engine for UciEngine {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn best_move(&mut self, game: &GameState) -> io::Result<pleco::BitMove> {
+        let mut position = format!("position fen {}", game.start_fen);
+        if !game.moves.is_empty() {
+            position.push_str(" moves");
+            for bit_move in &game.moves {
+                position.push(' ');
+                position.push_str(&bit_move.stringify());
+            }
+        }
+        self.send(&position)?;
+        self.send(&format!("go movetime {}", self.movetime))?;
+        let lines = self.read_until("bestmove")?;
+        // The last line is the one holding the move
+        let uci = lines
+            .last()
+            .unwrap()
+            .split_whitespace()
+            .nth(1)
+            .unwrap_or_default()
+            .to_string();
+        game.find_uci(&uci).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} played an illegal move: {}", self.name, uci),
+            )
+        })
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        // The engine may already be gone, in which case there is nothing left to stop
+        let _ = self.send("quit");
+        let _ = self.child.wait();
+    }
+}
+
+/// A Python callable taking a `Board` and returning the move to play, as a `BitMove` or in UCI
+/// notation.
+pub struct PolicyEngine {
+    pub name: String,
+    pub policy: PyObject,
+}
+
+impl Engine for PolicyEngine {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn best_move(&mut self, game: &GameState) -> io::Result<pleco::BitMove> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        Python::with_gil(|py| {
+            let board = Board::from(game.board.parallel_clone());
+            let choice = self
+                .policy
+                .call1(py, (board,))
+                .map_err(|err| invalid(format!("{} raised {}", self.name, err)))?;
+            let bit_move = match choice.extract::<BitMove>(py) {
+                Ok(bit_move) => Some(pleco::BitMove::from(bit_move)),
+                Err(_) => choice
+                    .extract::<String>(py)
+                    .ok()
+                    .and_then(|uci| game.find_uci(&uci)),
+            };
+            bit_move
+                .filter(|bit_move| game.is_legal(*bit_move))
+                .ok_or_else(|| invalid(format!("{} played an illegal move", self.name)))
+        })
+    }
+}
+
+/// How to start an engine. Games each get a fresh engine, so a crash only loses one game.
+#[derive(Clone)]
+pub enum EngineSpec {
+    Bot { bot: Bot, depth: u16 },
+    Uci { path: PathBuf, movetime: u64 },
+    Policy { name: String, policy: PyObject },
+}
+
+impl EngineSpec {
+    pub fn spawn(&self) -> io::Result<Box<dyn Engine>> {
+        Ok(match self {
+            EngineSpec::Bot { bot, depth } => Box::new(BotEngine {
+                bot: *bot,
+                depth: *depth,
+            }),
+            EngineSpec::Uci { path, movetime } => Box::new(UciEngine::start(path, *movetime)?),
+            EngineSpec::Policy { name, policy } => Box::new(PolicyEngine {
+                name: name.clone(),
+                policy: policy.clone(),
+            }),
+        })
+    }
+}
+
+impl fmt::Display for EngineSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineSpec::Bot { bot, depth } => write!(f, "{}-{}", bot.key(), depth),
+            EngineSpec::Uci { path, .. } => match path.file_stem() {
+                Some(stem) => write!(f, "{}", stem.to_string_lossy()),
+                None => write!(f, "{}", path.display()),
+            },
+            EngineSpec::Policy { name, .. } => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Parses `bot:<searcher>[:<depth>]` or `uci:<path>`; UCI engines get 100ms per move.
+impl FromStr for EngineSpec {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<EngineSpec, String> {
+        match spec.split_once(':') {
+            Some(("bot", rest)) => {
+                let (bot, depth) = match rest.split_once(':') {
+                    Some((bot, depth)) => (
+                        bot,
+                        depth
+                            .parse()
+                            .map_err(|_| format!("invalid search depth: {}", depth))?,
+                    ),
+                    None => (rest, DEFAULT_DEPTH),
+                };
+                Ok(EngineSpec::Bot {
+                    bot: bot.parse()?,
+                    depth,
+                })
+            }
+            Some(("uci", path)) if !path.is_empty() => Ok(EngineSpec::Uci {
+                path: PathBuf::from(path),
+                movetime: DEFAULT_MOVETIME,
+            }),
+            _ => Err(format!(
+                "invalid engine {:?}, expected bot:<searcher>[:<depth>] or uci:<path>",
+                spec
+            )),
+        }
+    }
+}
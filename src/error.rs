@@ -1,8 +1,10 @@
 use std::fmt;
 use std::fmt::Debug;
 
-use pyo3::exceptions::PyOSError;
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
 use pyo3::prelude::*;
+use pyo3::types::PyList;
 
 #[derive(Debug, Clone)]
 pub struct CustomError {
@@ -19,26 +21,93 @@ impl CustomError {
 
 impl fmt::Display for CustomError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "CustomError: {}", self.msg)
+        write!(f, "{}", self.msg)
     }
 }
 
-pub struct FenBuildError(pleco::board::FenBuildError);
-
-impl fmt::Display for FenBuildError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.0.fmt(f)
+impl From<CustomError> for PyErr {
+    fn from(err: CustomError) -> PyErr {
+        ChessError::new_err(err.msg)
     }
 }
 
-impl From<pleco::board::FenBuildError> for FenBuildError {
-    fn from(err: pleco::board::FenBuildError) -> FenBuildError {
-        FenBuildError(err)
-    }
+// Python exception hierarchy, all deriving from `ChessError`
+create_exception!(gym_chess_pleco, ChessError, PyException);
+create_exception!(gym_chess_pleco, InvalidFenError, ChessError);
+create_exception!(gym_chess_pleco, InvalidPositionError, ChessError);
+create_exception!(gym_chess_pleco, IllegalMoveError, ChessError);
+create_exception!(gym_chess_pleco, IllegalActionError, ChessError);
+create_exception!(gym_chess_pleco, InvalidSquareError, ChessError);
+create_exception!(gym_chess_pleco, InvalidActionIdError, ChessError);
+create_exception!(gym_chess_pleco, InvalidArgumentError, ChessError);
+create_exception!(gym_chess_pleco, TablebaseError, ChessError);
+
+pub fn register(py: Python, m: &PyModule) -> PyResult<()> {
+    m.add("ChessError", py.get_type::<ChessError>())?;
+    m.add("InvalidFenError", py.get_type::<InvalidFenError>())?;
+    m.add(
+        "InvalidPositionError",
+        py.get_type::<InvalidPositionError>(),
+    )?;
+    m.add("IllegalMoveError", py.get_type::<IllegalMoveError>())?;
+    m.add("IllegalActionError", py.get_type::<IllegalActionError>())?;
+    m.add("InvalidSquareError", py.get_type::<InvalidSquareError>())?;
+    m.add(
+        "InvalidActionIdError",
+        py.get_type::<InvalidActionIdError>(),
+    )?;
+    m.add(
+        "InvalidArgumentError",
+        py.get_type::<InvalidArgumentError>(),
+    )?;
+    m.add("TablebaseError", py.get_type::<TablebaseError>())?;
+    Ok(())
 }
 
-impl From<FenBuildError> for PyErr {
-    fn from(err: FenBuildError) -> PyErr {
-        PyOSError::new_err(err.to_string())
-    }
+fn with_attrs(err: PyErr, attrs: Vec<(&str, PyObject)>) -> PyErr {
+    Python::with_gil(|py| {
+        for (name, value) in attrs {
+            // Setting an attribute on a fresh exception instance cannot fail
+            err.value(py).setattr(name, value).unwrap();
+        }
+        err
+    })
+}
+
+/// An `InvalidFenError` naming the FEN field at fault (`placement`, `turn`, `en_passant`,
+/// `move_counters`, or `fen` for the string as a whole) and the reason.
+pub fn invalid_fen(fen: &str, err: pleco::board::FenBuildError) -> PyErr {
+    use pleco::board::FenBuildError::*;
+
+    let field = match err {
+        NotEnoughSections { .. } => "fen",
+        UnrecognizedTurn { .. } => "turn",
+        EPSquareUnreadable { .. } | EPSquareInvalid { .. } => "en_passant",
+        UnreadableMoves(_) => "move_counters",
+        _ => "placement",
+    };
+    // pleco's messages end with a newline
+    let reason = format!("{:?}", err).trim().to_string();
+
+    Python::with_gil(|py| {
+        with_attrs(
+            InvalidFenError::new_err(format!("invalid FEN {:?}: {}", fen, reason)),
+            vec![
+                ("fen", fen.into_py(py)),
+                ("field", field.into_py(py)),
+                ("reason", reason.into_py(py)),
+            ],
+        )
+    })
+}
+
+/// An `InvalidPositionError` whose `problems` attribute lists everything wrong with a position.
+pub fn invalid_position(problems: Vec<String>) -> PyErr {
+    Python::with_gil(|py| {
+        let err = InvalidPositionError::new_err(problems.join("; "));
+        with_attrs(
+            err,
+            vec![("problems", PyList::new(py, problems).into_py(py))],
+        )
+    })
 }
@@ -0,0 +1,313 @@
+//! Static evaluation split into terms.
+//!
+//! `breakdown` returns the terms of pleco's evaluator (`pleco::tools::eval::Eval`), which add up
+//! to its score. pleco's evaluator has no piece-square, mobility, passed pawn or threat terms;
+//! `features` measures those separately with Stockfish's middlegame and endgame bonuses, blended
+//! by game phase.
+
+use pleco::core::score::Score;
+use pleco::core::CastleType;
+use pleco::helper::prelude::{
+    bishop_moves, king_moves, knight_moves, passed_pawn_mask, pawn_attacks_from, psq, queen_moves,
+    rook_moves,
+};
+use pleco::tools::eval::{Eval, PAWN_POS};
+use pleco::{BitBoard, Board, PieceType, Player};
+
+use crate::score::phase;
+
+// Weights of pleco's evaluator
+const PAWN_VALUE: i32 = 100;
+const BISHOP_PAIR: i32 = 19;
+const CASTLE_ABILITY: i32 = 7;
+const KING_NEIGHBOUR: i32 = 9;
+const CHECK: i32 = 14;
+const OWN_BLOCKER: i32 = 18;
+const THEIR_BLOCKER: i32 = 6;
+// From here on pleco's evaluator scores both sides alike, leaving only non-pawn material
+const RULE_50_CUTOFF: i16 = 50;
+
+// Stockfish's mobility bonus by number of squares reached
+#[rustfmt::skip]
+const KNIGHT_MOBILITY: [(i32, i32); 9] = [
+    (-62, -81), (-53, -56), (-12, -30), (-4, -14), (3, 8), (13, 15), (22, 23), (28, 27), (33, 33),
+];
+#[rustfmt::skip]
+const BISHOP_MOBILITY: [(i32, i32); 14] = [
+    (-48, -59), (-20, -23), (16, -3), (26, 13), (38, 24), (51, 42), (55, 54), (63, 57), (63, 65),
+    (68, 73), (81, 78), (81, 86), (91, 88), (98, 97),
+];
+#[rustfmt::skip]
+const ROOK_MOBILITY: [(i32, i32); 15] = [
+    (-58, -76), (-27, -18), (-15, 28), (-10, 55), (-5, 69), (-2, 82), (9, 112), (16, 118),
+    (30, 132), (29, 142), (32, 155), (38, 165), (46, 166), (48, 169), (58, 171),
+];
+#[rustfmt::skip]
+const QUEEN_MOBILITY: [(i32, i32); 28] = [
+    (-39, -36), (-21, -15), (3, 8), (3, 18), (14, 34), (22, 54), (28, 61), (41, 73), (43, 79),
+    (48, 92), (56, 94), (60, 104), (60, 113), (66, 120), (67, 123), (70, 126), (71, 133),
+    (73, 136), (79, 140), (88, 143), (88, 148), (99, 166), (102, 170), (102, 175), (106, 184),
+    (109, 191), (113, 206), (116, 212),
+];
+// By relative rank
+#[rustfmt::skip]
+const PASSED_RANK: [(i32, i32); 8] = [
+    (0, 0), (10, 28), (17, 33), (15, 41), (62, 72), (168, 177), (276, 260), (0, 0),
+];
+// By the type of the piece attacked, indexed like `PieceType`
+#[rustfmt::skip]
+const THREAT_BY_MINOR: [(i32, i32); 7] = [
+    (0, 0), (6, 32), (59, 41), (79, 56), (90, 119), (79, 161), (0, 0),
+];
+#[rustfmt::skip]
+const THREAT_BY_ROOK: [(i32, i32); 7] = [
+    (0, 0), (3, 44), (38, 71), (38, 61), (0, 38), (51, 38), (0, 0),
+];
+const THREAT_BY_PAWN: (i32, i32) = (173, 94);
+const HANGING: (i32, i32) = (69, 36);
+
+/// pleco's static evaluation in centipawns, from the point of view of the side to move.
+pub fn evaluate(board: &Board) -> i32 {
+    Eval::eval_low(board)
+}
+
+/// The terms of `evaluate` as `[white, black]`, each in centipawns from that side's point of
+/// view. For the side to move, `evaluate` is the sum of its terms less the opponent's.
+pub fn breakdown(board: &Board) -> Vec<(&'static str, [i32; 2])> {
+    vec![
+        ("material", both(&|player| material(board, player))),
+        (
+            "pawn_structure",
+            both(&|player| pawn_structure(board, player)),
+        ),
+        ("king_safety", both(&|player| king_safety(board, player))),
+    ]
+}
+
+/// Positional features pleco's evaluator does not weigh, as `[white, black]` in centipawns from
+/// that side's point of view. They use Stockfish's bonuses, blended by game phase.
+pub fn features(board: &Board) -> Vec<(&'static str, [i32; 2])> {
+    let phase = phase(board);
+    let blend = |score: Score| crate::score::Score::from(score).value(phase);
+    vec![
+        ("psq", both(&|player| blend(piece_squares(board, player)))),
+        ("mobility", both(&|player| blend(mobility(board, player)))),
+        (
+            "passed_pawns",
+            both(&|player| blend(passed_pawns(board, player))),
+        ),
+        ("threats", both(&|player| blend(threats(board, player)))),
+    ]
+}
+
+fn both(term: &dyn Fn(Player) -> i32) -> [i32; 2] {
+    [term(Player::White), term(Player::Black)]
+}
+
+fn score((mg, eg): (i32, i32)) -> Score {
+    Score::new(mg, eg)
+}
+
+fn material(board: &Board, player: Player) -> i32 {
+    let mut value = board.non_pawn_material(player);
+    if board.rule_50() < RULE_50_CUTOFF {
+        value += board.count_piece(player, PieceType::P) as i32 * PAWN_VALUE;
+        if board.count_piece(player, PieceType::B) > 1 {
+            value += BISHOP_PAIR;
+        }
+    }
+    value
+}
+
+fn king_safety(board: &Board, player: Player) -> i32 {
+    if board.rule_50() >= RULE_50_CUTOFF {
+        return 0;
+    }
+    let mut value = 0;
+    for side in [CastleType::KingSide, CastleType::QueenSide] {
+        if board.can_castle(player, side) {
+            value += CASTLE_ABILITY;
+        }
+    }
+    if board.in_check() && board.turn() == player {
+        value -= CHECK;
+    }
+    let own = board.get_occupied_player(player);
+    value += (king_moves(board.king_sq(player)) & own).count_bits() as i32 * KING_NEIGHBOUR;
+
+    // Pieces standing between the king and an attacker
+    let blockers = board.all_pinned_pieces(player);
+    value += (blockers & own).count_bits() as i32 * OWN_BLOCKER;
+    value += (blockers & board.get_occupied_player(!player)).count_bits() as i32 * THEIR_BLOCKER;
+    value
+}
+
+fn pawn_structure(board: &Board, player: Player) -> i32 {
+    if board.rule_50() >= RULE_50_CUTOFF {
+        return 0;
+    }
+    let pawns = board.piece_bb(player, PieceType::P);
+    let mut value = 0;
+    let mut files = [0i32; 8];
+    let mut defended = BitBoard(0);
+    for sq in pawns {
+        defended |= pawn_attacks_from(sq, player);
+        files[sq.file() as usize] += 1;
+        value += PAWN_POS[player as usize][sq.0 as usize];
+    }
+    // Squares covered by pawns, and pawns covering each other
+    value += defended.count_bits() as i32;
+    value += 3 * (defended & pawns).count_bits() as i32;
+
+    for file in 0..8 {
+        if files[file] > 1 {
+            value -= 3 * files[file];
+        }
+        // pleco leaves the edge files out of its isolation check
+        if file > 0 && file < 7 && files[file] > 0 {
+            value += match (files[file - 1] > 0, files[file + 1] > 0) {
+                (true, true) => 7,
+                (false, false) => -4,
+                _ => 3,
+            };
+        }
+    }
+    value
+}
+
+fn piece_squares(board: &Board, player: Player) -> Score {
+    let mut total = Score::ZERO;
+    for sq in board.get_occupied_player(player) {
+        total += psq(board.piece_at_sq(sq), sq);
+    }
+    // pleco's tables are from white's point of view
+    match player {
+        Player::White => total,
+        Player::Black => -total,
+    }
+}
+
+// Squares each piece type of `player` attacks, indexed like `PieceType`
+fn attacks(board: &Board, player: Player) -> [BitBoard; 7] {
+    let occupied = board.occupied();
+    let mut attacks = [BitBoard(0); 7];
+    for piece_type in [
+        PieceType::P,
+        PieceType::N,
+        PieceType::B,
+        PieceType::R,
+        PieceType::Q,
+        PieceType::K,
+    ] {
+        for sq in board.piece_bb(player, piece_type) {
+            attacks[piece_type as usize] |= match piece_type {
+                PieceType::P => pawn_attacks_from(sq, player),
+                PieceType::N => knight_moves(sq),
+                PieceType::B => bishop_moves(occupied, sq),
+                PieceType::R => rook_moves(occupied, sq),
+                PieceType::Q => queen_moves(occupied, sq),
+                _ => king_moves(sq),
+            };
+        }
+    }
+    attacks
+}
+
+// Squares reached by each knight, bishop, rook and queen that are neither occupied by its own
+// side nor attacked by enemy pawns
+fn mobility(board: &Board, player: Player) -> Score {
+    let occupied = board.occupied();
+    let area =
+        !(board.get_occupied_player(player) | attacks(board, !player)[PieceType::P as usize]);
+    let bonus = |table: &[(i32, i32)], reach: BitBoard| {
+        score(table[((reach & area).count_bits() as usize).min(table.len() - 1)])
+    };
+    let mut total = Score::ZERO;
+    for sq in board.piece_bb(player, PieceType::N) {
+        total += bonus(&KNIGHT_MOBILITY, knight_moves(sq));
+    }
+    for sq in board.piece_bb(player, PieceType::B) {
+        total += bonus(&BISHOP_MOBILITY, bishop_moves(occupied, sq));
+    }
+    for sq in board.piece_bb(player, PieceType::R) {
+        total += bonus(&ROOK_MOBILITY, rook_moves(occupied, sq));
+    }
+    for sq in board.piece_bb(player, PieceType::Q) {
+        total += bonus(&QUEEN_MOBILITY, queen_moves(occupied, sq));
+    }
+    total
+}
+
+// Pawns with no enemy pawn ahead of them on their own or a neighbouring file
+fn passed_pawns(board: &Board, player: Player) -> Score {
+    let enemy_pawns = board.piece_bb(!player, PieceType::P);
+    let mut total = Score::ZERO;
+    for sq in board.piece_bb(player, PieceType::P) {
+        if (passed_pawn_mask(player, sq) & enemy_pawns).is_empty() {
+            total += score(PASSED_RANK[player.relative_rank_of_sq(sq) as usize]);
+        }
+    }
+    total
+}
+
+// Enemy pieces attacked by pawns, minor pieces or rooks, and undefended pieces under attack
+fn threats(board: &Board, player: Player) -> Score {
+    let ours = attacks(board, player);
+    let theirs = attacks(board, !player);
+    let attacked = ours.iter().fold(BitBoard(0), |all, bb| all | *bb);
+    let defended = theirs.iter().fold(BitBoard(0), |all, bb| all | *bb);
+    let targets = board.get_occupied_player(!player) & !board.piece_bb(!player, PieceType::K);
+
+    let minors = ours[PieceType::N as usize] | ours[PieceType::B as usize];
+    let mut total = Score::ZERO;
+    for sq in targets {
+        let piece_type = board.piece_at_sq(sq).type_of() as usize;
+        if (minors & sq.to_bb()).is_not_empty() {
+            total += score(THREAT_BY_MINOR[piece_type]);
+        }
+        if (ours[PieceType::R as usize] & sq.to_bb()).is_not_empty() {
+            total += score(THREAT_BY_ROOK[piece_type]);
+        }
+        if piece_type != PieceType::P as usize
+            && (ours[PieceType::P as usize] & sq.to_bb()).is_not_empty()
+        {
+            total += score(THREAT_BY_PAWN);
+        }
+        if (attacked & !defended & sq.to_bb()).is_not_empty() {
+            total += score(HANGING);
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn terms_add_up_to_evaluate() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+            // Checks and pins
+            "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3",
+            "r1bqk1nr/pppp1ppp/2n5/4p3/1b2P3/2NP4/PPP2PPP/R1BQKBNR w KQkq - 1 4",
+            "4k3/8/8/8/1b6/8/3N4/4K3 w - - 0 1",
+            // Pawn endings, passed pawns and a bishop pair
+            "8/5k2/3p4/1p1Pp2p/pP2Pp1P/P4P1K/8/8 b - - 99 50",
+            "4k3/1P6/8/8/8/8/6p1/2B1KB2 w - - 10 60",
+            // Past the 50-move cutoff
+            "4k3/pp6/8/8/8/8/PPP5/2BBK3 b - - 60 80",
+            "r3k3/8/8/8/8/8/8/4K2R w Kq - 75 90",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let us = board.turn() as usize;
+            let sum = breakdown(&board)
+                .iter()
+                .map(|(_, sides)| sides[us] - sides[1 - us])
+                .sum::<i32>();
+            assert_eq!(sum, evaluate(&board), "{}", fen);
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! Tactical features built on attack bitboards.
+//!
+//! Exchanges are valued like pleco's `Board::see_ge`, with its middlegame piece values (a pawn is
+//! 171), so that `see(board, m) >= threshold` agrees with `board.see_ge(m, threshold)`.
+
+use pleco::core::piece_move::MoveType;
+use pleco::helper::prelude::piecetype_value;
+use pleco::{BitBoard, BitMove, Board, PieceType, Player, SQ};
+
+// Least valuable first, the order attackers join an exchange in
+const ATTACKERS: [PieceType; 6] = [
+    PieceType::P,
+    PieceType::N,
+    PieceType::B,
+    PieceType::R,
+    PieceType::Q,
+    PieceType::K,
+];
+
+/// What `mov` wins or loses once both sides have made every capture on its destination that
+/// pays off. Castling, en passant and promotions are valued at 0, as by `see_ge`.
+pub fn see(board: &Board, mov: BitMove) -> i32 {
+    if mov.move_type() != MoveType::Normal {
+        return 0;
+    }
+    let from = mov.get_src();
+    let to = mov.get_dest();
+    let moved = board.piece_at_sq(from);
+    if moved == pleco::Piece::None {
+        return 0;
+    }
+
+    // gains[d] is the balance for the side making the d-th capture, if the exchange stops there
+    let mut gains = vec![piecetype_value(board.piece_at_sq(to).type_of(), false)];
+    let mut on_square = moved.type_of();
+    let mut stm = !moved.player_lossy();
+    let mut occupied = board.occupied() ^ from.to_bb() ^ to.to_bb();
+    loop {
+        let attackers = board.attackers_to(to, occupied) & occupied;
+        let mut ours = attackers & board.get_occupied_player(stm);
+        // Pinned pieces stay put as long as every pinner is on the board
+        if (board.pinning_pieces(stm) & !occupied).is_empty() {
+            ours &= !board.all_pinned_pieces(stm);
+        }
+        let (piece_type, sq) = match least_valuable(board, ours) {
+            Some(attacker) => attacker,
+            None => break,
+        };
+        // The king cannot recapture onto a square that is still attacked
+        if piece_type == PieceType::K
+            && (attackers & board.get_occupied_player(!stm)).is_not_empty()
+        {
+            break;
+        }
+        let gain = piecetype_value(on_square, false) - gains[gains.len() - 1];
+        gains.push(gain);
+        on_square = piece_type;
+        occupied ^= sq.to_bb();
+        stm = !stm;
+    }
+
+    // Either side stops capturing once going on would cost it
+    while gains.len() > 1 {
+        let last = gains.pop().unwrap();
+        let previous = gains.last_mut().unwrap();
+        *previous = -(-*previous).max(last);
+    }
+    gains[0]
+}
+
+fn least_valuable(board: &Board, attackers: BitBoard) -> Option<(PieceType, SQ)> {
+    ATTACKERS.iter().find_map(|&piece_type| {
+        let pieces = attackers & board.piece_bb_both_players(piece_type);
+        if pieces.is_empty() {
+            None
+        } else {
+            Some((piece_type, pieces.bit_scan_forward()))
+        }
+    })
+}
+
+/// How many pieces of `player` attack each square, a1 first.
+pub fn attack_map(board: &Board, player: Player) -> [u8; 64] {
+    let mut counts = [0; 64];
+    for &piece_type in ATTACKERS.iter() {
+        for sq in board.piece_bb(player, piece_type) {
+            for target in board.attacks_from(piece_type, sq, player) {
+                counts[target.0 as usize] += 1;
+            }
+        }
+    }
+    counts
+}
+
+/// Pieces of `player`, other than the king, attacked by the opponent and defended by none of
+/// their own.
+pub fn hanging_pieces(board: &Board, player: Player) -> BitBoard {
+    let occupied = board.occupied();
+    let targets = board.get_occupied_player(player) & !board.piece_bb(player, PieceType::K);
+    let mut hanging = BitBoard(0);
+    for sq in targets {
+        let attackers = board.attackers_to(sq, occupied);
+        if (attackers & board.get_occupied_player(!player)).is_not_empty()
+            && (attackers & board.get_occupied_player(player)).is_empty()
+        {
+            hanging |= sq.to_bb();
+        }
+    }
+    hanging
+}
+
+/// The squares pieces of `player` attack that are not occupied by their own side, summed by
+/// piece type. Pawns are left out, as the squares they attack are not where they move to.
+pub fn mobility(board: &Board, player: Player) -> [(&'static str, u32); 5] {
+    let own = board.get_occupied_player(player);
+    [
+        ("knight", PieceType::N),
+        ("bishop", PieceType::B),
+        ("rook", PieceType::R),
+        ("queen", PieceType::Q),
+        ("king", PieceType::K),
+    ]
+    .map(|(name, piece_type)| {
+        let squares = board
+            .piece_bb(player, piece_type)
+            .into_iter()
+            .map(|sq| (board.attacks_from(piece_type, sq, player) & !own).count_bits() as u32)
+            .sum();
+        (name, squares)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn see_agrees_with_see_ge() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+            // Batteries and x-rays behind the first attacker
+            "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+            "2r1r1k1/pp1bppbp/3p1np1/q3P3/2P2P2/1P2B3/P1N1B1PP/2RQ1RK1 b - - 0 1",
+            // A pinned defender and a king that cannot recapture into an attack
+            "4k3/4r3/8/4p3/3P4/8/4R3/4K3 w - - 0 1",
+            "3qk3/8/8/3p4/4N3/8/8/3RK3 w - - 0 1",
+            "6k1/8/8/3b4/8/1B6/8/3QK3 w - - 0 1",
+            // En passant and promotions, valued at 0
+            "4k3/1P6/8/3pP3/8/8/8/4K3 w - d6 0 2",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            for mov in board.generate_moves().iter().copied() {
+                let value = see(&board, mov);
+                for threshold in [
+                    -2000,
+                    -700,
+                    -171,
+                    -1,
+                    0,
+                    1,
+                    171,
+                    700,
+                    2000,
+                    value,
+                    value + 1,
+                ] {
+                    assert_eq!(
+                        value >= threshold,
+                        board.see_ge(mov, threshold),
+                        "{} {} see {} threshold {}",
+                        fen,
+                        mov,
+                        value,
+                        threshold
+                    );
+                }
+            }
+        }
+    }
+
+    fn white_and_black(fen: &str) -> (Board, [u8; 64], [u8; 64]) {
+        let board = Board::from_fen(fen).unwrap();
+        let white = attack_map(&board, Player::White);
+        let black = attack_map(&board, Player::Black);
+        (board, white, black)
+    }
+
+    #[test]
+    fn attack_map_counts_every_attacker() {
+        let (_, white, black) =
+            white_and_black("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        // Two pawns and a knight, two pawns, a bishop, queen, king and knight
+        assert_eq!(white[SQ::C3.0 as usize], 3);
+        assert_eq!(white[SQ::E3.0 as usize], 2);
+        assert_eq!(white[SQ::D2.0 as usize], 4);
+        assert!(white[24..].iter().all(|&count| count == 0));
+        assert_eq!(black[SQ::F6.0 as usize], 3);
+        assert!(black[..40].iter().all(|&count| count == 0));
+
+        // Pawns attack diagonally but not straight ahead, and a rook does not see through the
+        // rook in front of it
+        let (_, white, _) = white_and_black("4k3/8/8/8/8/8/R3P3/R3K3 w - - 0 1");
+        assert_eq!(white[SQ::D3.0 as usize], 1);
+        assert_eq!(white[SQ::F3.0 as usize], 1);
+        assert_eq!(white[SQ::E3.0 as usize], 0);
+        assert_eq!(white[SQ::A2.0 as usize], 1);
+        assert_eq!(white[SQ::A3.0 as usize], 1);
+        assert_eq!(white[SQ::A8.0 as usize], 1);
+        assert_eq!(white[SQ::D1.0 as usize], 2);
+    }
+
+    #[test]
+    fn hanging_pieces_are_attacked_and_undefended() {
+        let board = Board::from_fen("4k3/8/8/3pp3/5N2/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(hanging_pieces(&board, Player::Black), SQ::D5.to_bb());
+        assert_eq!(hanging_pieces(&board, Player::White), SQ::F4.to_bb());
+
+        // A defender takes d5 off the list
+        let board = Board::from_fen("4k3/8/2p5/3pp3/5N2/8/8/4K3 w - - 0 1").unwrap();
+        assert!(hanging_pieces(&board, Player::Black).is_empty());
+        assert_eq!(hanging_pieces(&board, Player::White), SQ::F4.to_bb());
+
+        // An attacked king never appears on it
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/r3K3 w - - 0 1").unwrap();
+        assert!(hanging_pieces(&board, Player::White).is_empty());
+    }
+
+    #[test]
+    fn mobility_counts_each_piece_type() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/N3K2R w K - 0 1").unwrap();
+        assert_eq!(
+            mobility(&board, Player::White),
+            [
+                ("knight", 2),
+                ("bishop", 0),
+                ("rook", 9),
+                ("queen", 0),
+                ("king", 5),
+            ]
+        );
+        let board = Board::start_pos();
+        assert_eq!(
+            mobility(&board, Player::Black),
+            [
+                ("knight", 4),
+                ("bishop", 0),
+                ("rook", 0),
+                ("queen", 0),
+                ("king", 0),
+            ]
+        );
+    }
+}
@@ -0,0 +1,374 @@
+//! Complete games between engines, from an opening to a result.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::dataset::Outcome;
+use crate::engine::Engine;
+use crate::pgn::{to_san, PgnGame, PgnReader};
+use crate::tablebase::Tablebase;
+
+/// A game in progress: the position, the moves that led to it and the key of every position
+/// reached, for repetitions.
+pub struct GameState {
+    pub board: pleco::Board,
+    pub start_fen: String,
+    pub moves: Vec<pleco::BitMove>,
+    pub sans: Vec<String>,
+    keys: Vec<u64>,
+}
+
+impl GameState {
+    pub fn new(board: pleco::Board) -> Self {
+        GameState {
+            start_fen: board.fen(),
+            keys: vec![board.zobrist()],
+            board,
+            moves: vec![],
+            sans: vec![],
+        }
+    }
+
+    /// The position after the opening's moves, which are part of the game.
+    pub fn from_opening(opening: &Opening) -> Self {
+        // Openings are checked when they are loaded
+        let mut game = GameState::new(pleco::Board::from_fen(&opening.fen).unwrap());
+        for bit_move in &opening.moves {
+            game.push(*bit_move);
+        }
+        game
+    }
+
+    /// Plays `bit_move`, which must be legal.
+    pub fn push(&mut self, bit_move: pleco::BitMove) {
+        self.sans.push(to_san(&self.board, bit_move));
+        self.board.apply_move(bit_move);
+        self.moves.push(bit_move);
+        self.keys.push(self.board.zobrist());
+    }
+
+    /// Takes back the last move, returning whether there was one.
+    pub fn undo(&mut self) -> bool {
+        if self.moves.pop().is_none() {
+            return false;
+        }
+        self.sans.pop();
+        self.keys.pop();
+        self.board.undo_move();
+        true
+    }
+
+    pub fn is_legal(&self, bit_move: pleco::BitMove) -> bool {
+        // `legal_move` assumes a pseudo-legal move
+        self.board.pseudo_legal_move(bit_move) && self.board.legal_move(bit_move)
+    }
+
+    /// The legal move written as `uci`, if there is one.
+    pub fn find_uci(&self, uci: &str) -> Option<pleco::BitMove> {
+        let uci = uci.to_ascii_lowercase();
+        self.board
+            .generate_moves()
+            .iter()
+            .find(|m| m.stringify() == uci)
+            .copied()
+    }
+
+    /// The result and the reason for it once the rules end the game.
+    pub fn outcome(&self) -> Option<(Outcome, &'static str)> {
+        let board = &self.board;
+        if board.checkmate() {
+            return Some(match board.turn() {
+                pleco::Player::White => (Outcome::BlackWins, "checkmate"),
+                pleco::Player::Black => (Outcome::WhiteWins, "checkmate"),
+            });
+        }
+        if board.stalemate() {
+            return Some((Outcome::Draw, "stalemate"));
+        }
+        if board.rule_50() >= 100 {
+            return Some((Outcome::Draw, "fifty-move rule"));
+        }
+        let key = board.zobrist();
+        if self.keys.iter().filter(|k| **k == key).count() >= 3 {
+            return Some((Outcome::Draw, "threefold repetition"));
+        }
+        // Bare kings, or a single minor piece left
+        let minors = board.piece_bb_both_players(pleco::PieceType::N)
+            | board.piece_bb_both_players(pleco::PieceType::B);
+        if board.count_all_pieces() == 2
+            || (board.count_all_pieces() == 3 && minors.count_bits() == 1)
+        {
+            return Some((Outcome::Draw, "insufficient material"));
+        }
+        None
+    }
+
+    /// The game as PGN with the given tags, adding `SetUp` and `FEN` for non-standard starts.
+    pub fn to_pgn(&self, mut tags: Vec<(String, String)>) -> PgnGame {
+        if self.start_fen != pleco::Board::start_pos().fen() {
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), self.start_fen.clone()));
+        }
+        PgnGame {
+            tags,
+            moves: self.sans.clone(),
+        }
+    }
+}
+
+/// A start position, given as a FEN and the moves played from it.
+#[derive(Clone, Debug)]
+pub struct Opening {
+    pub fen: String,
+    pub moves: Vec<pleco::BitMove>,
+}
+
+impl Default for Opening {
+    fn default() -> Self {
+        Opening {
+            fen: pleco::Board::start_pos().fen(),
+            moves: vec![],
+        }
+    }
+}
+
+/// Reads openings from an EPD file, one position per line, or from a PGN book, keeping the
+/// first `book_plies` moves of every game with legal moves.
+pub fn load_openings(path: &str, book_plies: usize) -> io::Result<Vec<Opening>> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let file =
+        File::open(path).map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path, err)))?;
+    let reader = BufReader::new(file);
+
+    let is_pgn = Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("pgn"));
+    let mut openings = vec![];
+    if is_pgn {
+        // Games that cannot be read or replayed are skipped, as when converting
+        for game in PgnReader::new(reader).flatten() {
+            let start = match game.start_board() {
+                Ok(start) => start,
+                Err(_) => continue,
+            };
+            let mut moves = vec![];
+            let replayed = game.replay(|_, bit_move| {
+                if moves.len() < book_plies {
+                    moves.push(bit_move)
+                }
+            });
+            if replayed.is_err() {
+                continue;
+            }
+            openings.push(Opening {
+                fen: start.fen(),
+                moves,
+            });
+        }
+    } else {
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            // EPD holds the first four FEN fields, then operations
+            let fields = line.split_whitespace().take(4).collect::<Vec<_>>();
+            if fields.is_empty() {
+                continue;
+            }
+            let fen = format!("{} 0 1", fields.join(" "));
+            pleco::Board::from_fen(&fen)
+                .map_err(|_| invalid(format!("{}:{}: invalid position", path, idx + 1)))?;
+            openings.push(Opening { fen, moves: vec![] });
+        }
+    }
+    if openings.is_empty() {
+        return Err(invalid(format!("{}: no openings found", path)));
+    }
+    Ok(openings)
+}
+
+/// Rules ending games early.
+#[derive(Clone)]
+pub struct Adjudication {
+    /// Games reaching this many plies are drawn.
+    pub max_plies: usize,
+    /// Games are decided by the tablebase once few enough pieces are left.
+    pub tablebase: Option<Tablebase>,
+    pub tablebase_pieces: usize,
+}
+
+impl Default for Adjudication {
+    fn default() -> Self {
+        Adjudication {
+            max_plies: 400,
+            tablebase: None,
+            tablebase_pieces: 0,
+        }
+    }
+}
+
+impl Adjudication {
+    fn adjudicate(&self, game: &GameState) -> Option<(Outcome, &'static str)> {
+        if game.moves.len() >= self.max_plies {
+            return Some((Outcome::Draw, "adjudicated: move limit"));
+        }
+        let tablebase = self.tablebase.as_ref()?;
+        if game.board.count_all_pieces() as usize > self.tablebase_pieces {
+            return None;
+        }
+        let wdl = tablebase.wdl(&game.board).ok()?;
+        let outcome = match (wdl.signum(), game.board.turn()) {
+            (0, _) => Outcome::Draw,
+            (1, pleco::Player::White) | (-1, pleco::Player::Black) => Outcome::WhiteWins,
+            _ => Outcome::BlackWins,
+        };
+        Some((outcome, "adjudicated: tablebase"))
+    }
+}
+
+/// A finished game and why it ended.
+pub struct FinishedGame {
+    pub state: GameState,
+    pub outcome: Outcome,
+    pub reason: String,
+}
+
+/// Plays `white` against `black` from `opening` until the rules or `adjudication` end the game.
+///
+/// An engine that fails to produce a legal move, or panics, forfeits.
+pub fn play_game(
+    white: &mut dyn Engine,
+    black: &mut dyn Engine,
+    opening: &Opening,
+    adjudication: &Adjudication,
+) -> FinishedGame {
+    let mut state = GameState::from_opening(opening);
+    loop {
+        if let Some((outcome, reason)) = state.outcome().or_else(|| adjudication.adjudicate(&state))
+        {
+            return FinishedGame {
+                state,
+                outcome,
+                reason: reason.to_string(),
+            };
+        }
+
+        let (engine, forfeit): (&mut dyn Engine, _) = match state.board.turn() {
+            pleco::Player::White => (white, Outcome::BlackWins),
+            pleco::Player::Black => (black, Outcome::WhiteWins),
+        };
+        // The engine is dropped with the game, so whatever state a panic left it in is not reused
+        let best_move = panic::catch_unwind(AssertUnwindSafe(|| engine.best_move(&state)))
+            .unwrap_or_else(|payload| Err(panicked(engine.name(), payload)));
+        match best_move {
+            Ok(bit_move) if state.is_legal(bit_move) => state.push(bit_move),
+            Ok(bit_move) => {
+                let reason = format!(
+                    "forfeit: {} played illegal move {}",
+                    engine.name(),
+                    bit_move
+                );
+                return FinishedGame {
+                    state,
+                    outcome: forfeit,
+                    reason,
+                };
+            }
+            Err(err) => {
+                return FinishedGame {
+                    state,
+                    outcome: forfeit,
+                    reason: format!("forfeit: {}", err),
+                }
+            }
+        }
+    }
+}
+
+fn panicked(name: String, payload: Box<dyn std::any::Any + Send>) -> io::Error {
+    let message = match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload
+            .downcast::<&str>()
+            .map_or("unknown cause".to_string(), |message| message.to_string()),
+    };
+    io::Error::other(format!("{} panicked: {}", name, message))
+}
+
+/// The current UTC date in PGN's `YYYY.MM.DD` form.
+pub fn today() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() / 86_400) as i64;
+    // Civil date from days since 1970-01-01, after Howard Hinnant's `civil_from_days`
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FirstMove;
+
+    impl Engine for FirstMove {
+        fn name(&self) -> String {
+            "first".to_string()
+        }
+
+        fn best_move(&mut self, game: &GameState) -> io::Result<pleco::BitMove> {
+            Ok(game.board.generate_moves()[0])
+        }
+    }
+
+    struct Panicking;
+
+    impl Engine for Panicking {
+        fn name(&self) -> String {
+            "panicking".to_string()
+        }
+
+        fn best_move(&mut self, _: &GameState) -> io::Result<pleco::BitMove> {
+            panic!("out of cheese")
+        }
+    }
+
+    #[test]
+    fn panicking_engines_forfeit() {
+        let game = play_game(
+            &mut FirstMove,
+            &mut Panicking,
+            &Opening::default(),
+            &Adjudication::default(),
+        );
+        assert_eq!(game.outcome, Outcome::WhiteWins);
+        assert_eq!(game.reason, "forfeit: panicking panicked: out of cheese");
+        assert_eq!(game.state.moves.len(), 1);
+    }
+
+    #[test]
+    fn adjudicates_long_games() {
+        let adjudication = Adjudication {
+            max_plies: 6,
+            ..Adjudication::default()
+        };
+        let game = play_game(
+            &mut FirstMove,
+            &mut FirstMove,
+            &Opening::default(),
+            &adjudication,
+        );
+        assert_eq!(game.outcome, Outcome::Draw);
+        assert_eq!(game.reason, "adjudicated: move limit");
+        assert_eq!(game.state.moves.len(), 6);
+    }
+}
@@ -1,36 +1,106 @@
 use action_space::{action_to_move_string, ACTION_SPACE_LEN};
+use numpy::PyArray1;
+use pyo3::exceptions::PyIOError;
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyList, PyString};
+use pyo3::types::{PyDict, PyList};
 use std::collections::HashMap;
 
 mod action_space;
 mod bitboard;
 mod board;
+mod board_builder;
 mod core;
+pub mod dataset;
+mod endgame;
+pub mod engine;
 mod error;
+mod evaluation;
+mod features;
+pub mod game;
+pub mod observation;
+pub mod pgn;
 mod piece_move;
+mod recorder;
+pub mod render;
+mod sampler;
 mod score;
+mod search;
 mod square;
+pub mod tablebase;
+pub mod tournament;
+mod transposition;
 
 use crate::action_space::{Action, ActionId, ACTION_SPACE};
-use crate::bitboard::BitBoard;
+use crate::bitboard::{BitBoard, BitBoardIterator};
 use crate::board::Board;
+use crate::board_builder::BoardBuilder;
 use crate::core::{CastleType, GenTypes, Piece, PieceType, Player};
+use crate::dataset::{DatasetReader, DatasetWriter, TrainingRecord};
+use crate::error::{IllegalActionError, InvalidActionIdError, InvalidArgumentError};
 use crate::piece_move::{BitMove, ScoringMove};
+use crate::recorder::{EpisodeReplay, RecordFormat, Recorder, ReplayStep, StepRecord};
+use crate::render::animation::Animation;
+use crate::render::raster::{self, Overlay};
+use crate::render::svg::{board_svg, Arrow, SvgOptions, ARROW_COLOUR, ARROW_WIDTH};
+use crate::render::RenderMode;
+use crate::sampler::{
+    entropy_seed, seeded_prng, EndgameGenerator, GamePhase, StartPositionSampler,
+};
 use crate::score::Score;
+use crate::search::{SearchHandle, SearchResult};
 use crate::square::Square;
+use crate::tablebase::{Tablebase, Wdl};
+use crate::tournament::sprt::{Sprt, SprtResult};
+use crate::tournament::{Participant, Tournament, TournamentResult};
+use crate::transposition::{Bound, TableEntry, TranspositionTable};
+use pleco::tools::prng::PRNG;
+
+// Probabilities below this fraction of the largest are not drawn
+const POLICY_CUTOFF: f32 = 0.01;
+
+/// A policy over the action space, either a dict of action ids or a value for every action.
+#[derive(FromPyObject)]
+pub enum Policy {
+    Sparse(HashMap<ActionId, f32>),
+    Dense(Vec<f32>),
+}
 
 #[pyclass]
 pub struct ChessEnv {
     pub board: Board,
     pub step_num: u64,
     pub action_map: HashMap<ActionId, Action>,
+    pub sampler: Option<StartPositionSampler>,
+    pub tablebase: Option<Tablebase>,
+    pub adjudication_pieces: usize,
+    pub adjudication: Option<Wdl>,
+    pub transposition_table: Option<TranspositionTable>,
+    pub episode: u32,
+    prng: PRNG,
+    recorder: Option<Recorder>,
+    render_mode: Option<RenderMode>,
 }
 
 impl ChessEnv {
+    fn unavailable_action(&self, action_id: ActionId) -> PyErr {
+        match action_to_move_string(action_id) {
+            Ok(uci) => IllegalActionError::new_err(format!(
+                "action {} ({}) is not legal in position {}",
+                action_id,
+                uci,
+                self.board.fen()
+            )),
+            Err(err) => InvalidActionIdError::new_err(err.to_string()),
+        }
+    }
+
     pub fn move_to_action(bit_move: &pleco::BitMove) -> ActionId {
         let sq_src = bit_move.get_src();
-        let diff_col = bit_move.dest_col() as i8 - bit_move.src_col() as i8;
+        let mut diff_col = bit_move.dest_col() as i8 - bit_move.src_col() as i8;
+        // pleco moves the king onto its rook when castling; the action is the king's two steps
+        if bit_move.is_castle() {
+            diff_col = 2 * diff_col.signum();
+        }
         let diff_row = bit_move.dest_row() as i8 - bit_move.src_row() as i8;
 
         // Underpromotion
@@ -51,7 +121,7 @@ impl ChessEnv {
                 _ => unreachable!(),
             };
 
-            let promo = target_piece * 4 + promo_move;
+            let promo = target_piece * 3 + promo_move;
             // println!("promo: {}", promo);
 
             let move_type = 8 * 7 + 8 + promo;
@@ -72,8 +142,7 @@ impl ChessEnv {
                 (-2, -1) => 5,
                 (-2, 1) => 6,
                 (-1, 2) => 7,
-                #[allow(unreachable_code)]
-                _ => !unreachable!(),
+                _ => unreachable!(),
             };
             let move_type = 8 * 7 + knight_move;
             let action = (u8::from(sq_src) as ActionId) * 73 + move_type;
@@ -90,13 +159,25 @@ impl ChessEnv {
             (-1, -1) => 5,
             (-1, 0) => 6,
             (-1, 1) => 7,
-            #[allow(unreachable_code)]
-            _ => !unreachable!(),
+            _ => unreachable!(),
         };
         let num_steps = std::cmp::max(diff_row.abs(), diff_col.abs()) as u16;
-        let directional_move = direction * 8 + num_steps;
-        let action = (u8::from(sq_src) as ActionId) * 73 + directional_move;
-        return action;
+        let directional_move = direction * 7 + num_steps - 1;
+        (u8::from(sq_src) as ActionId) * 73 + directional_move
+    }
+
+    // Ends the game early with the tablebase verdict, from the point of view of the side to move,
+    // once few enough pieces are left
+    fn adjudicate(&self) -> Option<Wdl> {
+        let tablebase = self.tablebase.as_ref()?;
+        let board = self.board.inner();
+        if board.count_all_pieces() as usize > self.adjudication_pieces
+            || board.checkmate()
+            || board.stalemate()
+        {
+            return None;
+        }
+        tablebase.wdl(board).ok()
     }
 
     fn generate_actions(&mut self) {
@@ -142,23 +223,102 @@ impl ChessEnv {
     // - np_random
 
     #[new]
-    pub fn new() -> Self {
+    #[pyo3(signature = (
+        sampler=None,
+        tablebase=None,
+        adjudication_pieces=None,
+        render_mode=None,
+        transposition_table=None
+    ))]
+    pub fn new(
+        sampler: Option<StartPositionSampler>,
+        tablebase: Option<Tablebase>,
+        adjudication_pieces: Option<usize>,
+        render_mode: Option<&str>,
+        transposition_table: Option<TranspositionTable>,
+    ) -> PyResult<Self> {
+        let render_mode = render_mode
+            .map(|name| {
+                RenderMode::from_name(name).ok_or_else(|| {
+                    let supported = RenderMode::ALL.map(|mode| mode.name());
+                    InvalidArgumentError::new_err(format!(
+                        "unknown render mode {:?}, expected one of {:?}",
+                        name, supported
+                    ))
+                })
+            })
+            .transpose()?;
+        // Adjudicate as soon as the position is covered by the tables unless told otherwise
+        let adjudication_pieces = adjudication_pieces
+            .or_else(|| tablebase.as_ref().map(|tb| tb.max_pieces()))
+            .unwrap_or(0);
         let mut env = Self {
             board: Board::new(),
             step_num: 0,
             action_map: HashMap::new(),
+            sampler,
+            tablebase,
+            adjudication_pieces,
+            adjudication: None,
+            transposition_table,
+            episode: 0,
+            // Unseeded environments draw start positions from a time-based stream, like gymnasium
+            prng: seeded_prng(entropy_seed()),
+            recorder: None,
+            render_mode,
         };
         env.generate_actions();
-        env
+        Ok(env)
+    }
+
+    #[classattr]
+    fn metadata(py: Python) -> PyObject {
+        let metadata = PyDict::new(py);
+        let render_modes = RenderMode::ALL.map(|mode| mode.name());
+        // Setting items on a fresh dict cannot fail
+        metadata.set_item("render_modes", render_modes).unwrap();
+        metadata.set_item("render_fps", 4).unwrap();
+        metadata.into()
     }
 
+    #[getter]
+    fn render_mode(&self) -> Option<&'static str> {
+        self.render_mode.map(|mode| mode.name())
+    }
+
+    /// Starts a new episode from the start position, a position drawn from the sampler, or the
+    /// FEN given as `options={"fen": ...}`, which takes precedence over the sampler.
+    #[pyo3(signature = (seed=None, options=None))]
     pub fn reset<'a>(
         &mut self,
         py: Python<'a>,
-        _seed: Option<i64>,
-        _options: Option<&'a PyDict>,
+        seed: Option<i64>,
+        options: Option<&'a PyDict>,
     ) -> PyResult<(&'a PyList, &'a PyDict)> {
-        self.board = Board::start_pos();
+        let mut fen = None;
+        for (key, value) in options.into_iter().flatten() {
+            match key.extract::<&str>()? {
+                "fen" => fen = Some(value.extract::<&str>()?),
+                other => {
+                    return Err(InvalidArgumentError::new_err(format!(
+                        "unknown reset option {:?}, expected \"fen\"",
+                        other
+                    )))
+                }
+            }
+        }
+        if let Some(seed) = seed {
+            self.prng = seeded_prng(seed as u64);
+        }
+        self.board = match (fen, &self.sampler) {
+            (Some(fen), _) => Board::from_fen(fen)?,
+            (None, Some(sampler)) => sampler.sample_board(&mut self.prng).into(),
+            (None, None) => Board::start_pos(),
+        };
+        self.step_num = 0;
+        self.episode += 1;
+        self.adjudication = None;
+        self.generate_actions();
         let obsrv = self.get_state(py)?;
 
         let info = PyDict::new(py);
@@ -174,37 +334,193 @@ impl ChessEnv {
         // input: action: ActType
         // output: [ObsType, SupportsFloat, bool, bool, dict[str, Any]]
 
-        let action = self.action_map.get(&action_id).unwrap().clone();
+        let action = match self.action_map.get(&action_id) {
+            Some(action) => action.clone(),
+            None => return Err(self.unavailable_action(action_id)),
+        };
         let bit_move = action.bit_move;
 
-        self.board.apply_move(bit_move);
+        // The recorder logs the position the action was taken from
+        let mut record = self.recorder.as_ref().map(|_| {
+            let mut legal_actions = self.action_map.keys().copied().collect::<Vec<_>>();
+            legal_actions.sort_unstable();
+            StepRecord {
+                episode: self.episode,
+                step: self.step_num as u32,
+                fen: self.board.fen(),
+                zobrist: self.board.zobrist(),
+                action_id,
+                bit_move: pleco::BitMove::from(&bit_move).get_raw(),
+                reward: 0,
+                legal_actions,
+            }
+        });
+
+        // Actions are generated from the legal moves of the position
+        self.board.apply_move_unchecked(bit_move);
         self.step_num += 1;
         let obsrv = self.get_state(py)?;
 
         let is_checkmate = self.board.checkmate();
         let is_stalemate = self.board.stalemate();
 
-        let terminated = is_checkmate && is_stalemate;
+        self.adjudication = self.adjudicate();
+
+        let terminated = is_checkmate || is_stalemate || self.adjudication.is_some();
         let truncated = false;
+        let reward = 0;
+
+        if let (Some(recorder), Some(record)) = (self.recorder.as_mut(), record.as_mut()) {
+            record.reward = reward;
+            recorder
+                .write(record)
+                .map_err(|err| PyIOError::new_err(err.to_string()))?;
+        }
 
         // add to info: zobrist, fen
         // add to state: basically the state from pleco::Board.Arc<State>
         self.generate_actions();
 
-        Ok((obsrv, 0, terminated, truncated))
+        Ok((obsrv, reward, terminated, truncated))
+    }
+
+    /// Renders the board in the mode chosen at construction: a string for `ansi` and `unicode`,
+    /// an HxWx3 uint8 array for `rgb_array`. Without a mode, returns `Board.pretty_string()`.
+    ///
+    /// `heatmap` holds one value per square, a1 first, and is shaded over the `rgb_array` frame.
+    #[pyo3(signature = (heatmap=None))]
+    pub fn render(&mut self, py: Python, heatmap: Option<Vec<f32>>) -> PyResult<PyObject> {
+        if heatmap.is_some() && self.render_mode != Some(RenderMode::RgbArray) {
+            return Err(InvalidArgumentError::new_err(
+                "a heatmap can only be drawn in rgb_array mode",
+            ));
+        }
+        let board = self.board.inner();
+        let last_move = self.board.last_move().map(pleco::BitMove::from);
+        let frame = match self.render_mode {
+            None => self.board.pretty_string().into_py(py),
+            Some(RenderMode::Ansi) => render::ansi(board, last_move, false).into_py(py),
+            Some(RenderMode::Unicode) => render::unicode(board).into_py(py),
+            Some(RenderMode::RgbArray) => {
+                if heatmap.as_ref().is_some_and(|heat| heat.len() != 64) {
+                    return Err(InvalidArgumentError::new_err(
+                        "a heatmap needs one value per square",
+                    ));
+                }
+                let overlay = Overlay {
+                    last_move,
+                    heatmap: heatmap.as_deref(),
+                };
+                // Surfaces a missing NumPy as an ImportError rather than a panic
+                py.import("numpy")?;
+                let canvas = raster::draw(board, &overlay);
+                PyArray1::from_vec(py, canvas.pixels)
+                    .reshape([canvas.height, canvas.width, 3])?
+                    .into_py(py)
+            }
+        };
+        Ok(frame)
     }
 
-    pub fn render<'a>(&mut self, py: Python<'a>) -> PyResult<&'a PyString> {
-        let frame = self.board.pretty_string();
-        let py_frame = PyString::new(py, &frame);
-        Ok(py_frame)
+    /// An SVG of the board with an arrow for every legal action of `policy`, thicker the more
+    /// probable it is. Entries for actions that are not legal are ignored.
+    #[pyo3(signature = (policy, size=400, flipped=false))]
+    pub fn render_policy_svg(&self, policy: Policy, size: u32, flipped: bool) -> String {
+        let mut weights = self
+            .action_map
+            .values()
+            .map(|action| {
+                let p = match &policy {
+                    Policy::Sparse(p) => p.get(&action.id).copied(),
+                    Policy::Dense(p) => p.get(action.id as usize).copied(),
+                };
+                (pleco::BitMove::from(&action.bit_move), p.unwrap_or(0.0))
+            })
+            .collect::<Vec<_>>();
+        // The most probable arrows are drawn last, on top
+        weights.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let max = weights.last().map_or(0.0, |(_, p)| *p);
+
+        let board = self.board.inner();
+        let options = SvgOptions {
+            size,
+            flipped,
+            last_move: board.last_move(),
+            check: board.in_check().then(|| board.king_sq(board.turn())),
+            arrows: weights
+                .into_iter()
+                .filter(|(_, p)| max > 0.0 && *p >= max * POLICY_CUTOFF)
+                .map(|(bit_move, p)| Arrow {
+                    from: bit_move.get_src(),
+                    to: bit_move.get_dest(),
+                    colour: ARROW_COLOUR.to_string(),
+                    width: (1.5 * ARROW_WIDTH * p / max).max(1.0),
+                })
+                .collect(),
+            ..SvgOptions::default()
+        };
+        board_svg(board, &options)
     }
 
     pub fn close<'a>(&mut self, _py: Python<'a>) -> PyResult<()> {
-        Ok(())
+        self.stop_recording()
     }
 
     // Additioanl methods
+    /// Logs every following step to `path`, as JSON lines or in a compact binary format.
+    #[pyo3(signature = (path, format=RecordFormat::Jsonl))]
+    pub fn start_recording(&mut self, path: &str, format: RecordFormat) -> PyResult<()> {
+        self.stop_recording()?;
+        let recorder =
+            Recorder::create(path, format).map_err(|err| PyIOError::new_err(err.to_string()))?;
+        self.recorder = Some(recorder);
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) -> PyResult<()> {
+        if let Some(mut recorder) = self.recorder.take() {
+            recorder
+                .flush()
+                .map_err(|err| PyIOError::new_err(err.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Iterates over a recorded log, rebuilding every position and checking its zobrist key.
+    #[staticmethod]
+    pub fn replay(path: &str) -> PyResult<EpisodeReplay> {
+        EpisodeReplay::open(path).map_err(|err| PyIOError::new_err(err.to_string()))
+    }
+
+    /// Searches the current position like `Board.search`, through the transposition table the
+    /// environment was created with, if any.
+    #[pyo3(signature = (algorithm, depth=None, movetime_ms=None, nodes=None, handle=None))]
+    pub fn search(
+        &self,
+        py: Python,
+        algorithm: &str,
+        depth: Option<u16>,
+        movetime_ms: Option<u64>,
+        nodes: Option<u64>,
+        handle: Option<SearchHandle>,
+    ) -> PyResult<SearchResult> {
+        self.board.search(
+            py,
+            algorithm,
+            depth,
+            movetime_ms,
+            nodes,
+            handle,
+            self.transposition_table.clone(),
+        )
+    }
+
+    pub fn get_adjudication(&self) -> Option<Wdl> {
+        self.adjudication
+    }
+
+    /// The observation: the piece id on every square, as `Board.state()` gives it. These are
+    /// not the planes of `Board.observation()` that dataset records hold.
     pub fn get_state<'a>(&mut self, py: Python<'a>) -> PyResult<&'a PyList> {
         let state = self.board.state();
 
@@ -227,37 +543,105 @@ impl ChessEnv {
 
     pub fn get_action_mask<'a>(&self, py: Python<'a>) -> &'a PyList {
         let action_ids = self.action_map.keys().copied().collect::<Vec<_>>();
-        let mut action_mask = [0 as u16; ACTION_SPACE_LEN as usize];
+        let mut action_mask = [0u16; ACTION_SPACE_LEN as usize];
         for action_id in action_ids {
             action_mask[action_id as usize] = 1;
         }
         PyList::new(py, action_mask)
     }
 
+    /// The UCI move of every action id, indexed by id. Ids that lead off the board are `None`;
+    /// queen promotions are listed without their promotion piece.
     #[staticmethod]
     pub fn build_action_space<'a>(py: Python<'a>) -> &'a PyList {
-        // Includes illegal moves which need to be filtered out
-        let action_space = ACTION_SPACE.map(action_to_move_string).collect::<Vec<_>>();
-        let list = PyList::new(py, action_space);
-        todo!()
+        // Includes moves that are illegal in any given position
+        let action_space = ACTION_SPACE
+            .map(|action| action_to_move_string(action).ok())
+            .collect::<Vec<_>>();
+        PyList::new(py, action_space)
     }
 }
 
 /// A Python module implemented in Rust.
 #[pymodule]
-fn gym_chess_pleco(_py: Python, m: &PyModule) -> PyResult<()> {
+fn gym_chess_pleco(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<Action>()?;
+    m.add_class::<Animation>()?;
     m.add_class::<BitMove>()?;
     m.add_class::<BitBoard>()?;
+    m.add_class::<BitBoardIterator>()?;
     m.add_class::<Board>()?;
+    m.add_class::<BoardBuilder>()?;
+    m.add_class::<Bound>()?;
     m.add_class::<CastleType>()?;
     m.add_class::<ChessEnv>()?;
+    m.add_class::<DatasetReader>()?;
+    m.add_class::<DatasetWriter>()?;
+    m.add_class::<EndgameGenerator>()?;
+    m.add_class::<GamePhase>()?;
     m.add_class::<GenTypes>()?;
+    m.add_class::<Participant>()?;
     m.add_class::<Piece>()?;
     m.add_class::<PieceType>()?;
     m.add_class::<Player>()?;
+    m.add_class::<RecordFormat>()?;
+    m.add_class::<ReplayStep>()?;
+    m.add_class::<EpisodeReplay>()?;
     m.add_class::<Score>()?;
     m.add_class::<ScoringMove>()?;
+    m.add_class::<Sprt>()?;
+    m.add_class::<SprtResult>()?;
+    m.add_class::<SearchHandle>()?;
+    m.add_class::<SearchResult>()?;
     m.add_class::<Square>()?;
+    m.add_class::<StartPositionSampler>()?;
+    m.add_class::<TableEntry>()?;
+    m.add_class::<Tablebase>()?;
+    m.add_class::<Tournament>()?;
+    m.add_class::<TournamentResult>()?;
+    m.add_class::<TrainingRecord>()?;
+    m.add_class::<TranspositionTable>()?;
+    m.add_class::<Wdl>()?;
+    error::register(py, m)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env_at(tablebase: Tablebase, adjudication_pieces: Option<usize>, fen: &str) -> ChessEnv {
+        let mut env =
+            ChessEnv::new(None, Some(tablebase), adjudication_pieces, None, None).unwrap();
+        env.board = pleco::Board::from_fen(fen).unwrap().into();
+        env
+    }
+
+    #[test]
+    fn adjudication_needs_tables_for_the_position() {
+        let empty = std::env::temp_dir().join("gym_chess_pleco_no_tables");
+        std::fs::create_dir_all(&empty).unwrap();
+        let tablebase = Tablebase::open(empty.to_str().unwrap()).unwrap();
+        let krk = "4k3/8/8/8/8/8/8/R3K3 b - - 0 1";
+        assert_eq!(env_at(tablebase.clone(), None, krk).adjudication_pieces, 0);
+        assert_eq!(env_at(tablebase, Some(3), krk).adjudicate(), None);
+    }
+
+    #[test]
+    fn adjudicates_positions_covered_by_the_tables() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/syzygy");
+        let tablebase = Tablebase::open(dir).unwrap();
+
+        let krk = env_at(tablebase.clone(), None, "4k3/8/8/8/8/8/8/R3K3 b - - 0 1");
+        assert_eq!(krk.adjudication_pieces, 3);
+        assert_eq!(krk.adjudicate(), Some(Wdl::Loss));
+        let kpk = env_at(tablebase.clone(), None, "8/4P3/8/8/8/8/k7/4K3 w - - 0 1");
+        assert_eq!(kpk.adjudicate(), Some(Wdl::Win));
+        // Positions with more pieces than asked for are played on
+        let kqk = env_at(tablebase.clone(), Some(2), "k7/8/1K6/8/8/8/7Q/8 w - - 0 1");
+        assert_eq!(kqk.adjudicate(), None);
+        // Finished games are left to the rules
+        let mated = env_at(tablebase, None, "R3k3/8/4K3/8/8/8/8/8 b - - 0 1");
+        assert_eq!(mated.adjudicate(), None);
+    }
+}
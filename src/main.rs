@@ -1,18 +1,35 @@
-use pleco::{Board, Piece, PieceType, Player, SQ};
+use clap::{Parser, Subcommand};
 
-fn main() {
-    println!("Hello World");
+mod cli;
 
-    let board = Board::start_pos();
-    let board_fen = board.fen();
-    println!("board_fen: {}", board_fen);
+#[derive(Parser)]
+#[command(version, about = "Command line tools for gym-chess-pleco")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
 
-    let moves = board.generate_moves();
+#[derive(Subcommand)]
+enum Command {
+    /// Converts PGN databases into sharded supervised-learning datasets
+    Convert(cli::convert::ConvertArgs),
+    /// Plays a game in the terminal against a pleco searcher or a UCI engine
+    Play(cli::play::PlayArgs),
+    /// Runs a sequential probability ratio test between two engines
+    Sprt(cli::sprt::SprtArgs),
+    /// Plays a round robin or gauntlet between engines and rates them
+    Tournament(cli::tournament::TournamentArgs),
+}
 
-    for move_ in moves {
-        println!("move: {}", move_);
+fn main() {
+    let result = match Cli::parse().command {
+        Command::Convert(args) => cli::convert::run(args),
+        Command::Play(args) => cli::play::run(args),
+        Command::Sprt(args) => cli::sprt::run(args),
+        Command::Tournament(args) => cli::tournament::run(args),
+    };
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
     }
-
-    let sq = SQ::A1;
-    println!("square: {}", sq);
 }
@@ -0,0 +1,107 @@
+// Observation planes of the dataset writers and `Board.observation()`.
+//
+// `ChessEnv` observations are not these planes but one piece id per square, from `Board.state()`,
+// so an agent trained on the environment sees another encoding than the dataset records.
+//
+// Every plane is binary, so an observation is stored as one bitboard per plane
+// (bit `i` set <=> square `i` is set, a1 = 0 .. h8 = 63):
+//   0..6    white pawns, knights, bishops, rooks, queens, king
+//   6..12   black pawns, knights, bishops, rooks, queens, king
+//   12      side to move (all ones when white is to move)
+//   13..17  castling rights: white king side, white queen side, black king side, black queen side
+//   17      en passant square
+pub const NUM_PLANES: usize = 18;
+
+pub type Planes = [u64; NUM_PLANES];
+
+const PIECE_TYPES: [pleco::PieceType; 6] = [
+    pleco::PieceType::P,
+    pleco::PieceType::N,
+    pleco::PieceType::B,
+    pleco::PieceType::R,
+    pleco::PieceType::Q,
+    pleco::PieceType::K,
+];
+
+pub fn encode(board: &pleco::Board) -> Planes {
+    let mut planes = [0; NUM_PLANES];
+
+    for (idx, piece_type) in PIECE_TYPES.iter().enumerate() {
+        planes[idx] = board.piece_bb(pleco::Player::White, *piece_type).0;
+        planes[idx + 6] = board.piece_bb(pleco::Player::Black, *piece_type).0;
+    }
+
+    let full = |set: bool| if set { u64::MAX } else { 0 };
+    planes[12] = full(board.turn() == pleco::Player::White);
+    planes[13] = full(board.can_castle(pleco::Player::White, pleco::core::CastleType::KingSide));
+    planes[14] = full(board.can_castle(pleco::Player::White, pleco::core::CastleType::QueenSide));
+    planes[15] = full(board.can_castle(pleco::Player::Black, pleco::core::CastleType::KingSide));
+    planes[16] = full(board.can_castle(pleco::Player::Black, pleco::core::CastleType::QueenSide));
+
+    let ep_square = board.ep_square();
+    if ep_square != pleco::SQ::NONE {
+        planes[17] = ep_square.to_bb().0;
+    }
+
+    planes
+}
+
+/// Expands packed planes to `[plane][rank][file]` 0/1 values, rank 0 being the first rank.
+pub fn unpack(planes: &Planes) -> Vec<[[u8; 8]; 8]> {
+    planes
+        .iter()
+        .map(|plane| {
+            let mut grid = [[0; 8]; 8];
+            for (rank, row) in grid.iter_mut().enumerate() {
+                for (file, value) in row.iter_mut().enumerate() {
+                    *value = ((plane >> (rank * 8 + file)) & 1) as u8;
+                }
+            }
+            grid
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_the_start_position() {
+        let planes = encode(&pleco::Board::start_pos());
+        assert_eq!(planes[0], 0xff00);
+        assert_eq!(planes[1], 1 << 1 | 1 << 6);
+        assert_eq!(planes[2], 1 << 2 | 1 << 5);
+        assert_eq!(planes[3], 1 << 0 | 1 << 7);
+        assert_eq!(planes[4], 1 << 3);
+        assert_eq!(planes[5], 1 << 4);
+        for (white, black) in planes[..6].iter().zip(&planes[6..12]) {
+            assert_eq!(white.swap_bytes(), *black);
+        }
+        assert_eq!(planes[12..17], [u64::MAX; 5]);
+        assert_eq!(planes[17], 0);
+    }
+
+    #[test]
+    fn encodes_turn_castling_and_en_passant() {
+        let board = pleco::Board::from_fen("r3k3/8/8/8/3pP3/8/8/4K2R b Kq e3 0 1").unwrap();
+        let planes = encode(&board);
+        assert_eq!(planes[12], 0);
+        assert_eq!(planes[13..17], [u64::MAX, 0, 0, u64::MAX]);
+        // e3
+        assert_eq!(planes[17], 1 << 20);
+    }
+
+    #[test]
+    fn unpacks_by_rank_then_file() {
+        let mut planes = [0; NUM_PLANES];
+        // b1 and h8
+        planes[3] = 1 << 1 | 1 << 63;
+        let grids = unpack(&planes);
+        assert_eq!(grids.len(), NUM_PLANES);
+        assert_eq!(grids[3][0][1], 1);
+        assert_eq!(grids[3][7][7], 1);
+        assert_eq!(grids[3].iter().flatten().sum::<u8>(), 2);
+        assert!(grids[0].iter().flatten().all(|value| *value == 0));
+    }
+}
@@ -0,0 +1,552 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::Path;
+
+/// A single game read from a PGN file.
+///
+/// Moves are kept as the raw SAN tokens of the main line; comments, variations and NAGs are
+/// dropped while parsing.
+#[derive(Clone, Debug, Default)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    InvalidFen(String),
+    IllegalMove { ply: usize, san: String },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::InvalidFen(fen) => write!(f, "invalid FEN tag: {}", fen),
+            ReplayError::IllegalMove { ply, san } => {
+                write!(f, "illegal move at ply {}: {}", ply, san)
+            }
+        }
+    }
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn start_board(&self) -> Result<pleco::Board, ReplayError> {
+        match self.tag("FEN") {
+            Some(fen) => {
+                pleco::Board::from_fen(fen).map_err(|_| ReplayError::InvalidFen(fen.to_string()))
+            }
+            None => Ok(pleco::Board::start_pos()),
+        }
+    }
+
+    /// Replays the main line, calling `visit` with every position and the move played from it.
+    ///
+    /// Returns the final position, or the first move that could not be resolved.
+    pub fn replay<F>(&self, mut visit: F) -> Result<pleco::Board, ReplayError>
+    where
+        F: FnMut(&pleco::Board, pleco::BitMove),
+    {
+        let mut board = self.start_board()?;
+        for (ply, san) in self.moves.iter().enumerate() {
+            let bit_move = parse_san(&board, san).ok_or_else(|| ReplayError::IllegalMove {
+                ply,
+                san: san.clone(),
+            })?;
+            visit(&board, bit_move);
+            board.apply_move(bit_move);
+        }
+        Ok(board)
+    }
+}
+
+impl fmt::Display for PgnGame {
+    /// Writes the game as PGN: the tags, then the movetext wrapped at 80 columns and ended by
+    /// the `Result` tag.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (key, value) in &self.tags {
+            writeln!(f, "[{} \"{}\"]", key, value.replace('"', "\\\""))?;
+        }
+        writeln!(f)?;
+
+        // Numbering follows the start position when the game has one
+        let fen = self.tag("FEN").unwrap_or_default();
+        let mut fields = fen.split_whitespace().skip(1);
+        let mut white_to_move = fields.next() != Some("b");
+        let mut number = fields.nth(3).and_then(|n| n.parse().ok()).unwrap_or(1);
+
+        let mut tokens = vec![];
+        for (ply, san) in self.moves.iter().enumerate() {
+            if white_to_move {
+                tokens.push(format!("{}. {}", number, san));
+            } else if ply == 0 {
+                tokens.push(format!("{}... {}", number, san));
+            } else {
+                tokens.push(san.clone());
+            }
+            if !white_to_move {
+                number += 1;
+            }
+            white_to_move = !white_to_move;
+        }
+        tokens.push(self.tag("Result").unwrap_or("*").to_string());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + token.len() >= 80 {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        writeln!(f, "{}", line)
+    }
+}
+
+/// Streams games out of a PGN source one at a time, so arbitrarily large files can be read.
+///
+/// Text that is not UTF-8, such as Latin-1 player names, is decoded lossily. The reader stops
+/// after the first I/O error.
+pub struct PgnReader<R: BufRead> {
+    reader: R,
+    pending: Option<String>,
+    failed: bool,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            pending: None,
+            failed: false,
+        }
+    }
+
+    fn read_line(&mut self) -> Option<io::Result<String>> {
+        let mut line = vec![];
+        match self.reader.read_until(b'\n', &mut line) {
+            Ok(0) => None,
+            Ok(_) => Some(Ok(String::from_utf8_lossy(&line).into_owned())),
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = io::Result<PgnGame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let mut tags = vec![];
+        let mut movetext = String::new();
+
+        loop {
+            let line = match self.pending.take() {
+                Some(line) => line,
+                None => match self.read_line() {
+                    Some(Ok(line)) => line,
+                    Some(Err(err)) => return Some(Err(err)),
+                    None => break,
+                },
+            };
+            let line = line.trim_start_matches('\u{feff}').trim();
+
+            if line.starts_with('%') {
+                continue;
+            }
+            if line.starts_with('[') {
+                // A tag after some movetext starts the next game
+                if !movetext.trim().is_empty() {
+                    self.pending = Some(line.to_string());
+                    break;
+                }
+                if let Some(tag) = parse_tag(line) {
+                    tags.push(tag);
+                }
+            } else {
+                movetext.push_str(line);
+                movetext.push('\n');
+            }
+        }
+
+        if tags.is_empty() && movetext.trim().is_empty() {
+            return None;
+        }
+        Some(Ok(PgnGame {
+            tags,
+            moves: parse_movetext(&movetext),
+        }))
+    }
+}
+
+/// Writes games to a PGN file as they finish, so an interrupted run keeps the games played.
+pub struct PgnWriter {
+    writer: BufWriter<File>,
+    games: usize,
+}
+
+impl PgnWriter {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+            games: 0,
+        })
+    }
+
+    pub fn write(&mut self, game: &PgnGame) -> io::Result<()> {
+        if self.games > 0 {
+            writeln!(self.writer)?;
+        }
+        write!(self.writer, "{}", game)?;
+        self.games += 1;
+        self.writer.flush()
+    }
+}
+
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.trim_end().strip_suffix(']')?;
+    let (key, value) = inner.split_once(char::is_whitespace)?;
+    let value = value.trim().trim_matches('"').replace("\\\"", "\"");
+    Some((key.to_string(), value))
+}
+
+fn parse_movetext(movetext: &str) -> Vec<String> {
+    let mut moves = vec![];
+
+    let mut cleaned = String::with_capacity(movetext.len());
+    let mut chars = movetext.chars();
+    let mut variation_depth = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                }
+                cleaned.push(' ');
+            }
+            ';' => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                cleaned.push(' ');
+            }
+            '(' => variation_depth += 1,
+            ')' => variation_depth -= 1,
+            _ if variation_depth > 0 => {}
+            _ => cleaned.push(c),
+        }
+    }
+
+    for token in cleaned.split_whitespace() {
+        if let "1-0" | "0-1" | "1/2-1/2" | "*" = token {
+            continue;
+        }
+        if token.starts_with('$') {
+            continue;
+        }
+        // Strip move numbers, including ones glued to the move as in "12.e4"
+        let token = match token.rfind('.') {
+            Some(idx) if token[..idx].chars().all(|c| c.is_ascii_digit() || c == '.') => {
+                &token[idx + 1..]
+            }
+            _ => token,
+        };
+        if !token.is_empty() {
+            moves.push(token.to_string());
+        }
+    }
+
+    moves
+}
+
+/// Resolves a SAN string against the legal moves of `board`.
+///
+/// Check suffixes, annotations and the `=` of promotions are optional, and castling may be
+/// written with zeros.
+pub fn parse_san(board: &pleco::Board, san: &str) -> Option<pleco::BitMove> {
+    let wanted = normalize_san(san);
+    board
+        .generate_moves()
+        .iter()
+        .copied()
+        .find(|&bit_move| normalize_san(&san_body(board, bit_move)) == wanted)
+}
+
+/// Writes `bit_move`, which must be legal in `board`, in SAN with its check or mate suffix.
+pub fn to_san(board: &pleco::Board, bit_move: pleco::BitMove) -> String {
+    let mut san = san_body(board, bit_move);
+    let mut after = board.parallel_clone();
+    after.apply_move(bit_move);
+    if after.checkmate() {
+        san.push('#');
+    } else if after.in_check() {
+        san.push('+');
+    }
+    san
+}
+
+fn normalize_san(san: &str) -> String {
+    san.trim_end_matches(['+', '#', '!', '?'])
+        .replace('0', "O")
+        .replace('=', "")
+}
+
+fn san_body(board: &pleco::Board, bit_move: pleco::BitMove) -> String {
+    if bit_move.is_king_castle() {
+        return "O-O".to_string();
+    }
+    if bit_move.is_queen_castle() {
+        return "O-O-O".to_string();
+    }
+
+    let src = bit_move.get_src();
+    let dest = bit_move.get_dest();
+    let piece_type = board.moved_piece(bit_move).type_of();
+    let is_capture = bit_move.is_capture() || bit_move.is_en_passant();
+
+    let mut san = String::new();
+    if piece_type == pleco::PieceType::P {
+        if is_capture {
+            san.push(file_char(src));
+            san.push('x');
+        }
+        san.push_str(&dest.to_string());
+        if bit_move.is_promo() {
+            san.push('=');
+            san.push(bit_move.promo_piece().char_upper());
+        }
+        return san;
+    }
+
+    san.push(piece_type.char_upper());
+
+    let rivals = board
+        .generate_moves()
+        .iter()
+        .filter(|m| {
+            m.get_dest() == dest
+                && m.get_src() != src
+                && !m.is_castle()
+                && board.moved_piece(**m).type_of() == piece_type
+        })
+        .map(|m| m.get_src())
+        .collect::<Vec<_>>();
+    if !rivals.is_empty() {
+        if rivals.iter().all(|sq| sq.file() != src.file()) {
+            san.push(file_char(src));
+        } else if rivals.iter().all(|sq| sq.rank() != src.rank()) {
+            san.push(rank_char(src));
+        } else {
+            san.push(file_char(src));
+            san.push(rank_char(src));
+        }
+    }
+
+    if is_capture {
+        san.push('x');
+    }
+    san.push_str(&dest.to_string());
+    san
+}
+
+fn file_char(sq: pleco::SQ) -> char {
+    (b'a' + sq.file_idx_of_sq()) as char
+}
+
+fn rank_char(sq: pleco::SQ) -> char {
+    (b'1' + sq.rank_idx_of_sq()) as char
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAMES: &str = "\u{feff}[Event \"Casual\"]
+[White \"Anderssen\"]
+[Result \"1-0\"]
+
+1. e4 {King's pawn} e5 2. Nf3 (2. f4 exf4 3. Bc4) Nc6 $1 3.Bb5 a6 ; the rest
+4. Ba4 1-0
+% an escaped line
+[Event \"Study\"]
+[FEN \"4k3/8/8/8/8/8/8/R3K3 b Q - 0 40\"]
+[Result \"*\"]
+
+40... Kd7 41. O-O-O+ *
+";
+
+    fn read(pgn: &[u8]) -> Vec<PgnGame> {
+        PgnReader::new(pgn).collect::<io::Result<Vec<_>>>().unwrap()
+    }
+
+    fn board(fen: &str) -> pleco::Board {
+        pleco::Board::from_fen(fen).unwrap()
+    }
+
+    #[test]
+    fn reads_main_lines() {
+        let games = read(GAMES.as_bytes());
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].tag("Event"), Some("Casual"));
+        assert_eq!(games[0].tag("Result"), Some("1-0"));
+        assert_eq!(
+            games[0].moves,
+            ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4"]
+        );
+        assert_eq!(games[1].moves, ["Kd7", "O-O-O+"]);
+
+        let end = games[1].replay(|_, _| {}).unwrap();
+        assert_eq!(end.fen(), "8/3k4/8/8/8/8/8/2KR4 b - - 2 41");
+    }
+
+    #[test]
+    fn writes_what_it_reads() {
+        for game in read(GAMES.as_bytes()) {
+            let written = game.to_string();
+            let reread = read(written.as_bytes());
+            assert_eq!(reread.len(), 1, "{}", written);
+            assert_eq!(reread[0].tags, game.tags);
+            assert_eq!(reread[0].moves, game.moves);
+        }
+        let study = read(GAMES.as_bytes()).remove(1).to_string();
+        assert!(study.ends_with("\n40... Kd7 41. O-O-O+ *\n"));
+    }
+
+    #[test]
+    fn decodes_latin1_lossily() {
+        let mut pgn = b"[White \"M\xfcller\"]\n\n1. d4 d5 *\n".to_vec();
+        pgn.extend_from_slice(GAMES.as_bytes());
+        let games = read(&pgn);
+        assert_eq!(games.len(), 3);
+        assert_eq!(games[0].tag("White"), Some("M\u{fffd}ller"));
+        assert_eq!(games[0].moves, ["d4", "d5"]);
+    }
+
+    #[test]
+    fn stops_after_an_io_error() {
+        struct Failing;
+        impl io::Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk on fire"))
+            }
+        }
+        let mut reader = PgnReader::new(io::BufReader::new(Failing));
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn reports_the_first_bad_move() {
+        let game = PgnGame {
+            tags: vec![],
+            moves: vec!["e4".into(), "e5".into(), "Ke3".into()],
+        };
+        match game.replay(|_, _| {}) {
+            Err(ReplayError::IllegalMove { ply: 2, san }) => assert_eq!(san, "Ke3"),
+            other => panic!("{:?}", other.map(|board| board.fen())),
+        }
+        let game = PgnGame {
+            tags: vec![("FEN".into(), "8/8/8 w - - 0 1".into())],
+            moves: vec![],
+        };
+        assert!(matches!(
+            game.start_board(),
+            Err(ReplayError::InvalidFen(_))
+        ));
+    }
+
+    #[test]
+    fn parses_san_variants() {
+        let castling = board("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        for san in ["O-O", "0-0", "O-O+"] {
+            assert_eq!(parse_san(&castling, san).unwrap().stringify(), "e1g1");
+        }
+        assert!(parse_san(&castling, "0-0-0").unwrap().is_queen_castle());
+
+        let promotion = board("8/4P1k1/8/8/8/8/8/4K3 w - - 0 1");
+        for san in ["e8=Q", "e8Q", "e8=Q!?"] {
+            assert_eq!(parse_san(&promotion, san).unwrap().stringify(), "e7e8q");
+        }
+        assert_eq!(parse_san(&promotion, "e8=N+").unwrap().stringify(), "e7e8n");
+
+        let en_passant = board("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2");
+        assert!(parse_san(&en_passant, "exd6").unwrap().is_en_passant());
+
+        let start = pleco::Board::start_pos();
+        for san in ["e5", "Nf4", "Ke2", "exd3", "O-O", ""] {
+            assert_eq!(parse_san(&start, san), None, "{}", san);
+        }
+    }
+
+    #[test]
+    fn disambiguates_by_file_then_rank() {
+        let knights = board("4k3/8/8/8/N3N3/8/N7/1N1K1N2 w - - 0 1");
+        let san = |uci: &str| {
+            let bit_move = knights
+                .generate_moves()
+                .iter()
+                .copied()
+                .find(|m| m.stringify() == uci)
+                .unwrap();
+            to_san(&knights, bit_move)
+        };
+        assert_eq!(san("b1d2"), "Nbd2");
+        assert_eq!(san("f1d2"), "Nfd2");
+        assert_eq!(san("b1c3"), "Nbc3");
+        assert_eq!(san("e4c3"), "Nec3");
+        assert_eq!(san("a2c3"), "N2c3");
+        assert_eq!(san("a4c3"), "Na4c3");
+        assert_eq!(san("f1h2"), "Nh2");
+    }
+
+    #[test]
+    fn san_round_trips_every_legal_move() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q2/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        ] {
+            let board = board(fen);
+            for bit_move in board.generate_moves().iter().copied() {
+                let san = to_san(&board, bit_move);
+                assert_eq!(
+                    parse_san(&board, &san),
+                    Some(bit_move),
+                    "{} in {}",
+                    san,
+                    fen
+                );
+            }
+        }
+
+        let mut fools_mate = pleco::Board::start_pos();
+        for uci in ["f2f3", "e7e5", "g2g4"] {
+            fools_mate.apply_uci_move(uci);
+        }
+        let mate = parse_san(&fools_mate, "Qh4").unwrap();
+        assert_eq!(to_san(&fools_mate, mate), "Qh4#");
+    }
+}
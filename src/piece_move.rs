@@ -1,5 +1,12 @@
+use pyo3::basic::CompareOp;
 use pyo3::prelude::*;
 use std::convert::From;
+use std::fmt;
+
+use crate::board::Board;
+use crate::core::PieceType;
+use crate::error::IllegalMoveError;
+use crate::square::Square;
 
 /// Represents a singular move.
 ///
@@ -22,11 +29,97 @@ impl BitMove {
     fn __repr__(&self) -> String {
         self.to_string()
     }
+
+    fn __hash__(&self) -> u64 {
+        self.data as u64
+    }
+
+    fn __richcmp__(&self, other: &Self, op: CompareOp, py: Python<'_>) -> PyObject {
+        match op {
+            CompareOp::Eq => (self.data == other.data).into_py(py),
+            CompareOp::Ne => (self.data != other.data).into_py(py),
+            _ => py.NotImplemented(),
+        }
+    }
+
+    /// Resolves a UCI string such as `"e7e8q"` against the legal moves of `board`, filling in
+    /// the capture, castle, en passant and double push flags.
+    #[staticmethod]
+    pub fn from_uci(board: &Board, uci: &str) -> PyResult<BitMove> {
+        board.find_uci(uci).map(|m| m.into()).ok_or_else(|| {
+            IllegalMoveError::new_err(format!(
+                "illegal move {} in position {}",
+                uci,
+                board.inner().fen()
+            ))
+        })
+    }
+
+    pub fn raw(&self) -> u16 {
+        self.data
+    }
+
+    pub fn get_src(&self) -> Square {
+        self.inner().get_src().into()
+    }
+
+    /// The destination square. For castling this is the square of the castling rook.
+    pub fn get_dest(&self) -> Square {
+        self.inner().get_dest().into()
+    }
+
+    /// The piece promoted to, or `None` if this is not a promotion.
+    pub fn promo_piece(&self) -> Option<PieceType> {
+        let bit_move = self.inner();
+        bit_move.is_promo().then(|| bit_move.promo_piece().into())
+    }
+
+    pub fn is_promo(&self) -> bool {
+        self.inner().is_promo()
+    }
+
+    pub fn is_capture(&self) -> bool {
+        self.inner().is_capture()
+    }
+
+    pub fn is_castle(&self) -> bool {
+        self.inner().is_castle()
+    }
+
+    pub fn is_king_castle(&self) -> bool {
+        self.inner().is_king_castle()
+    }
+
+    pub fn is_queen_castle(&self) -> bool {
+        self.inner().is_queen_castle()
+    }
+
+    pub fn is_en_passant(&self) -> bool {
+        self.inner().is_en_passant()
+    }
+
+    pub fn is_double_push(&self) -> bool {
+        self.inner().is_double_push().0
+    }
+
+    pub fn is_quiet_move(&self) -> bool {
+        self.inner().is_quiet_move()
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.inner().is_null()
+    }
+}
+
+impl BitMove {
+    fn inner(&self) -> pleco::BitMove {
+        pleco::BitMove::new(self.data)
+    }
 }
 
-impl ToString for BitMove {
-    fn to_string(&self) -> String {
-        pleco::BitMove::new(self.data).stringify()
+impl fmt::Display for BitMove {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", pleco::BitMove::new(self.data).stringify())
     }
 }
 
@@ -51,13 +144,66 @@ impl From<&BitMove> for pleco::BitMove {
 /// Structure containing both a score (represented as a i16) and a `BitMove`.
 ///
 /// This is useful for tracking a list of moves alongside each of their scores.
-#[derive(Clone)]
+///
+/// Scoring moves order by score, so a list of them can be sorted directly. Moves with equal
+/// scores fall back to the raw move bits, so two scoring moves compare equal only when both
+/// the score and the move match.
+#[derive(Clone, Debug)]
 #[pyclass]
 pub struct ScoringMove {
+    #[pyo3(get)]
     pub bit_move: BitMove,
+    #[pyo3(get)]
     pub score: i16,
 }
 
+#[pymethods]
+impl ScoringMove {
+    #[new]
+    #[pyo3(signature = (bit_move, score=0))]
+    pub fn new(bit_move: BitMove, score: i16) -> Self {
+        ScoringMove { bit_move, score }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("ScoringMove({}, {})", self.bit_move, self.score)
+    }
+
+    fn __hash__(&self) -> u64 {
+        ((self.bit_move.data as u64) << 16) | self.score as u16 as u64
+    }
+
+    fn __richcmp__(&self, other: &Self, op: CompareOp) -> bool {
+        op.matches(self.cmp(other))
+    }
+}
+
+impl ScoringMove {
+    fn key(&self) -> (i16, u16) {
+        (self.score, self.bit_move.data)
+    }
+}
+
+impl PartialEq for ScoringMove {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for ScoringMove {}
+
+impl PartialOrd for ScoringMove {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ScoringMove {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
 impl From<pleco::ScoringMove> for ScoringMove {
     fn from(mov: pleco::ScoringMove) -> ScoringMove {
         ScoringMove {
@@ -75,3 +221,105 @@ impl From<ScoringMove> for pleco::ScoringMove {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(fen: &str, uci: &str) -> BitMove {
+        let board = pleco::Board::from_fen(fen).unwrap().into();
+        BitMove::from_uci(&board, uci).unwrap()
+    }
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    #[test]
+    fn reads_move_flags() {
+        let quiet = find(START, "g1f3");
+        assert!(quiet.is_quiet_move());
+        assert!(!quiet.is_capture() && !quiet.is_double_push() && !quiet.is_castle());
+        assert_eq!(
+            (quiet.get_src(), quiet.get_dest()),
+            (Square::G1, Square::F3)
+        );
+        assert_eq!(quiet.promo_piece(), None);
+
+        let double_push = find(START, "e2e4");
+        assert!(double_push.is_double_push());
+        assert!(!double_push.is_capture());
+
+        let castles = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        let king_side = find(castles, "e1g1");
+        assert!(king_side.is_castle() && king_side.is_king_castle());
+        assert!(!king_side.is_queen_castle());
+        // pleco encodes castling as the king taking its own rook
+        assert_eq!(king_side.get_dest(), Square::H1);
+        let queen_side = find(castles, "e1c1");
+        assert!(queen_side.is_castle() && queen_side.is_queen_castle());
+
+        let en_passant = find("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2", "e5d6");
+        assert!(en_passant.is_en_passant() && en_passant.is_capture());
+
+        let capture = find("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1", "e4d5");
+        assert!(capture.is_capture() && !capture.is_en_passant());
+
+        let promotions = "1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1";
+        let queen = find(promotions, "a7a8q");
+        assert!(queen.is_promo() && !queen.is_capture());
+        assert_eq!(queen.promo_piece(), Some(PieceType::Q));
+        let knight = find(promotions, "a7b8n");
+        assert!(knight.is_promo() && knight.is_capture());
+        assert_eq!(knight.promo_piece(), Some(PieceType::N));
+
+        assert!(BitMove::new(0).is_null());
+        assert!(!quiet.is_null());
+    }
+
+    #[test]
+    fn resolves_uci_against_the_board() {
+        let board = pleco::Board::start_pos().into();
+        assert!(BitMove::from_uci(&board, "e2e5").is_err());
+        assert!(BitMove::from_uci(&board, "e7e5").is_err());
+        assert!(BitMove::from_uci(&board, "nonsense").is_err());
+        assert_eq!(
+            BitMove::from_uci(&board, "E2E4").unwrap().to_string(),
+            "e2e4"
+        );
+    }
+
+    #[test]
+    fn converts_to_and_from_pleco() {
+        let bit_move = find(START, "b1c3");
+        let raw = bit_move.raw();
+        let inner = pleco::BitMove::from(&bit_move);
+        assert_eq!(inner.get_raw(), raw);
+        assert_eq!(BitMove::from(inner).raw(), raw);
+        assert_eq!(pleco::BitMove::from(bit_move.clone()), inner);
+        assert_eq!(bit_move.__hash__(), raw as u64);
+        assert_eq!(bit_move.__repr__(), "b1c3");
+    }
+
+    #[test]
+    fn scoring_moves_order_consistently_with_equality() {
+        let e4 = find(START, "e2e4");
+        let d4 = find(START, "d2d4");
+        let a = ScoringMove::new(e4.clone(), 10);
+        let b = ScoringMove::new(d4, 10);
+        assert_ne!(a, b);
+        assert_ne!(a.cmp(&b), std::cmp::Ordering::Equal);
+        assert!(!a.__richcmp__(&b, CompareOp::Eq));
+        assert!(a.__richcmp__(&b, CompareOp::Lt) != a.__richcmp__(&b, CompareOp::Gt));
+        assert!(!a.__richcmp__(&b, CompareOp::Le) || !a.__richcmp__(&b, CompareOp::Ge));
+
+        let same = ScoringMove::new(e4.clone(), 10);
+        assert_eq!(a, same);
+        assert!(a.__richcmp__(&same, CompareOp::Le) && a.__richcmp__(&same, CompareOp::Ge));
+        assert_eq!(a.__hash__(), same.__hash__());
+
+        let better = ScoringMove::new(e4, 11);
+        assert!(a.__richcmp__(&better, CompareOp::Lt));
+        let mut moves = [better.clone(), a.clone(), b.clone()];
+        moves.sort();
+        assert_eq!(moves.last(), Some(&better));
+    }
+}
@@ -0,0 +1,385 @@
+use pyo3::exceptions::PyIOError;
+use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+
+use crate::action_space::ActionId;
+use crate::board::Board;
+use crate::error::InvalidFenError;
+use crate::piece_move::BitMove;
+
+const BINARY_MAGIC: &[u8; 4] = b"GCPR";
+const BINARY_VERSION: u8 = 1;
+
+#[pyclass]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum RecordFormat {
+    Jsonl,
+    Binary,
+}
+
+/// One environment step: the position the action was taken from, the action and its reward.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StepRecord {
+    pub episode: u32,
+    pub step: u32,
+    pub fen: String,
+    pub zobrist: u64,
+    pub action_id: ActionId,
+    pub bit_move: u16,
+    pub reward: i64,
+    pub legal_actions: Vec<ActionId>,
+}
+
+pub struct Recorder {
+    writer: BufWriter<File>,
+    format: RecordFormat,
+}
+
+impl Recorder {
+    pub fn create(path: &str, format: RecordFormat) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        if format == RecordFormat::Binary {
+            writer.write_all(BINARY_MAGIC)?;
+            writer.write_all(&[BINARY_VERSION])?;
+        }
+        Ok(Self { writer, format })
+    }
+
+    pub fn write(&mut self, record: &StepRecord) -> io::Result<()> {
+        match self.format {
+            RecordFormat::Jsonl => {
+                serde_json::to_writer(&mut self.writer, record)?;
+                self.writer.write_all(b"\n")
+            }
+            RecordFormat::Binary => write_binary(&mut self.writer, record),
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// Binary layout, little endian:
+//   episode u32 | step u32 | zobrist u64 | action u16 | move u16 | reward i64
+//   | fen length u8 | fen bytes | action count u16 | actions u16 * count
+fn write_binary<W: Write>(writer: &mut W, record: &StepRecord) -> io::Result<()> {
+    writer.write_all(&record.episode.to_le_bytes())?;
+    writer.write_all(&record.step.to_le_bytes())?;
+    writer.write_all(&record.zobrist.to_le_bytes())?;
+    writer.write_all(&record.action_id.to_le_bytes())?;
+    writer.write_all(&record.bit_move.to_le_bytes())?;
+    writer.write_all(&record.reward.to_le_bytes())?;
+    writer.write_all(&[record.fen.len() as u8])?;
+    writer.write_all(record.fen.as_bytes())?;
+    writer.write_all(&(record.legal_actions.len() as u16).to_le_bytes())?;
+    for action in &record.legal_actions {
+        writer.write_all(&action.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_binary<R: Read>(reader: &mut R) -> io::Result<Option<StepRecord>> {
+    let mut episode = [0; 4];
+    match reader.read_exact(&mut episode) {
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+
+    let mut step = [0; 4];
+    let mut zobrist = [0; 8];
+    let mut action_id = [0; 2];
+    let mut bit_move = [0; 2];
+    let mut reward = [0; 8];
+    let mut fen_len = [0; 1];
+    reader.read_exact(&mut step)?;
+    reader.read_exact(&mut zobrist)?;
+    reader.read_exact(&mut action_id)?;
+    reader.read_exact(&mut bit_move)?;
+    reader.read_exact(&mut reward)?;
+    reader.read_exact(&mut fen_len)?;
+
+    let mut fen = vec![0; fen_len[0] as usize];
+    reader.read_exact(&mut fen)?;
+    let fen =
+        String::from_utf8(fen).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    let mut num_actions = [0; 2];
+    reader.read_exact(&mut num_actions)?;
+    let mut legal_actions = Vec::with_capacity(u16::from_le_bytes(num_actions) as usize);
+    for _ in 0..u16::from_le_bytes(num_actions) {
+        let mut action = [0; 2];
+        reader.read_exact(&mut action)?;
+        legal_actions.push(u16::from_le_bytes(action));
+    }
+
+    Ok(Some(StepRecord {
+        episode: u32::from_le_bytes(episode),
+        step: u32::from_le_bytes(step),
+        fen,
+        zobrist: u64::from_le_bytes(zobrist),
+        action_id: u16::from_le_bytes(action_id),
+        bit_move: u16::from_le_bytes(bit_move),
+        reward: i64::from_le_bytes(reward),
+        legal_actions,
+    }))
+}
+
+/// Reads back a log written by `Recorder`, detecting the format from its first bytes.
+pub struct RecordReader {
+    reader: BufReader<File>,
+    format: RecordFormat,
+}
+
+impl RecordReader {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let format = if reader.fill_buf()?.starts_with(BINARY_MAGIC) {
+            let mut header = [0; 5];
+            reader.read_exact(&mut header)?;
+            if header[4] != BINARY_VERSION {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unsupported log version {}", header[4]),
+                ));
+            }
+            RecordFormat::Binary
+        } else {
+            RecordFormat::Jsonl
+        };
+        Ok(Self { reader, format })
+    }
+}
+
+impl Iterator for RecordReader {
+    type Item = io::Result<StepRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.format {
+            RecordFormat::Binary => read_binary(&mut self.reader).transpose(),
+            RecordFormat::Jsonl => loop {
+                let mut line = String::new();
+                match self.reader.read_line(&mut line) {
+                    Ok(0) => return None,
+                    Ok(_) if line.trim().is_empty() => continue,
+                    Ok(_) => {
+                        return Some(serde_json::from_str(&line).map_err(io::Error::from));
+                    }
+                    Err(err) => return Some(Err(err)),
+                }
+            },
+        }
+    }
+}
+
+/// A recorded step after being re-played on a fresh board.
+///
+/// `diverged` is set when the rebuilt position does not hash to the recorded zobrist key, or
+/// when the recorded move is not legal in it. Replay continues from the recorded FEN.
+#[pyclass]
+pub struct ReplayStep {
+    #[pyo3(get)]
+    pub episode: u32,
+    #[pyo3(get)]
+    pub step: u32,
+    #[pyo3(get)]
+    pub fen: String,
+    #[pyo3(get)]
+    pub action_id: ActionId,
+    #[pyo3(get)]
+    pub bit_move: BitMove,
+    #[pyo3(get)]
+    pub reward: i64,
+    #[pyo3(get)]
+    pub legal_actions: Vec<ActionId>,
+    #[pyo3(get)]
+    pub recorded_zobrist: u64,
+    #[pyo3(get)]
+    pub replayed_zobrist: u64,
+    #[pyo3(get)]
+    pub diverged: bool,
+    board: pleco::Board,
+}
+
+#[pymethods]
+impl ReplayStep {
+    fn __repr__(&self) -> String {
+        format!(
+            "ReplayStep(episode={}, step={}, action_id={}, bit_move={}, diverged={})",
+            self.episode,
+            self.step,
+            self.action_id,
+            self.bit_move,
+            if self.diverged { "True" } else { "False" }
+        )
+    }
+
+    /// The rebuilt position the action was taken from.
+    pub fn board(&self) -> Board {
+        self.board.clone().into()
+    }
+}
+
+#[pyclass]
+pub struct EpisodeReplay {
+    records: RecordReader,
+    board: Option<pleco::Board>,
+    episode: Option<u32>,
+}
+
+impl EpisodeReplay {
+    pub fn open(path: &str) -> io::Result<Self> {
+        Ok(Self {
+            records: RecordReader::open(path)?,
+            board: None,
+            episode: None,
+        })
+    }
+}
+
+#[pymethods]
+impl EpisodeReplay {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self) -> PyResult<Option<ReplayStep>> {
+        let record = match self.records.next() {
+            Some(record) => record.map_err(|err| PyIOError::new_err(err.to_string()))?,
+            None => return Ok(None),
+        };
+
+        let recorded = pleco::Board::from_fen(&record.fen)
+            .map_err(|_| InvalidFenError::new_err(format!("invalid FEN in log: {}", record.fen)))?;
+
+        // Each episode is rebuilt from its first recorded position
+        let board = match self.board.take() {
+            Some(board) if self.episode == Some(record.episode) => board,
+            _ => recorded.clone(),
+        };
+        self.episode = Some(record.episode);
+
+        let bit_move = pleco::BitMove::new(record.bit_move);
+        let is_legal =
+            |board: &pleco::Board| board.pseudo_legal_move(bit_move) && board.legal_move(bit_move);
+        let replayed_zobrist = board.zobrist();
+        let diverged = replayed_zobrist != record.zobrist || !is_legal(&board);
+
+        let mut next = if diverged { recorded } else { board.clone() };
+        if is_legal(&next) {
+            next.apply_move(bit_move);
+            self.board = Some(next);
+        }
+
+        Ok(Some(ReplayStep {
+            episode: record.episode,
+            step: record.step,
+            fen: record.fen,
+            action_id: record.action_id,
+            bit_move: bit_move.into(),
+            reward: record.reward,
+            legal_actions: record.legal_actions,
+            recorded_zobrist: record.zobrist,
+            replayed_zobrist,
+            diverged,
+            board,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ChessEnv;
+
+    // Records the moves of one episode from `fen`, as ChessEnv::step does
+    fn play(episode: u32, fen: &str, moves: &[&str]) -> Vec<StepRecord> {
+        let mut board = pleco::Board::from_fen(fen).unwrap();
+        let mut records = vec![];
+        for (step, uci) in moves.iter().enumerate() {
+            let legal = board.generate_moves();
+            let bit_move = *legal.iter().find(|m| m.stringify() == *uci).unwrap();
+            records.push(StepRecord {
+                episode,
+                step: step as u32,
+                fen: board.fen(),
+                zobrist: board.zobrist(),
+                action_id: ChessEnv::move_to_action(&bit_move),
+                bit_move: bit_move.get_raw(),
+                reward: 0,
+                legal_actions: legal.iter().map(ChessEnv::move_to_action).collect(),
+            });
+            board.apply_move(bit_move);
+        }
+        records
+    }
+
+    fn record(name: &str, format: RecordFormat, records: &[StepRecord]) -> String {
+        let path = std::env::temp_dir().join(format!("gym_chess_pleco_{}", name));
+        let path = path.to_str().unwrap().to_string();
+        let mut recorder = Recorder::create(&path, format).unwrap();
+        for record in records {
+            recorder.write(record).unwrap();
+        }
+        recorder.flush().unwrap();
+        path
+    }
+
+    fn replay(path: &str) -> Vec<ReplayStep> {
+        let mut replay = EpisodeReplay::open(path).unwrap();
+        std::iter::from_fn(|| replay.__next__().unwrap()).collect()
+    }
+
+    #[test]
+    fn reads_back_both_formats() {
+        let start = pleco::Board::start_pos().fen();
+        let records = play(
+            0,
+            &start,
+            &["e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6", "e1g1"],
+        );
+        for (name, format) in [
+            ("read.jsonl", RecordFormat::Jsonl),
+            ("read.bin", RecordFormat::Binary),
+        ] {
+            let path = record(name, format, &records);
+            let read = RecordReader::open(&path).unwrap();
+            assert_eq!(read.collect::<io::Result<Vec<_>>>().unwrap(), records);
+        }
+    }
+
+    #[test]
+    fn replays_recorded_episodes() {
+        let start = pleco::Board::start_pos().fen();
+        let mut records = play(0, &start, &["e2e4", "e7e5", "g1f3"]);
+        records.extend(play(
+            1,
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+            &["e2e4", "e8d7"],
+        ));
+        let steps = replay(&record("replay.bin", RecordFormat::Binary, &records));
+
+        assert_eq!(steps.len(), records.len());
+        for (step, record) in steps.iter().zip(&records) {
+            assert!(!step.diverged, "{:?}", step.__repr__());
+            assert_eq!(step.board.fen(), record.fen);
+            assert_eq!(step.replayed_zobrist, record.zobrist);
+        }
+    }
+
+    #[test]
+    fn flags_divergence_and_resumes_from_the_log() {
+        let start = pleco::Board::start_pos().fen();
+        let mut records = play(0, &start, &["e2e4", "e7e5", "g1f3", "b8c6"]);
+        // The log skips a move: the second record's position cannot be reached from the first
+        records.remove(1);
+        let steps = replay(&record("diverged.jsonl", RecordFormat::Jsonl, &records));
+
+        let diverged = steps.iter().map(|step| step.diverged).collect::<Vec<_>>();
+        assert_eq!(diverged, [false, true, false]);
+        assert_ne!(steps[1].replayed_zobrist, records[1].zobrist);
+        assert_eq!(steps[2].board.fen(), records[2].fen);
+    }
+}
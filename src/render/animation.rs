@@ -0,0 +1,361 @@
+use pyo3::exceptions::PyIOError;
+use pyo3::prelude::*;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+
+use crate::error::{invalid_fen, IllegalMoveError, InvalidArgumentError, InvalidFenError};
+use crate::pgn::{to_san, PgnReader, ReplayError};
+use crate::piece_move::BitMove;
+use crate::recorder::RecordReader;
+use crate::render::raster::{self, Canvas, Overlay, BORDER, COORDINATES, MARGIN};
+use crate::ChessEnv;
+
+// Height of the strip below the board holding the caption
+const CAPTION_ROWS: usize = 20;
+// NeuQuant sampling factor for GIF palettes, 1 (best) to 30 (fastest)
+const GIF_QUANTIZE_SPEED: i32 = 10;
+// Shortest GIF frame delay, in hundredths of a second, that browsers play as given
+const MIN_GIF_DELAY: u32 = 2;
+
+/// A game drawn frame by frame with the crate's rasterizer, one frame for the start position and
+/// one after every move, exported as an animated GIF or as PNG files.
+///
+/// Captions show the move in SAN and, for recorded episodes, the reward it earned.
+#[pyclass]
+pub struct Animation {
+    start_fen: String,
+    moves: Vec<pleco::BitMove>,
+    rewards: Option<Vec<i64>>,
+}
+
+#[pymethods]
+impl Animation {
+    fn __repr__(&self) -> String {
+        format!(
+            "Animation({} moves from {})",
+            self.moves.len(),
+            self.start_fen
+        )
+    }
+
+    fn __len__(&self) -> usize {
+        self.moves.len() + 1
+    }
+
+    /// The moves of `moves`, played from `start_fen` or the starting position.
+    #[staticmethod]
+    #[pyo3(signature = (moves, start_fen=None))]
+    pub fn from_moves(moves: Vec<BitMove>, start_fen: Option<&str>) -> PyResult<Animation> {
+        let start_fen = start_fen.map_or(pleco::Board::start_pos().fen(), |fen| fen.to_string());
+        Animation::new(
+            start_fen,
+            moves.into_iter().map(|m| m.into()).collect(),
+            None,
+        )
+    }
+
+    /// The `index`-th game of the PGN file at `path`.
+    #[staticmethod]
+    #[pyo3(signature = (path, index=0))]
+    pub fn from_pgn(path: &str, index: usize) -> PyResult<Animation> {
+        let file = File::open(path).map_err(|err| PyIOError::new_err(err.to_string()))?;
+        let game = PgnReader::new(BufReader::new(file))
+            .nth(index)
+            .ok_or_else(|| {
+                InvalidArgumentError::new_err(format!("{} has no game {}", path, index))
+            })?
+            .map_err(|err| PyIOError::new_err(err.to_string()))?;
+
+        let mut moves = vec![];
+        let start = game
+            .start_board()
+            .and_then(|board| {
+                game.replay(|_, bit_move| moves.push(bit_move))
+                    .map(|_| board)
+            })
+            .map_err(|err| match err {
+                ReplayError::InvalidFen(_) => InvalidFenError::new_err(err.to_string()),
+                ReplayError::IllegalMove { .. } => IllegalMoveError::new_err(err.to_string()),
+            })?;
+        Animation::new(start.fen(), moves, None)
+    }
+
+    /// An episode logged by `ChessEnv.start_recording`, the first one unless `episode` is given.
+    #[staticmethod]
+    #[pyo3(signature = (path, episode=None))]
+    pub fn from_recording(path: &str, mut episode: Option<u32>) -> PyResult<Animation> {
+        let reader = RecordReader::open(path).map_err(|err| PyIOError::new_err(err.to_string()))?;
+        let mut records = vec![];
+        for record in reader {
+            let record = record.map_err(|err| PyIOError::new_err(err.to_string()))?;
+            let wanted = *episode.get_or_insert(record.episode);
+            if record.episode == wanted {
+                records.push(record);
+            }
+        }
+        let first = records.first().ok_or_else(|| {
+            InvalidArgumentError::new_err(format!("{} holds no steps of the episode", path))
+        })?;
+
+        let start_fen = first.fen.clone();
+        let moves = records
+            .iter()
+            .map(|record| pleco::BitMove::new(record.bit_move))
+            .collect();
+        let rewards = records.iter().map(|record| record.reward).collect();
+        Animation::new(start_fen, moves, Some(rewards))
+    }
+
+    /// The episode `env` is currently playing.
+    #[staticmethod]
+    pub fn from_env(env: &ChessEnv) -> PyResult<Animation> {
+        let moves = env
+            .board
+            .move_stack()
+            .into_iter()
+            .map(|m| m.into())
+            .collect();
+        Animation::new(env.board.root_fen(), moves, None)
+    }
+
+    /// Writes an animated GIF that loops forever, showing every frame for `delay_ms`.
+    ///
+    /// Delays under 20 ms are raised to 20 ms, as browsers play shorter ones at a default speed.
+    #[pyo3(signature = (path, delay_ms=500, captions=true))]
+    pub fn save_gif(&self, path: &str, delay_ms: u32, captions: bool) -> PyResult<()> {
+        self.write_gif(path, delay_ms, captions)
+            .map_err(|err| PyIOError::new_err(err.to_string()))
+    }
+
+    /// Writes every frame to `directory` as `frame-00000.png`, `frame-00001.png`, ... and returns
+    /// their paths.
+    #[pyo3(signature = (directory, captions=true))]
+    pub fn save_png_frames(&self, directory: &str, captions: bool) -> PyResult<Vec<String>> {
+        self.write_png_frames(directory, captions)
+            .map_err(|err| PyIOError::new_err(err.to_string()))
+    }
+}
+
+impl Animation {
+    fn new(
+        start_fen: String,
+        moves: Vec<pleco::BitMove>,
+        rewards: Option<Vec<i64>>,
+    ) -> PyResult<Animation> {
+        let mut board =
+            pleco::Board::from_fen(&start_fen).map_err(|err| invalid_fen(&start_fen, err))?;
+        for (ply, bit_move) in moves.iter().enumerate() {
+            if !(board.pseudo_legal_move(*bit_move) && board.legal_move(*bit_move)) {
+                return Err(IllegalMoveError::new_err(format!(
+                    "illegal move {} at ply {} in position {}",
+                    bit_move,
+                    ply,
+                    board.fen()
+                )));
+            }
+            board.apply_move(*bit_move);
+        }
+        Ok(Animation {
+            start_fen,
+            moves,
+            rewards,
+        })
+    }
+
+    pub fn frames(&self, captions: bool) -> Vec<Canvas> {
+        // Moves were checked against the position when the animation was built
+        let mut board = pleco::Board::from_fen(&self.start_fen).unwrap();
+        let mut frames = vec![frame(&board, None, captions.then(String::new))];
+        for (ply, bit_move) in self.moves.iter().enumerate() {
+            let caption = captions.then(|| {
+                let reward = self.rewards.as_ref().map(|rewards| rewards[ply]);
+                caption(&board, *bit_move, reward)
+            });
+            board.apply_move(*bit_move);
+            frames.push(frame(&board, Some(*bit_move), caption));
+        }
+        frames
+    }
+
+    pub fn write_gif(&self, path: &str, delay_ms: u32, captions: bool) -> io::Result<()> {
+        let frames = self.frames(captions);
+        let (width, height) = (frames[0].width as u16, frames[0].height as u16);
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = gif::Encoder::new(file, width, height, &[]).map_err(io::Error::other)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(io::Error::other)?;
+        // GIF delays are counted in hundredths of a second
+        let delay = (delay_ms / 10).clamp(MIN_GIF_DELAY, u16::MAX as u32) as u16;
+        for canvas in &frames {
+            let mut frame =
+                gif::Frame::from_rgb_speed(width, height, &canvas.pixels, GIF_QUANTIZE_SPEED);
+            frame.delay = delay;
+            encoder.write_frame(&frame).map_err(io::Error::other)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_png_frames(&self, directory: &str, captions: bool) -> io::Result<Vec<String>> {
+        fs::create_dir_all(directory)?;
+        let mut paths = vec![];
+        for (idx, canvas) in self.frames(captions).iter().enumerate() {
+            let path = Path::new(directory).join(format!("frame-{:05}.png", idx));
+            write_png(&path, canvas)?;
+            paths.push(path.to_string_lossy().into_owned());
+        }
+        Ok(paths)
+    }
+}
+
+pub fn write_png(path: &Path, canvas: &Canvas) -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, canvas.width as u32, canvas.height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&canvas.pixels)?;
+    Ok(())
+}
+
+fn frame(
+    board: &pleco::Board,
+    last_move: Option<pleco::BitMove>,
+    caption: Option<String>,
+) -> Canvas {
+    let overlay = Overlay {
+        last_move,
+        ..Overlay::default()
+    };
+    let mut canvas = raster::draw(board, &overlay);
+    if let Some(caption) = caption {
+        let top = canvas.height;
+        canvas.extend_bottom(CAPTION_ROWS, BORDER);
+        canvas.text(
+            MARGIN,
+            top + (CAPTION_ROWS - 10) / 2,
+            &caption,
+            2,
+            COORDINATES,
+        );
+    }
+    canvas
+}
+
+// e.g. "12. Nf3" or "12... Nf6 reward: 1"
+fn caption(board: &pleco::Board, bit_move: pleco::BitMove, reward: Option<i64>) -> String {
+    let fen = board.fen();
+    let fullmove = fen.split_whitespace().nth(5).unwrap_or("1");
+    let dots = match board.turn() {
+        pleco::Player::White => ".",
+        pleco::Player::Black => "...",
+    };
+    let mut caption = format!("{}{} {}", fullmove, dots, to_san(board, bit_move));
+    if let Some(reward) = reward {
+        caption.push_str(&format!("  reward: {}", reward));
+    }
+    caption
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn animation(ucis: &[&str]) -> Animation {
+        let mut board = pleco::Board::start_pos();
+        let moves = ucis
+            .iter()
+            .map(|uci| {
+                let legal = board.generate_moves();
+                let bit_move = *legal.iter().find(|m| m.stringify() == *uci).unwrap();
+                board.apply_move(bit_move);
+                bit_move
+            })
+            .collect();
+        Animation::new(pleco::Board::start_pos().fen(), moves, None).unwrap()
+    }
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("gym_chess_pleco_{}", name));
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn rejects_illegal_moves() {
+        let start = pleco::Board::start_pos();
+        let e2e4 = *start
+            .generate_moves()
+            .iter()
+            .find(|m| m.stringify() == "e2e4")
+            .unwrap();
+        // White cannot play e2e4 twice in a row
+        assert!(Animation::new(start.fen(), vec![e2e4, e2e4], None).is_err());
+    }
+
+    #[test]
+    fn gif_has_a_frame_per_position() {
+        let animation = animation(&["e2e4", "e7e5", "g1f3"]);
+        assert_eq!(animation.__len__(), 4);
+        let path = temp_path("animation.gif");
+        for (delay_ms, delay) in [(500, 50), (0, 2), (15, 2), (30, 3)] {
+            animation.write_gif(&path, delay_ms, true).unwrap();
+            let mut options = gif::DecodeOptions::new();
+            options.set_color_output(gif::ColorOutput::RGBA);
+            let mut decoder = options.read_info(File::open(&path).unwrap()).unwrap();
+            assert_eq!(decoder.width() as usize, raster::BOARD_PIXELS);
+            assert_eq!(
+                decoder.height() as usize,
+                raster::BOARD_PIXELS + CAPTION_ROWS
+            );
+            let mut frames = 0;
+            while let Some(frame) = decoder.read_next_frame().unwrap() {
+                assert_eq!(frame.delay, delay);
+                frames += 1;
+            }
+            assert_eq!(frames, animation.__len__());
+        }
+    }
+
+    #[test]
+    fn png_frames_round_trip() {
+        let animation = animation(&["d2d4", "d7d5"]);
+        let directory = temp_path("animation_frames");
+        let _ = fs::remove_dir_all(&directory);
+        let paths = animation.write_png_frames(&directory, false).unwrap();
+        assert_eq!(paths.len(), animation.__len__());
+        let frames = animation.frames(false);
+        for (path, canvas) in paths.iter().zip(&frames) {
+            assert!(path.ends_with(".png"));
+            let decoder = png::Decoder::new(File::open(path).unwrap());
+            let mut reader = decoder.read_info().unwrap();
+            let mut pixels = vec![0; reader.output_buffer_size()];
+            let info = reader.next_frame(&mut pixels).unwrap();
+            assert_eq!(
+                (info.width as usize, info.height as usize),
+                (canvas.width, canvas.height)
+            );
+            assert_eq!(info.color_type, png::ColorType::Rgb);
+            assert_eq!(&pixels[..info.buffer_size()], &canvas.pixels[..]);
+        }
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn captions_show_the_move_and_reward() {
+        let board = pleco::Board::start_pos();
+        let e2e4 = *board
+            .generate_moves()
+            .iter()
+            .find(|m| m.stringify() == "e2e4")
+            .unwrap();
+        assert_eq!(caption(&board, e2e4, None), "1. e4");
+        let mut after = board.clone();
+        after.apply_move(e2e4);
+        let e7e5 = *after
+            .generate_moves()
+            .iter()
+            .find(|m| m.stringify() == "e7e5")
+            .unwrap();
+        assert_eq!(caption(&after, e7e5, Some(-1)), "1... e5  reward: -1");
+    }
+}
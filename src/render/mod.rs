@@ -0,0 +1,187 @@
+pub mod animation;
+pub mod raster;
+pub mod svg;
+
+/// The render modes `ChessEnv` supports, chosen when it is constructed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RenderMode {
+    Ansi,
+    Unicode,
+    RgbArray,
+}
+
+impl RenderMode {
+    pub const ALL: [RenderMode; 3] = [RenderMode::Ansi, RenderMode::Unicode, RenderMode::RgbArray];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RenderMode::Ansi => "ansi",
+            RenderMode::Unicode => "unicode",
+            RenderMode::RgbArray => "rgb_array",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<RenderMode> {
+        RenderMode::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+}
+
+// 256-colour backgrounds for the terminal board
+const ANSI_LIGHT: u8 = 180;
+const ANSI_DARK: u8 = 137;
+const ANSI_LAST_MOVE: u8 = 143;
+const ANSI_CHECK: u8 = 160;
+const ANSI_RESET: &str = "\x1b[0m";
+
+/// A coloured terminal board, white at the bottom unless `flipped`, with the squares of
+/// `last_move` highlighted and the king of the side to move in red when it is in check.
+pub fn ansi(board: &pleco::Board, last_move: Option<pleco::BitMove>, flipped: bool) -> String {
+    let check_sq = board.in_check().then(|| board.king_sq(board.turn()));
+    let (ranks, files): (Vec<u8>, Vec<u8>) = if flipped {
+        ((0..8).collect(), (0..8).rev().collect())
+    } else {
+        ((0..8).rev().collect(), (0..8).collect())
+    };
+    let mut out = String::new();
+    for &rank in &ranks {
+        out.push_str(&format!(" {} ", rank + 1));
+        for &file in &files {
+            let sq = pleco::SQ(rank * 8 + file);
+            let background = if Some(sq) == check_sq {
+                ANSI_CHECK
+            } else if last_move.is_some_and(|m| m.get_src() == sq || m.get_dest() == sq) {
+                ANSI_LAST_MOVE
+            } else if (rank + file) % 2 == 1 {
+                ANSI_LIGHT
+            } else {
+                ANSI_DARK
+            };
+            let piece = board.piece_at_sq(sq);
+            // Bright white for white pieces, black for black ones
+            let foreground = match piece.player() {
+                Some(pleco::Player::White) => "1;97",
+                _ => "1;30",
+            };
+            out.push_str(&format!(
+                "\x1b[48;5;{};{}m {} ",
+                background,
+                foreground,
+                piece.character().unwrap_or(' ')
+            ));
+        }
+        out.push_str(ANSI_RESET);
+        out.push('\n');
+    }
+    out.push_str("   ");
+    for &file in &files {
+        out.push_str(&format!(" {} ", (b'a' + file) as char));
+    }
+    out.push('\n');
+    out
+}
+
+/// A plain text board drawn with chess glyphs, white at the bottom.
+pub fn unicode(board: &pleco::Board) -> String {
+    let mut out = String::new();
+    for rank in (0..8).rev() {
+        let row = (0..8)
+            .map(|file| glyph(board.piece_at_sq(pleco::SQ(rank * 8 + file))).to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        out.push_str(&format!("{} {}\n", rank + 1, row));
+    }
+    out.push_str("  a b c d e f g h\n");
+    out
+}
+
+fn glyph(piece: pleco::Piece) -> char {
+    use pleco::Piece::*;
+
+    match piece {
+        WhitePawn => '♙',
+        WhiteKnight => '♘',
+        WhiteBishop => '♗',
+        WhiteRook => '♖',
+        WhiteQueen => '♕',
+        WhiteKing => '♔',
+        BlackPawn => '♟',
+        BlackKnight => '♞',
+        BlackBishop => '♝',
+        BlackRook => '♜',
+        BlackQueen => '♛',
+        BlackKing => '♚',
+        None => '·',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn after(ucis: &[&str]) -> (pleco::Board, Option<pleco::BitMove>) {
+        let mut board = pleco::Board::start_pos();
+        let mut last = None;
+        for uci in ucis {
+            let legal = board.generate_moves();
+            let bit_move = *legal.iter().find(|m| m.stringify() == *uci).unwrap();
+            board.apply_move(bit_move);
+            last = Some(bit_move);
+        }
+        (board, last)
+    }
+
+    #[test]
+    fn draws_the_start_position_in_unicode() {
+        assert_eq!(
+            unicode(&pleco::Board::start_pos()),
+            "8 ♜ ♞ ♝ ♛ ♚ ♝ ♞ ♜\n\
+             7 ♟ ♟ ♟ ♟ ♟ ♟ ♟ ♟\n\
+             6 · · · · · · · ·\n\
+             5 · · · · · · · ·\n\
+             4 · · · · · · · ·\n\
+             3 · · · · · · · ·\n\
+             2 ♙ ♙ ♙ ♙ ♙ ♙ ♙ ♙\n\
+             1 ♖ ♘ ♗ ♕ ♔ ♗ ♘ ♖\n  \
+             a b c d e f g h\n"
+        );
+    }
+
+    #[test]
+    fn draws_the_start_position_in_ansi() {
+        let out = ansi(&pleco::Board::start_pos(), None, false);
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 9);
+        assert!(lines[0].starts_with(" 8 \x1b[48;5;180;1;30m r \x1b[48;5;137;1;30m n "));
+        // a1 is dark, with a bright white rook
+        assert!(lines[7].starts_with(" 1 \x1b[48;5;137;1;97m R \x1b[48;5;180;1;97m N "));
+        assert!(lines[4].contains("\x1b[48;5;180;1;30m   "));
+        assert!(lines.iter().take(8).all(|line| line.ends_with(ANSI_RESET)));
+        assert_eq!(lines[8], "    a  b  c  d  e  f  g  h ");
+
+        let flipped = ansi(&pleco::Board::start_pos(), None, true);
+        let lines = flipped.lines().collect::<Vec<_>>();
+        assert!(lines[0].starts_with(" 1 \x1b[48;5;180;1;97m R "));
+        assert_eq!(lines[8], "    h  g  f  e  d  c  b  a ");
+    }
+
+    #[test]
+    fn highlights_the_last_move_and_check() {
+        let (board, last) = after(&["e2e4"]);
+        let out = ansi(&board, last, false);
+        assert_eq!(out.matches("\x1b[48;5;143;").count(), 2);
+        assert!(!out.contains("\x1b[48;5;160;"));
+
+        let (board, last) = after(&["f2f3", "e7e5", "g2g4", "d8h4"]);
+        let out = ansi(&board, last, false);
+        // The white king on e1, in check from the queen
+        assert!(out.contains("\x1b[48;5;160;1;97m K "));
+    }
+
+    #[test]
+    fn names_render_modes() {
+        for mode in RenderMode::ALL {
+            assert_eq!(RenderMode::from_name(mode.name()), Some(mode));
+        }
+        assert_eq!(RenderMode::from_name("human"), None);
+    }
+}
@@ -0,0 +1,524 @@
+//! A small software rasterizer drawing boards into RGB pixel buffers.
+//!
+//! Pieces and coordinates come from bitmaps embedded below, so nothing depends on a GPU or on
+//! fonts installed on the system.
+
+pub type Rgb = [u8; 3];
+
+/// Side of a square in pixels.
+pub const SQUARE: usize = 48;
+/// Width of the border holding the coordinates.
+pub const MARGIN: usize = 20;
+/// Width and height of a rendered board.
+pub const BOARD_PIXELS: usize = 8 * SQUARE + 2 * MARGIN;
+
+pub const BORDER: Rgb = [48, 46, 43];
+pub const COORDINATES: Rgb = [200, 200, 200];
+const LIGHT: Rgb = [240, 217, 181];
+const DARK: Rgb = [181, 136, 99];
+const LAST_MOVE: Rgb = [205, 210, 106];
+const CHECK: Rgb = [220, 40, 40];
+const HEAT: Rgb = [255, 90, 0];
+const ARROW: Rgb = [21, 120, 27];
+const OUTLINE: Rgb = [0, 0, 0];
+const WHITE_PIECE: Rgb = [250, 250, 250];
+const BLACK_PIECE: Rgb = [45, 45, 45];
+
+// Fraction of a square a piece covers
+const PIECE_SCALE: f32 = 0.8;
+
+/// An RGB image stored row by row, three bytes per pixel.
+pub struct Canvas {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Canvas {
+    pub fn new(width: usize, height: usize, colour: Rgb) -> Self {
+        Canvas {
+            width,
+            height,
+            pixels: colour.repeat(width * height),
+        }
+    }
+
+    /// Mixes `colour` into the pixel at `(x, y)`, `alpha` being its opacity.
+    pub fn blend(&mut self, x: usize, y: usize, colour: Rgb, alpha: f32) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let idx = (y * self.width + x) * 3;
+        for (channel, value) in colour.iter().enumerate() {
+            let old = self.pixels[idx + channel] as f32;
+            self.pixels[idx + channel] = (old + (*value as f32 - old) * alpha).round() as u8;
+        }
+    }
+
+    pub fn fill_rect(&mut self, x: usize, y: usize, w: usize, h: usize, colour: Rgb, alpha: f32) {
+        for py in y..y + h {
+            for px in x..x + w {
+                self.blend(px, py, colour, alpha);
+            }
+        }
+    }
+
+    /// Draws an arrow from `from` to `to`, its shaft `width` pixels wide. Edges are smoothed by
+    /// sampling every pixel four times.
+    pub fn arrow(&mut self, from: (f32, f32), to: (f32, f32), width: f32, colour: Rgb, alpha: f32) {
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length == 0.0 {
+            return;
+        }
+        let (ux, uy) = (dx / length, dy / length);
+        let head_length = (width * 2.5).min(length);
+        let head_width = width * 1.6;
+        let shaft_end = length - head_length;
+
+        // Whether a point falls inside the arrow, in coordinates along and across it
+        let inside = |x: f32, y: f32| {
+            let along = (x - from.0) * ux + (y - from.1) * uy;
+            let across = ((x - from.0) * uy - (y - from.1) * ux).abs();
+            if along < 0.0 || along > length {
+                false
+            } else if along <= shaft_end {
+                across <= width / 2.0
+            } else {
+                across <= head_width * (length - along) / head_length
+            }
+        };
+
+        let reach = head_width.max(width);
+        let min_x = (from.0.min(to.0) - reach).max(0.0) as usize;
+        let min_y = (from.1.min(to.1) - reach).max(0.0) as usize;
+        let max_x = ((from.0.max(to.0) + reach) as usize).min(self.width);
+        let max_y = ((from.1.max(to.1) + reach) as usize).min(self.height);
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let coverage = [(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)]
+                    .iter()
+                    .filter(|(ox, oy)| inside(x as f32 + ox, y as f32 + oy))
+                    .count();
+                if coverage > 0 {
+                    self.blend(x, y, colour, alpha * coverage as f32 / 4.0);
+                }
+            }
+        }
+    }
+
+    /// Adds `rows` rows of `colour` below the image.
+    pub fn extend_bottom(&mut self, rows: usize, colour: Rgb) {
+        self.pixels.extend(colour.repeat(self.width * rows));
+        self.height += rows;
+    }
+
+    /// Draws `text` with the embedded font, its top left corner at `(x, y)`.
+    pub fn text(&mut self, x: usize, y: usize, text: &str, scale: usize, colour: Rgb) {
+        for (idx, c) in text.chars().enumerate() {
+            let Some(rows) = font_glyph(c) else {
+                continue;
+            };
+            let left = x + idx * 4 * scale;
+            for (row, bits) in rows.iter().enumerate() {
+                for (col, bit) in bits.bytes().enumerate() {
+                    if bit == b'#' {
+                        self.fill_rect(
+                            left + col * scale,
+                            y + row * scale,
+                            scale,
+                            scale,
+                            colour,
+                            1.0,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Draws a piece centred in the square whose top left corner is at `(x, y)`.
+    pub fn piece(&mut self, x: usize, y: usize, size: usize, piece: pleco::Piece) {
+        let fill = match piece.player() {
+            Some(pleco::Player::White) => WHITE_PIECE,
+            Some(pleco::Player::Black) => BLACK_PIECE,
+            None => return,
+        };
+        let sprite = sprite(piece.type_of());
+
+        // The sprite is sampled at the target size so the outline stays one pixel wide
+        let side = (size as f32 * PIECE_SCALE) as usize;
+        let offset = (size - side) / 2;
+        let covered = |px: isize, py: isize| {
+            if px < 0 || py < 0 || px >= side as isize || py >= side as isize {
+                return false;
+            }
+            let row = py as usize * SPRITE_SIZE / side;
+            let col = px as usize * SPRITE_SIZE / side;
+            sprite[row].as_bytes()[col] == b'#'
+        };
+        for py in 0..side as isize {
+            for px in 0..side as isize {
+                if !covered(px, py) {
+                    continue;
+                }
+                let edge = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                    .iter()
+                    .any(|(dx, dy)| !covered(px + dx, py + dy));
+                let colour = if edge { OUTLINE } else { fill };
+                self.blend(
+                    x + offset + px as usize,
+                    y + offset + py as usize,
+                    colour,
+                    1.0,
+                );
+            }
+        }
+    }
+}
+
+/// What to draw on top of the position.
+#[derive(Default)]
+pub struct Overlay<'a> {
+    pub last_move: Option<pleco::BitMove>,
+    /// One value per square (a1 first), shaded relative to the largest.
+    pub heatmap: Option<&'a [f32]>,
+}
+
+/// Renders `board` with white at the bottom, ranks and files in the border.
+pub fn draw(board: &pleco::Board, overlay: &Overlay) -> Canvas {
+    let mut canvas = Canvas::new(BOARD_PIXELS, BOARD_PIXELS, BORDER);
+
+    let heat_max = overlay
+        .heatmap
+        .map(|heat| heat.iter().copied().fold(0.0, f32::max))
+        .unwrap_or(0.0);
+    let check_sq = board.in_check().then(|| board.king_sq(board.turn()));
+
+    for idx in 0..64u8 {
+        let sq = pleco::SQ(idx);
+        let (x, y) = square_origin(sq);
+        let (file, rank) = (idx % 8, idx / 8);
+        let colour = if (file + rank) % 2 == 1 { LIGHT } else { DARK };
+        canvas.fill_rect(x, y, SQUARE, SQUARE, colour, 1.0);
+
+        if overlay
+            .last_move
+            .is_some_and(|m| m.get_src() == sq || m.get_dest() == sq)
+        {
+            canvas.fill_rect(x, y, SQUARE, SQUARE, LAST_MOVE, 0.5);
+        }
+        if let Some(heat) = overlay.heatmap {
+            if heat_max > 0.0 {
+                let level = (heat[idx as usize] / heat_max).clamp(0.0, 1.0);
+                canvas.fill_rect(x, y, SQUARE, SQUARE, HEAT, 0.65 * level);
+            }
+        }
+        if Some(sq) == check_sq {
+            canvas.fill_rect(x, y, SQUARE, SQUARE, CHECK, 0.6);
+        }
+        canvas.piece(x, y, SQUARE, board.piece_at_sq(sq));
+    }
+
+    // Glyphs are 3x5, drawn at twice the size
+    let scale = 2;
+    for idx in 0..8 {
+        let rank_y = MARGIN + (7 - idx) * SQUARE + (SQUARE - 5 * scale) / 2;
+        canvas.text(
+            (MARGIN - 3 * scale) / 2,
+            rank_y,
+            &(idx + 1).to_string(),
+            scale,
+            COORDINATES,
+        );
+        let file_x = MARGIN + idx * SQUARE + (SQUARE - 3 * scale) / 2;
+        let file = ((b'a' + idx as u8) as char).to_string();
+        canvas.text(
+            file_x,
+            MARGIN + 8 * SQUARE + (MARGIN - 5 * scale) / 2,
+            &file,
+            scale,
+            COORDINATES,
+        );
+    }
+
+    if let Some(last_move) = overlay.last_move {
+        canvas.arrow(
+            square_centre(last_move.get_src()),
+            square_centre(last_move.get_dest()),
+            SQUARE as f32 * 0.18,
+            ARROW,
+            0.8,
+        );
+    }
+    canvas
+}
+
+/// The top left corner of `sq` on a rendered board.
+pub fn square_origin(sq: pleco::SQ) -> (usize, usize) {
+    let file = sq.file_idx_of_sq() as usize;
+    let rank = sq.rank_idx_of_sq() as usize;
+    (MARGIN + file * SQUARE, MARGIN + (7 - rank) * SQUARE)
+}
+
+pub fn square_centre(sq: pleco::SQ) -> (f32, f32) {
+    let (x, y) = square_origin(sq);
+    (
+        x as f32 + SQUARE as f32 / 2.0,
+        y as f32 + SQUARE as f32 / 2.0,
+    )
+}
+
+const SPRITE_SIZE: usize = 16;
+
+#[rustfmt::skip]
+const PAWN: [&str; SPRITE_SIZE] = [
+    "................",
+    "................",
+    "................",
+    ".......##.......",
+    "......####......",
+    "......####......",
+    ".......##.......",
+    "......####......",
+    ".....######.....",
+    "......####......",
+    "......####......",
+    ".....######.....",
+    "....########....",
+    "...##########...",
+    "...##########...",
+    "................",
+];
+
+#[rustfmt::skip]
+const KNIGHT: [&str; SPRITE_SIZE] = [
+    "................",
+    "................",
+    "......#.#.......",
+    ".....######.....",
+    "....########....",
+    "...##########...",
+    "...###.######...",
+    "..###########...",
+    "..######.#####..",
+    "...###..######..",
+    ".......#######..",
+    "......#######...",
+    ".....########...",
+    "....##########..",
+    "....##########..",
+    "................",
+];
+
+#[rustfmt::skip]
+const BISHOP: [&str; SPRITE_SIZE] = [
+    "................",
+    ".......##.......",
+    "......####......",
+    ".....##.###.....",
+    "....###.####....",
+    "....##.#####....",
+    "....########....",
+    ".....######.....",
+    "......####......",
+    ".....######.....",
+    "......####......",
+    ".....######.....",
+    "....########....",
+    "...##########...",
+    "...##########...",
+    "................",
+];
+
+#[rustfmt::skip]
+const ROOK: [&str; SPRITE_SIZE] = [
+    "................",
+    "................",
+    "...##.####.##...",
+    "...##.####.##...",
+    "...##########...",
+    "....########....",
+    ".....######.....",
+    ".....######.....",
+    ".....######.....",
+    ".....######.....",
+    ".....######.....",
+    "....########....",
+    "...##########...",
+    "...##########...",
+    "...##########...",
+    "................",
+];
+
+#[rustfmt::skip]
+const QUEEN: [&str; SPRITE_SIZE] = [
+    "................",
+    "..#....##....#..",
+    "..##...##...##..",
+    "..##..####..##..",
+    "..###.####.###..",
+    "...##########...",
+    "...##########...",
+    "....########....",
+    ".....######.....",
+    ".....######.....",
+    "....########....",
+    ".....######.....",
+    "....########....",
+    "...##########...",
+    "...##########...",
+    "................",
+];
+
+#[rustfmt::skip]
+const KING: [&str; SPRITE_SIZE] = [
+    ".......##.......",
+    "......####......",
+    ".......##.......",
+    ".....######.....",
+    "....########....",
+    "...##########...",
+    "...##########...",
+    "....########....",
+    ".....######.....",
+    ".....######.....",
+    "....########....",
+    ".....######.....",
+    "....########....",
+    "...##########...",
+    "...##########...",
+    "................",
+];
+
+fn sprite(piece_type: pleco::PieceType) -> &'static [&'static str; SPRITE_SIZE] {
+    match piece_type {
+        pleco::PieceType::P => &PAWN,
+        pleco::PieceType::N => &KNIGHT,
+        pleco::PieceType::B => &BISHOP,
+        pleco::PieceType::R => &ROOK,
+        pleco::PieceType::Q => &QUEEN,
+        _ => &KING,
+    }
+}
+
+// A 3x5 font covering the board coordinates and captions: SAN, move numbers and rewards
+fn font_glyph(c: char) -> Option<[&'static str; 5]> {
+    let rows = match c {
+        'a' => ["...", ".##", "#.#", "#.#", ".##"],
+        'b' => ["#..", "##.", "#.#", "#.#", "##."],
+        'c' => ["...", ".##", "#..", "#..", ".##"],
+        'd' => ["..#", ".##", "#.#", "#.#", ".##"],
+        'e' => ["...", ".#.", "###", "#..", ".##"],
+        'f' => [".##", "#..", "###", "#..", "#.."],
+        'g' => [".##", "#..", "#.#", "#.#", ".##"],
+        'h' => ["#..", "#..", "##.", "#.#", "#.#"],
+        '1' => [".#.", "##.", ".#.", ".#.", "###"],
+        '2' => ["##.", "..#", ".#.", "#..", "###"],
+        '3' => ["##.", "..#", ".#.", "..#", "##."],
+        '4' => ["#.#", "#.#", "###", "..#", "..#"],
+        '5' => ["###", "#..", "##.", "..#", "##."],
+        '6' => [".##", "#..", "###", "#.#", "###"],
+        '7' => ["###", "..#", ".#.", ".#.", ".#."],
+        '8' => ["###", "#.#", "###", "#.#", "###"],
+        '9' => ["###", "#.#", "###", "..#", "##."],
+        '0' => ["###", "#.#", "#.#", "#.#", "###"],
+        'r' => ["...", "#.#", "##.", "#..", "#.."],
+        'w' => ["...", "#.#", "#.#", "###", "#.#"],
+        'x' => ["...", "...", "#.#", ".#.", "#.#"],
+        'K' => ["#.#", "#.#", "##.", "#.#", "#.#"],
+        'Q' => [".#.", "#.#", "#.#", "##.", ".##"],
+        'R' => ["##.", "#.#", "##.", "#.#", "#.#"],
+        'B' => ["##.", "#.#", "##.", "#.#", "##."],
+        'N' => ["#.#", "###", "###", "#.#", "#.#"],
+        'O' => [".#.", "#.#", "#.#", "#.#", ".#."],
+        '-' => ["...", "...", "###", "...", "..."],
+        '+' => ["...", ".#.", "###", ".#.", "..."],
+        '#' => ["#.#", "###", "#.#", "###", "#.#"],
+        '=' => ["...", "###", "...", "###", "..."],
+        '.' => ["...", "...", "...", "...", ".#."],
+        ':' => ["...", ".#.", "...", ".#.", "..."],
+        _ => return None,
+    };
+    Some(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(canvas: &Canvas, x: usize, y: usize) -> Rgb {
+        let idx = (y * canvas.width + x) * 3;
+        [
+            canvas.pixels[idx],
+            canvas.pixels[idx + 1],
+            canvas.pixels[idx + 2],
+        ]
+    }
+
+    // A pixel in the corner of `sq`, which pieces leave uncovered
+    fn corner(canvas: &Canvas, sq: u8) -> Rgb {
+        let (x, y) = square_origin(pleco::SQ(sq));
+        pixel(canvas, x + 1, y + 1)
+    }
+
+    #[test]
+    fn draws_an_rgb_frame_of_the_board() {
+        let canvas = draw(&pleco::Board::start_pos(), &Overlay::default());
+        assert_eq!((canvas.width, canvas.height), (BOARD_PIXELS, BOARD_PIXELS));
+        // The HxWx3 uint8 array rgb_array hands out
+        assert_eq!(canvas.pixels.len(), BOARD_PIXELS * BOARD_PIXELS * 3);
+        assert_eq!(pixel(&canvas, 0, 0), BORDER);
+        // a1 dark, h1 light, a8 light
+        assert_eq!(corner(&canvas, 0), DARK);
+        assert_eq!(corner(&canvas, 7), LIGHT);
+        assert_eq!(corner(&canvas, 56), LIGHT);
+        // Pieces are drawn in the middle of their squares
+        let (x, y) = square_origin(pleco::SQ(4));
+        assert_ne!(pixel(&canvas, x + SQUARE / 2, y + SQUARE / 2 + 8), DARK);
+        let (x, y) = square_origin(pleco::SQ(28));
+        assert_eq!(pixel(&canvas, x + SQUARE / 2, y + SQUARE / 2), LIGHT);
+    }
+
+    #[test]
+    fn shades_overlays() {
+        let mut board = pleco::Board::start_pos();
+        let e2e4 = *board
+            .generate_moves()
+            .iter()
+            .find(|m| m.stringify() == "e2e4")
+            .unwrap();
+        board.apply_move(e2e4);
+        let mut heat = [0.0; 64];
+        heat[0] = 2.0;
+        heat[7] = 1.0;
+        let canvas = draw(
+            &board,
+            &Overlay {
+                last_move: Some(e2e4),
+                heatmap: Some(&heat),
+            },
+        );
+        let mut e2 = Canvas::new(1, 1, LIGHT);
+        e2.blend(0, 0, LAST_MOVE, 0.5);
+        assert_eq!(corner(&canvas, 12), pixel(&e2, 0, 0));
+        assert_eq!(corner(&canvas, 28), pixel(&e2, 0, 0));
+        // The hottest square is shaded most
+        let mut a1 = Canvas::new(1, 1, DARK);
+        a1.blend(0, 0, HEAT, 0.65);
+        assert_eq!(corner(&canvas, 0), pixel(&a1, 0, 0));
+        assert_ne!(corner(&canvas, 7), LIGHT);
+        assert_eq!(corner(&canvas, 56), LIGHT);
+    }
+
+    #[test]
+    fn blends_and_clips() {
+        let mut canvas = Canvas::new(2, 1, [0, 0, 0]);
+        canvas.blend(0, 0, [255, 100, 10], 0.5);
+        canvas.blend(5, 5, [255, 255, 255], 1.0);
+        assert_eq!(canvas.pixels, [128, 50, 5, 0, 0, 0]);
+        canvas.extend_bottom(2, [1, 2, 3]);
+        assert_eq!(canvas.height, 3);
+        assert_eq!(pixel(&canvas, 1, 2), [1, 2, 3]);
+    }
+}
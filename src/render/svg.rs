@@ -0,0 +1,370 @@
+//! Standalone SVG boards, pieces included as vector graphics.
+
+use std::fmt::Write;
+
+// Board geometry in user units, scaled to the requested size through the viewBox
+const SQUARE: f32 = 45.0;
+const MARGIN: f32 = 15.0;
+const BOARD: f32 = 8.0 * SQUARE + 2.0 * MARGIN;
+
+const BORDER: &str = "#302e2b";
+const COORDINATES: &str = "#c8c8c8";
+const LIGHT: &str = "#f0d9b5";
+const DARK: &str = "#b58863";
+const LAST_MOVE: &str = "#cdd26a";
+
+pub const ARROW_COLOUR: &str = "#15781b";
+/// Width of an arrow's shaft, in units where a square is 45 wide.
+pub const ARROW_WIDTH: f32 = 9.0;
+
+pub struct Arrow {
+    pub from: pleco::SQ,
+    pub to: pleco::SQ,
+    pub colour: String,
+    pub width: f32,
+}
+
+/// What to draw and how.
+pub struct SvgOptions {
+    /// Width and height of the image in pixels.
+    pub size: u32,
+    /// Draws the board from black's side.
+    pub flipped: bool,
+    pub last_move: Option<pleco::BitMove>,
+    /// The square of a king in check.
+    pub check: Option<pleco::SQ>,
+    /// Squares painted in a colour, under the pieces.
+    pub fills: Vec<(pleco::SQ, String)>,
+    /// Arrows drawn over the pieces.
+    pub arrows: Vec<Arrow>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        SvgOptions {
+            size: 400,
+            flipped: false,
+            last_move: None,
+            check: None,
+            fills: vec![],
+            arrows: vec![],
+        }
+    }
+}
+
+/// Renders `board` as a standalone SVG document.
+pub fn board_svg(board: &pleco::Board, options: &SvgOptions) -> String {
+    let mut svg = String::new();
+    // Writing to a String cannot fail
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" version="1.1" width="{size}" height="{size}" viewBox="0 0 {board} {board}">"#,
+        size = options.size,
+        board = BOARD
+    )
+    .unwrap();
+
+    svg.push_str("<defs>\n");
+    for player in [pleco::Player::White, pleco::Player::Black] {
+        for piece_type in [
+            pleco::PieceType::P,
+            pleco::PieceType::N,
+            pleco::PieceType::B,
+            pleco::PieceType::R,
+            pleco::PieceType::Q,
+            pleco::PieceType::K,
+        ] {
+            svg.push_str(&piece_def(pleco::Piece::make_lossy(player, piece_type)));
+        }
+    }
+    svg.push_str(concat!(
+        r##"<radialGradient id="check-gradient">"##,
+        r##"<stop offset="0%" stop-color="#ff0000" stop-opacity="1"/>"##,
+        r##"<stop offset="50%" stop-color="#e70000" stop-opacity="1"/>"##,
+        r##"<stop offset="100%" stop-color="#9e0000" stop-opacity="0"/>"##,
+        "</radialGradient>\n</defs>\n"
+    ));
+
+    writeln!(
+        svg,
+        r#"<rect x="0" y="0" width="{board}" height="{board}" fill="{BORDER}"/>"#,
+        board = BOARD
+    )
+    .unwrap();
+
+    for idx in 0..64u8 {
+        let sq = pleco::SQ(idx);
+        let (x, y) = square_origin(sq, options.flipped);
+        let light = (sq.file_idx_of_sq() + sq.rank_idx_of_sq()) % 2 == 1;
+        let colour = if light { LIGHT } else { DARK };
+        square_rect(&mut svg, x, y, colour, None);
+    }
+    for (sq, colour) in &options.fills {
+        let (x, y) = square_origin(*sq, options.flipped);
+        square_rect(&mut svg, x, y, &escape(colour), None);
+    }
+    if let Some(last_move) = options.last_move {
+        for sq in [last_move.get_src(), last_move.get_dest()] {
+            let (x, y) = square_origin(sq, options.flipped);
+            square_rect(&mut svg, x, y, LAST_MOVE, Some(0.5));
+        }
+    }
+    if let Some(sq) = options.check {
+        let (x, y) = square_origin(sq, options.flipped);
+        square_rect(&mut svg, x, y, "url(#check-gradient)", None);
+    }
+
+    for idx in 0..8u8 {
+        let file = (b'a' + idx) as char;
+        let (x, _) = square_origin(pleco::SQ(idx), options.flipped);
+        let (_, y) = square_origin(pleco::SQ(idx * 8), options.flipped);
+        writeln!(
+            svg,
+            r#"<text x="{}" y="{}" font-family="sans-serif" font-size="11" text-anchor="middle" fill="{COORDINATES}">{}</text>"#,
+            x + SQUARE / 2.0,
+            BOARD - MARGIN / 2.0 + 4.0,
+            file
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            r#"<text x="{}" y="{}" font-family="sans-serif" font-size="11" text-anchor="middle" fill="{COORDINATES}">{}</text>"#,
+            MARGIN / 2.0,
+            y + SQUARE / 2.0 + 4.0,
+            idx + 1
+        )
+        .unwrap();
+    }
+
+    for (sq, piece) in board.get_piece_locations() {
+        let (x, y) = square_origin(sq, options.flipped);
+        writeln!(
+            svg,
+            r##"<use xlink:href="#{}" href="#{}" transform="translate({}, {})"/>"##,
+            piece_id(piece),
+            piece_id(piece),
+            x,
+            y
+        )
+        .unwrap();
+    }
+
+    for arrow in &options.arrows {
+        svg.push_str(&arrow_polygon(arrow, options.flipped));
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn square_origin(sq: pleco::SQ, flipped: bool) -> (f32, f32) {
+    let (file, rank) = (sq.file_idx_of_sq(), sq.rank_idx_of_sq());
+    let (col, row) = if flipped {
+        (7 - file, rank)
+    } else {
+        (file, 7 - rank)
+    };
+    (MARGIN + col as f32 * SQUARE, MARGIN + row as f32 * SQUARE)
+}
+
+fn square_rect(svg: &mut String, x: f32, y: f32, fill: &str, opacity: Option<f32>) {
+    let opacity = opacity.map_or(String::new(), |o| format!(r#" fill-opacity="{}""#, o));
+    writeln!(
+        svg,
+        r#"<rect x="{}" y="{}" width="{SQUARE}" height="{SQUARE}" fill="{}"{}/>"#,
+        x, y, fill, opacity
+    )
+    .unwrap();
+}
+
+// A shaft and a triangular head, in the same proportions as the raster arrows
+fn arrow_polygon(arrow: &Arrow, flipped: bool) -> String {
+    let centre = |sq| {
+        let (x, y) = square_origin(sq, flipped);
+        (x + SQUARE / 2.0, y + SQUARE / 2.0)
+    };
+    let (from, to) = (centre(arrow.from), centre(arrow.to));
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = (dx * dx + dy * dy).sqrt();
+    if length == 0.0 {
+        return String::new();
+    }
+    let (ux, uy) = (dx / length, dy / length);
+    // Perpendicular to the arrow
+    let (nx, ny) = (-uy, ux);
+    let head_length = (arrow.width * 2.5).min(length);
+    let head_width = arrow.width * 1.6;
+    let base = (to.0 - ux * head_length, to.1 - uy * head_length);
+    let shaft = arrow.width / 2.0;
+
+    let points = [
+        (from.0 + nx * shaft, from.1 + ny * shaft),
+        (base.0 + nx * shaft, base.1 + ny * shaft),
+        (base.0 + nx * head_width, base.1 + ny * head_width),
+        to,
+        (base.0 - nx * head_width, base.1 - ny * head_width),
+        (base.0 - nx * shaft, base.1 - ny * shaft),
+        (from.0 - nx * shaft, from.1 - ny * shaft),
+    ]
+    .iter()
+    .map(|(x, y)| format!("{:.2},{:.2}", x, y))
+    .collect::<Vec<_>>()
+    .join(" ");
+    format!(
+        "<polygon points=\"{}\" fill=\"{}\" fill-opacity=\"0.8\"/>\n",
+        points,
+        escape(&arrow.colour)
+    )
+}
+
+fn piece_id(piece: pleco::Piece) -> String {
+    let player = match piece.player_lossy() {
+        pleco::Player::White => "white",
+        pleco::Player::Black => "black",
+    };
+    let piece_type = match piece.type_of() {
+        pleco::PieceType::P => "pawn",
+        pleco::PieceType::N => "knight",
+        pleco::PieceType::B => "bishop",
+        pleco::PieceType::R => "rook",
+        pleco::PieceType::Q => "queen",
+        _ => "king",
+    };
+    format!("{}-{}", player, piece_type)
+}
+
+// Pieces are drawn on a 45x45 square. Details such as the knight's eye take the colour of the
+// opposite side so they show on black pieces too.
+fn piece_def(piece: pleco::Piece) -> String {
+    let (fill, detail) = match piece.player_lossy() {
+        pleco::Player::White => ("#ffffff", "#000000"),
+        pleco::Player::Black => ("#000000", "#ffffff"),
+    };
+    let shapes = match piece.type_of() {
+        pleco::PieceType::P => concat!(
+            r#"<path d="M 22.5,9 A 4.5,4.5 0 0 0 18.9,16.2 C 16.5,17.6 15.5,20 16.5,22 L 19,22 "#,
+            r#"C 16,26 14,31 14,36 L 31,36 C 31,31 29,26 26,22 L 28.5,22 "#,
+            r#"C 29.5,20 28.5,17.6 26.1,16.2 A 4.5,4.5 0 0 0 22.5,9 Z"/>"#
+        )
+        .to_string(),
+        pleco::PieceType::N => format!(
+            concat!(
+                r#"<path d="M 14,36 L 33,36 C 33,30 31,24 32,18 C 32,13 28,9 22,9 L 20,6 L 18,10 "#,
+                r#"C 15,12 12,17 11,22 C 10,25 13,27 15,25 L 18,23 C 19,24 20,24 21,23 "#,
+                r#"C 20,28 16,31 14,36 Z"/>"#,
+                r#"<circle cx="18" cy="15" r="1.3" fill="{detail}" stroke="none"/>"#
+            ),
+            detail = detail
+        ),
+        pleco::PieceType::B => format!(
+            concat!(
+                r#"<path d="M 13,36 L 32,36 L 32,33 L 27,33 C 30,28 31,23 29,19 "#,
+                r#"C 27.5,16 25,13.5 22.5,12 C 20,13.5 17.5,16 16,19 C 14,23 15,28 18,33 "#,
+                r#"L 13,33 Z"/>"#,
+                r#"<circle cx="22.5" cy="9.5" r="2.5"/>"#,
+                r#"<path d="M 22.5,18 L 22.5,25 M 19,21.5 L 26,21.5" stroke="{detail}" fill="none"/>"#
+            ),
+            detail = detail
+        ),
+        pleco::PieceType::R => concat!(
+            r#"<path d="M 11,36 L 34,36 L 34,32 L 31,32 L 29,18 L 32,15 L 32,10 L 28,10 "#,
+            r#"L 28,12.5 L 24.5,12.5 L 24.5,10 L 20.5,10 L 20.5,12.5 L 17,12.5 L 17,10 "#,
+            r#"L 13,10 L 13,15 L 16,18 L 14,32 L 11,32 Z"/>"#
+        )
+        .to_string(),
+        pleco::PieceType::Q => concat!(
+            r#"<path d="M 10,36 L 35,36 L 35,33 L 32,33 L 36,14 L 29.5,25 L 29,11 L 25,24 "#,
+            r#"L 22.5,9 L 20,24 L 16,11 L 15.5,25 L 9,14 L 13,33 L 10,33 Z"/>"#,
+            r#"<circle cx="9" cy="14" r="2"/><circle cx="16" cy="11" r="2"/>"#,
+            r#"<circle cx="22.5" cy="9" r="2"/><circle cx="29" cy="11" r="2"/>"#,
+            r#"<circle cx="36" cy="14" r="2"/>"#
+        )
+        .to_string(),
+        _ => concat!(
+            r#"<path d="M 22.5,6 L 22.5,20 M 18.5,9.5 L 26.5,9.5" fill="none"/>"#,
+            r#"<path d="M 11,36 L 34,36 L 34,33 L 31,33 C 35,28 37,22 33,18 "#,
+            r#"C 30,15 25,17 22.5,21 C 20,17 15,15 12,18 C 8,22 10,28 14,33 L 11,33 Z"/>"#
+        )
+        .to_string(),
+    };
+    format!(
+        "<g id=\"{}\" fill=\"{}\" stroke=\"#000000\" stroke-width=\"1.5\" stroke-linejoin=\"round\">{}</g>\n",
+        piece_id(piece),
+        fill,
+        shapes
+    )
+}
+
+// Colours come from callers and end up in attributes
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_fills_arrows_and_pieces() {
+        let options = SvgOptions {
+            size: 200,
+            fills: vec![(pleco::SQ(0), "#ff0000".to_string())],
+            arrows: vec![
+                Arrow {
+                    from: pleco::SQ(12),
+                    to: pleco::SQ(28),
+                    colour: "blue".to_string(),
+                    width: ARROW_WIDTH,
+                },
+                Arrow {
+                    from: pleco::SQ(6),
+                    to: pleco::SQ(21),
+                    colour: "\"><script>".to_string(),
+                    width: ARROW_WIDTH,
+                },
+            ],
+            check: Some(pleco::SQ(4)),
+            ..SvgOptions::default()
+        };
+        let svg = board_svg(&pleco::Board::start_pos(), &options);
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains(r#"width="200" height="200" viewBox="0 0 390 390""#));
+        assert!(svg.ends_with("</svg>\n"));
+        // a1, filled after the squares are drawn
+        assert!(svg.contains(r##"<rect x="15" y="330" width="45" height="45" fill="#ff0000"/>"##));
+        assert!(svg.contains(r##"fill="url(#check-gradient)""##));
+        assert_eq!(svg.matches("<polygon ").count(), 2);
+        assert!(svg.contains(r#"fill="blue" fill-opacity="0.8""#));
+        assert!(svg.contains(r#"fill="&quot;>&lt;script>""#));
+        assert!(!svg.contains("<script>"));
+        assert_eq!(svg.matches("<use ").count(), 32);
+        assert!(svg.contains(r##"href="#white-king" transform="translate(195, 330)""##));
+    }
+
+    #[test]
+    fn flips_the_board() {
+        let options = SvgOptions {
+            flipped: true,
+            fills: vec![(pleco::SQ(0), "red".to_string())],
+            ..SvgOptions::default()
+        };
+        let svg = board_svg(&pleco::Board::start_pos(), &options);
+        // a1 is in the top right corner from black's side
+        assert!(svg.contains(r#"<rect x="330" y="15" width="45" height="45" fill="red"/>"#));
+        assert!(svg.contains(r##"href="#white-king" transform="translate(150, 15)""##));
+    }
+
+    #[test]
+    fn skips_arrows_to_their_own_square() {
+        let arrow = Arrow {
+            from: pleco::SQ(9),
+            to: pleco::SQ(9),
+            colour: ARROW_COLOUR.to_string(),
+            width: ARROW_WIDTH,
+        };
+        assert_eq!(arrow_polygon(&arrow, false), "");
+    }
+}
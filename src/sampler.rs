@@ -0,0 +1,408 @@
+use pleco::tools::prng::PRNG;
+use pyo3::exceptions::PyIOError;
+use pyo3::prelude::*;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+use crate::board::Board;
+use crate::endgame::{parse_material, random_position};
+use crate::error::{InvalidArgumentError, InvalidFenError};
+use crate::pgn::PgnReader;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Positions reached before this many half moves are considered part of the opening
+const OPENING_PLIES: u16 = 20;
+
+#[pyclass]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum EndgameGenerator {
+    KQvK,
+    KRvK,
+    KPvK,
+}
+
+impl EndgameGenerator {
+    fn material(&self) -> &'static str {
+        match self {
+            EndgameGenerator::KQvK => "KQvK",
+            EndgameGenerator::KRvK => "KRvK",
+            EndgameGenerator::KPvK => "KPvK",
+        }
+    }
+}
+
+#[pyclass]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GamePhase {
+    Opening,
+    Middlegame,
+    Endgame,
+}
+
+impl GamePhase {
+    pub fn of(board: &pleco::Board) -> GamePhase {
+        if board.non_pawn_material_all() <= pleco::core::score::END_GAME_LIMIT {
+            GamePhase::Endgame
+        } else if board.moves_played() < OPENING_PLIES {
+            GamePhase::Opening
+        } else {
+            GamePhase::Middlegame
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Source {
+    Fens(Vec<String>),
+    Generators(Vec<(EndgameGenerator, f64)>),
+}
+
+/// Draws the starting position of an episode for curriculum learning.
+///
+/// Positions come either from a fixed pool (a FEN/EPD file or positions extracted from a PGN
+/// corpus) or from a weighted list of random endgame generators.
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct StartPositionSampler {
+    source: Source,
+}
+
+impl StartPositionSampler {
+    pub fn sample_board(&self, prng: &mut PRNG) -> pleco::Board {
+        match &self.source {
+            Source::Fens(fens) => {
+                let idx = (prng.rand() % fens.len() as u64) as usize;
+                // Every FEN was validated when the pool was loaded
+                pleco::Board::from_fen(&fens[idx]).unwrap()
+            }
+            Source::Generators(generators) => {
+                let chosen = choose(generators, prng.rand() as f64 / u64::MAX as f64);
+                // Static signatures always parse, and these endings always have legal placements
+                let material = parse_material(chosen.material()).unwrap();
+                random_position(&material, None, true, prng).unwrap()
+            }
+        }
+    }
+}
+
+#[pymethods]
+impl StartPositionSampler {
+    fn __repr__(&self) -> String {
+        match &self.source {
+            Source::Fens(fens) => format!("StartPositionSampler({} positions)", fens.len()),
+            Source::Generators(generators) => {
+                format!("StartPositionSampler({:?})", generators)
+            }
+        }
+    }
+
+    /// Loads one position per line. EPD lines (four fields plus opcodes) are accepted too.
+    #[staticmethod]
+    pub fn from_fen_file(path: &str) -> PyResult<Self> {
+        let fens = read_fen_file(path).map_err(|err| match err.kind() {
+            io::ErrorKind::InvalidInput => InvalidFenError::new_err(err.to_string()),
+            _ => PyIOError::new_err(err.to_string()),
+        })?;
+        Self::from_fens(fens)
+    }
+
+    #[staticmethod]
+    pub fn from_generators(generators: Vec<(EndgameGenerator, f64)>) -> PyResult<Self> {
+        if generators
+            .iter()
+            .any(|(_, weight)| !weight.is_finite() || *weight < 0.0)
+        {
+            return Err(InvalidArgumentError::new_err(
+                "generator weights must be finite and non-negative",
+            ));
+        }
+        if generators.iter().map(|(_, weight)| weight).sum::<f64>() <= 0.0 {
+            return Err(InvalidArgumentError::new_err(
+                "at least one generator needs a positive weight",
+            ));
+        }
+        Ok(Self {
+            source: Source::Generators(generators),
+        })
+    }
+
+    /// Collects every position of the given phase from the main lines of a PGN corpus.
+    ///
+    /// Games that cannot be read or replayed are skipped.
+    #[staticmethod]
+    pub fn from_pgn_file(path: &str, phase: GamePhase) -> PyResult<Self> {
+        let fens = read_pgn_file(path, phase).map_err(|err| PyIOError::new_err(err.to_string()))?;
+        Self::from_fens(fens)
+    }
+
+    pub fn sample(&self, seed: u64) -> Board {
+        self.sample_board(&mut seeded_prng(seed)).into()
+    }
+}
+
+impl StartPositionSampler {
+    fn from_fens(fens: Vec<String>) -> PyResult<Self> {
+        if fens.is_empty() {
+            return Err(InvalidArgumentError::new_err("no positions found"));
+        }
+        Ok(Self {
+            source: Source::Fens(fens),
+        })
+    }
+}
+
+// The generator whose share of the total weight holds `fraction`, between 0 and 1. Generators
+// without weight are never chosen, even when rounding takes `fraction` to the very end.
+fn choose(generators: &[(EndgameGenerator, f64)], fraction: f64) -> EndgameGenerator {
+    let total = generators.iter().map(|(_, weight)| weight).sum::<f64>();
+    let mut target = fraction * total;
+    let mut chosen = None;
+    for (generator, weight) in generators.iter().filter(|(_, weight)| *weight > 0.0) {
+        chosen = Some(*generator);
+        if target < *weight {
+            break;
+        }
+        target -= weight;
+    }
+    // from_generators requires a positive weight
+    chosen.unwrap()
+}
+
+// Invalid positions are reported as `InvalidInput` with the line they are on
+fn read_fen_file(path: &str) -> io::Result<Vec<String>> {
+    let file = File::open(path)?;
+    let mut fens = vec![];
+    for (line_num, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fen = normalize_epd(line);
+        if pleco::Board::from_fen(&fen).is_err() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}:{}: invalid position: {}", path, line_num + 1, line),
+            ));
+        }
+        fens.push(fen);
+    }
+    Ok(fens)
+}
+
+fn read_pgn_file(path: &str, phase: GamePhase) -> io::Result<Vec<String>> {
+    let file = File::open(path)?;
+    let mut fens = vec![];
+    for game in PgnReader::new(BufReader::new(file)).flatten() {
+        let mut positions = vec![];
+        let replayed = game.replay(|board, _| {
+            if GamePhase::of(board) == phase {
+                positions.push(board.fen());
+            }
+        });
+        if replayed.is_ok() {
+            fens.extend(positions);
+        }
+    }
+    Ok(fens)
+}
+
+/// Builds a PRNG from an arbitrary seed. The xorshift generator gets stuck on a zero state, so
+/// the seed is scrambled first.
+pub fn seeded_prng(seed: u64) -> PRNG {
+    // splitmix64 finalizer
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    PRNG::init(if z == 0 { 1 } else { z })
+}
+
+/// A seed drawn from the clock, for callers that did not ask for reproducibility.
+pub fn entropy_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+fn normalize_epd(line: &str) -> String {
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+    let position = fields.iter().take(4).copied().collect::<Vec<_>>().join(" ");
+    let counters = fields
+        .iter()
+        .skip(4)
+        .take(2)
+        .take_while(|field| field.parse::<u16>().is_ok())
+        .copied()
+        .collect::<Vec<_>>();
+    match counters.len() {
+        2 => format!("{} {}", position, counters.join(" ")),
+        _ => format!("{} 0 1", position),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write_file(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("gym_chess_pleco_{}", name));
+        fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    fn generators(weights: [f64; 3]) -> StartPositionSampler {
+        let generators = [
+            EndgameGenerator::KQvK,
+            EndgameGenerator::KRvK,
+            EndgameGenerator::KPvK,
+        ];
+        StartPositionSampler::from_generators(generators.into_iter().zip(weights).collect())
+            .unwrap()
+    }
+
+    fn material(board: &pleco::Board) -> &'static str {
+        if board.count_pieces_player(pleco::Player::White) != 2 {
+            "?"
+        } else if board.count_piece(pleco::Player::White, pleco::PieceType::Q) == 1 {
+            "KQvK"
+        } else if board.count_piece(pleco::Player::White, pleco::PieceType::R) == 1 {
+            "KRvK"
+        } else {
+            "KPvK"
+        }
+    }
+
+    #[test]
+    fn same_seed_same_positions() {
+        let sampler = generators([1.0, 1.0, 1.0]);
+        let draw = |seed| {
+            let mut prng = seeded_prng(seed);
+            (0..20)
+                .map(|_| sampler.sample_board(&mut prng).fen())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(draw(7), draw(7));
+        assert_ne!(draw(7), draw(8));
+        // A zero seed is scrambled rather than sticking the generator at zero
+        assert_ne!(seeded_prng(0).rand(), 0);
+    }
+
+    #[test]
+    fn follows_the_generator_weights() {
+        let sampler = generators([3.0, 1.0, 0.0]);
+        let mut prng = seeded_prng(1);
+        let mut counts = [0; 3];
+        for _ in 0..4000 {
+            match material(&sampler.sample_board(&mut prng)) {
+                "KQvK" => counts[0] += 1,
+                "KRvK" => counts[1] += 1,
+                other => panic!("sampled {}", other),
+            }
+        }
+        assert!((2850..3150).contains(&counts[0]), "{:?}", counts);
+    }
+
+    #[test]
+    fn never_chooses_generators_without_weight() {
+        let weighted = [
+            (EndgameGenerator::KQvK, 0.0),
+            (EndgameGenerator::KRvK, 1.0),
+            (EndgameGenerator::KPvK, 0.0),
+        ];
+        assert_eq!(choose(&weighted, 0.0), EndgameGenerator::KRvK);
+        assert_eq!(choose(&weighted, 1.0), EndgameGenerator::KRvK);
+        // Past the total, as rounding can take it
+        assert_eq!(choose(&weighted, 1.0 + 1e-9), EndgameGenerator::KRvK);
+        let halves = [(EndgameGenerator::KQvK, 0.5), (EndgameGenerator::KPvK, 0.5)];
+        assert_eq!(choose(&halves, 0.49), EndgameGenerator::KQvK);
+        assert_eq!(choose(&halves, 0.5), EndgameGenerator::KPvK);
+
+        let kqk = EndgameGenerator::KQvK;
+        assert!(StartPositionSampler::from_generators(vec![(kqk, 0.0)]).is_err());
+        assert!(StartPositionSampler::from_generators(vec![(kqk, -1.0)]).is_err());
+        assert!(StartPositionSampler::from_generators(vec![(kqk, f64::NAN)]).is_err());
+        assert!(StartPositionSampler::from_generators(vec![(kqk, f64::INFINITY)]).is_err());
+    }
+
+    #[test]
+    fn loads_fen_and_epd_files() {
+        let path = write_file(
+            "positions.epd",
+            "# comments and blank lines are skipped\n\
+             \n\
+             rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1\n\
+             4k3/8/8/8/8/8/4P3/4K3 w - - bm e4; id \"kp\";\n\
+             \t8/8/8/8/8/5k2/8/5K1R b - - 12 40  \n",
+        );
+        let fens = read_fen_file(&path).unwrap();
+        assert_eq!(
+            fens,
+            [
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+                "8/8/8/8/8/5k2/8/5K1R b - - 12 40",
+            ]
+        );
+        let sampler = StartPositionSampler::from_fen_file(&path).unwrap();
+        let mut prng = seeded_prng(3);
+        for _ in 0..10 {
+            assert!(fens.contains(&sampler.sample_board(&mut prng).fen()));
+        }
+    }
+
+    #[test]
+    fn reports_the_line_of_a_bad_position() {
+        let path = write_file(
+            "bad_positions.fen",
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\n\
+             \n\
+             4k3/8/8/8/8/8/4P3/4K3 x - - 0 1\n",
+        );
+        let err = read_fen_file(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "{}:3: invalid position: 4k3/8/8/8/8/8/4P3/4K3 x - - 0 1",
+                path
+            )
+        );
+        let missing = read_fen_file("/nonexistent/positions.fen").unwrap_err();
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+        assert!(StartPositionSampler::from_fen_file(&path).is_err());
+    }
+
+    #[test]
+    fn collects_positions_of_one_phase() {
+        // 24 plies from the start, then a pawn ending after 4 plies
+        let path = write_file(
+            "phases.pgn",
+            "[Event \"opening\"]\n\n\
+             1. Nf3 Nf6 2. Ng1 Ng8 3. Nf3 Nf6 4. Ng1 Ng8 5. Nf3 Nf6 6. Ng1 Ng8 \
+             7. Nf3 Nf6 8. Ng1 Ng8 9. Nf3 Nf6 10. Ng1 Ng8 11. Nf3 Nf6 12. Ng1 Ng8 *\n\n\
+             [Event \"ending\"]\n\
+             [FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\"]\n\n\
+             1. e4 Kd7 2. Kd2 Ke6 *\n",
+        );
+        let opening = read_pgn_file(&path, GamePhase::Opening).unwrap();
+        assert_eq!(opening.len(), 20);
+        assert_eq!(opening[0], pleco::Board::start_pos().fen());
+        let middlegame = read_pgn_file(&path, GamePhase::Middlegame).unwrap();
+        assert_eq!(middlegame.len(), 4);
+        let endgame = read_pgn_file(&path, GamePhase::Endgame).unwrap();
+        assert_eq!(endgame.len(), 4);
+        assert_eq!(endgame[0], "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        for (fens, phase) in [
+            (&opening, GamePhase::Opening),
+            (&middlegame, GamePhase::Middlegame),
+            (&endgame, GamePhase::Endgame),
+        ] {
+            for fen in fens {
+                assert_eq!(GamePhase::of(&pleco::Board::from_fen(fen).unwrap()), phase);
+            }
+        }
+    }
+}
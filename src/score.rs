@@ -1,14 +1,117 @@
+// pyo3 0.18 expands the operator methods into nested impls
+#![allow(non_local_definitions)]
+
+use pleco::core::score::{Value, END_GAME_LIMIT, MID_GAME_LIMIT};
+use pyo3::basic::CompareOp;
+use pyo3::exceptions::PyOverflowError;
 use pyo3::prelude::*;
 
+/// Phase of a position with all the middlegame material on the board. Endgames are phase 0.
+pub const PHASE_MIDGAME: i32 = 128;
+
+/// A pair of middlegame and endgame values, interpolated by game phase.
 #[pyclass]
 #[derive(Copy, Clone, Debug)]
 pub struct Score(pleco::core::score::Score);
 
 #[pymethods]
 impl Score {
+    #[new]
+    pub fn new(mg: Value, eg: Value) -> Self {
+        Score(pleco::core::score::Score::new(mg, eg))
+    }
+
     fn __repr__(&self) -> String {
         self.0.to_string()
     }
+
+    #[getter]
+    pub fn mg(&self) -> Value {
+        self.0.mg()
+    }
+
+    #[getter]
+    pub fn eg(&self) -> Value {
+        self.0.eg()
+    }
+
+    /// The middlegame and endgame values in pawns.
+    pub fn centipawns(&self) -> (f64, f64) {
+        self.0.centipawns()
+    }
+
+    /// Blends both values by `phase`, from 0 (endgame) to `PHASE_MIDGAME` (128, middlegame).
+    pub fn value(&self, phase: i32) -> Value {
+        let phase = i64::from(phase.clamp(0, PHASE_MIDGAME));
+        let midgame = i64::from(PHASE_MIDGAME);
+        let (mg, eg) = (i64::from(self.0.mg()), i64::from(self.0.eg()));
+        // A weighted mean of two i32 values, so it fits back into one
+        ((mg * phase + eg * (midgame - phase)) / midgame) as Value
+    }
+
+    fn __add__(&self, other: &Self) -> PyResult<Self> {
+        self.checked(other, i32::checked_add, "addition")
+    }
+
+    fn __sub__(&self, other: &Self) -> PyResult<Self> {
+        self.checked(other, i32::checked_sub, "subtraction")
+    }
+
+    fn __neg__(&self) -> PyResult<Self> {
+        Score::ZERO.checked(self, i32::checked_sub, "negation")
+    }
+
+    fn __mul__(&self, factor: i32) -> PyResult<Self> {
+        self.checked(
+            &Score::new(factor, factor),
+            i32::checked_mul,
+            "multiplication",
+        )
+    }
+
+    fn __rmul__(&self, factor: i32) -> PyResult<Self> {
+        self.__mul__(factor)
+    }
+
+    fn __hash__(&self) -> u64 {
+        ((self.0.mg() as u32 as u64) << 32) | self.0.eg() as u32 as u64
+    }
+
+    fn __richcmp__(&self, other: &Self, op: CompareOp, py: Python<'_>) -> PyObject {
+        match op {
+            CompareOp::Eq => (self.0 == other.0).into_py(py),
+            CompareOp::Ne => (self.0 != other.0).into_py(py),
+            _ => py.NotImplemented(),
+        }
+    }
+}
+
+impl Score {
+    const ZERO: Score = Score(pleco::core::score::Score::ZERO);
+
+    // Applies `op` to both halves, raising OverflowError instead of wrapping
+    fn checked(
+        &self,
+        other: &Score,
+        op: fn(Value, Value) -> Option<Value>,
+        operation: &str,
+    ) -> PyResult<Score> {
+        match (op(self.mg(), other.mg()), op(self.eg(), other.eg())) {
+            (Some(mg), Some(eg)) => Ok(Score::new(mg, eg)),
+            _ => Err(PyOverflowError::new_err(format!(
+                "score {} overflows a 32-bit value",
+                operation
+            ))),
+        }
+    }
+}
+
+/// The game phase of `board`, from 0 (endgame) to `PHASE_MIDGAME`, based on non-pawn material.
+pub fn phase(board: &pleco::Board) -> i32 {
+    let material = board
+        .non_pawn_material_all()
+        .clamp(END_GAME_LIMIT, MID_GAME_LIMIT);
+    (material - END_GAME_LIMIT) * PHASE_MIDGAME / (MID_GAME_LIMIT - END_GAME_LIMIT)
 }
 
 impl From<pleco::core::score::Score> for Score {
@@ -22,3 +125,29 @@ impl From<Score> for pleco::core::score::Score {
         score.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blends_extreme_values() {
+        let score = Score::new(i32::MAX, i32::MIN);
+        assert_eq!(score.value(PHASE_MIDGAME), i32::MAX);
+        assert_eq!(score.value(0), i32::MIN);
+        assert_eq!(score.value(PHASE_MIDGAME / 2), 0);
+        assert_eq!(Score::new(100, 20).value(96), 80);
+    }
+
+    #[test]
+    fn arithmetic_stays_in_range() {
+        let score = Score::new(1 << 30, -3);
+        assert_eq!(score.__mul__(-2).unwrap().mg(), i32::MIN);
+        assert_eq!(score.__add__(&score.__neg__().unwrap()).unwrap().eg(), 0);
+        assert!(score.__mul__(2).is_err());
+        assert!(score.__rmul__(3).is_err());
+        assert!(Score::new(0, i32::MIN).__neg__().is_err());
+        assert!(Score::new(i32::MAX, 0).__add__(&Score::new(1, 0)).is_err());
+        assert!(Score::new(0, i32::MIN).__sub__(&Score::new(0, 1)).is_err());
+    }
+}
@@ -0,0 +1,668 @@
+//! Depth-, time- and node-limited search with a principal variation.
+//!
+//! pleco's searchers only return a move, so each `Bot` is run here on pleco's evaluator: plain
+//! minimax, alpha-beta, and the iterative searcher's alpha-beta with move ordering and a
+//! quiescence search. pleco's jamboree searcher is a parallel alpha-beta that has no counterpart
+//! here, so it can only be played as a `Bot`. Every algorithm deepens one ply at a time, keeping
+//! the last iteration that finished, so limits other than depth still give the best move found
+//! so far.
+
+use pleco::core::GenTypes;
+use pleco::tools::prng::PRNG;
+use pleco::{BitMove, Board, PieceType};
+use pyo3::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::engine::{Bot, DEFAULT_DEPTH};
+use crate::evaluation::evaluate;
+use crate::piece_move;
+use crate::sampler::{entropy_seed, seeded_prng};
+use crate::transposition::{Bound, TranspositionTable};
+
+const MAX_PLY: usize = 128;
+const MATE: i32 = 32_000;
+const INFINITE: i32 = MATE + 1;
+// Nodes between two looks at the clock
+const TIME_CHECK_INTERVAL: u64 = 1024;
+
+/// The bots `search` runs.
+pub const ALGORITHMS: [Bot; 4] = [Bot::Random, Bot::Minimax, Bot::AlphaBeta, Bot::Iterative];
+
+/// The bot named `name`, if it is one of `ALGORITHMS`.
+pub fn parse_algorithm(name: &str) -> Result<Bot, String> {
+    let bot = name.parse::<Bot>()?;
+    if !ALGORITHMS.contains(&bot) {
+        let names = ALGORITHMS.map(|algorithm| algorithm.key());
+        return Err(format!(
+            "{} has no limited search, expected one of {:?}",
+            bot.key(),
+            names
+        ));
+    }
+    Ok(bot)
+}
+
+/// When to stop searching. With no limit at all, `DEFAULT_DEPTH` is searched.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Limits {
+    pub depth: Option<u16>,
+    pub movetime: Option<Duration>,
+    pub nodes: Option<u64>,
+}
+
+/// Stops a search from another thread.
+#[pyclass]
+#[derive(Clone, Default)]
+pub struct SearchHandle {
+    stop: Arc<AtomicBool>,
+}
+
+impl SearchHandle {
+    pub fn stop_flag(&self) -> &AtomicBool {
+        &self.stop
+    }
+}
+
+#[pymethods]
+impl SearchHandle {
+    #[new]
+    pub fn new() -> Self {
+        SearchHandle::default()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("SearchHandle(stopped={})", self.stopped())
+    }
+
+    /// Ends every search using this handle, which returns the best move found so far.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    #[getter]
+    pub fn stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    /// Lets the handle be used for another search.
+    pub fn reset(&self) {
+        self.stop.store(false, Ordering::Relaxed);
+    }
+}
+
+/// The outcome of a search.
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    pub best_move: Option<BitMove>,
+    /// In centipawns from the point of view of the side to move.
+    pub score: i32,
+    pub pv: Vec<BitMove>,
+    /// The deepest iteration that finished.
+    pub depth: u16,
+    pub nodes: u64,
+    pub time: Duration,
+}
+
+#[pymethods]
+impl SearchResult {
+    fn __repr__(&self) -> String {
+        let best_move = self
+            .best_move
+            .map_or_else(|| "None".to_string(), |bit_move| bit_move.stringify());
+        format!(
+            "SearchResult(best_move={}, score={}, depth={}, nodes={}, time_ms={})",
+            best_move,
+            self.score,
+            self.depth,
+            self.nodes,
+            self.time.as_millis()
+        )
+    }
+
+    /// None when there is no legal move.
+    #[getter]
+    fn best_move(&self) -> Option<piece_move::BitMove> {
+        self.best_move.map(piece_move::BitMove::from)
+    }
+
+    #[getter]
+    fn score(&self) -> i32 {
+        self.score
+    }
+
+    /// Moves until mate, positive if the side to move mates, or None without a forced mate.
+    #[getter]
+    fn mate(&self) -> Option<i32> {
+        let plies = MATE - self.score.abs();
+        if plies > MAX_PLY as i32 {
+            return None;
+        }
+        let moves = (plies + 1) / 2;
+        Some(if self.score > 0 { moves } else { -moves })
+    }
+
+    #[getter]
+    fn pv(&self) -> Vec<piece_move::BitMove> {
+        self.pv
+            .iter()
+            .map(|m| piece_move::BitMove::from(*m))
+            .collect()
+    }
+
+    #[getter]
+    fn depth(&self) -> u16 {
+        self.depth
+    }
+
+    #[getter]
+    fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    #[getter]
+    fn time_ms(&self) -> f64 {
+        self.time.as_secs_f64() * 1000.0
+    }
+}
+
+/// Searches `board` until `limits` are reached or `stop` is set. `history` holds the zobrist
+/// keys of the positions played before, the current one last, to score repetitions as draws.
+/// Results are shared with other searches through `table`, which starts a new generation.
+///
+/// Panics if `algorithm` is not one of `ALGORITHMS`.
+pub fn search(
+    board: &Board,
+    history: &[u64],
+    algorithm: Bot,
+    limits: Limits,
+    stop: &AtomicBool,
+    table: Option<&TranspositionTable>,
+) -> SearchResult {
+    assert!(
+        ALGORITHMS.contains(&algorithm),
+        "{} has no limited search",
+        algorithm.key()
+    );
+    let start = Instant::now();
+    let mut search = Search {
+        board: board.shallow_clone(),
+        algorithm,
+        limits,
+        stop,
+        start,
+        nodes: 0,
+        aborted: false,
+        keys: history.to_vec(),
+        pv: vec![vec![]; MAX_PLY + 1],
+        previous: vec![],
+        table,
+    };
+    if let Some(table) = table {
+        table.new_search();
+    }
+    if search.keys.last() != Some(&board.zobrist()) {
+        search.keys.push(board.zobrist());
+    }
+
+    let moves = board.generate_moves();
+    let mut result = SearchResult {
+        best_move: moves.first().copied(),
+        score: evaluate(board),
+        pv: moves.first().copied().into_iter().collect(),
+        depth: 0,
+        nodes: 0,
+        time: Duration::ZERO,
+    };
+    if moves.is_empty() {
+        result.score = if board.in_check() { -MATE } else { 0 };
+    } else if algorithm == Bot::Random {
+        let mut prng: PRNG = seeded_prng(entropy_seed());
+        let bit_move = moves[prng.rand() as usize % moves.len()];
+        result.best_move = Some(bit_move);
+        result.pv = vec![bit_move];
+        result.depth = 1;
+    } else {
+        let max_depth = match limits {
+            Limits {
+                depth: Some(depth), ..
+            } => depth,
+            Limits {
+                movetime: None,
+                nodes: None,
+                ..
+            } => DEFAULT_DEPTH,
+            _ => MAX_PLY as u16,
+        };
+        for depth in 1..=max_depth {
+            let score = search.negamax(depth, 0, -INFINITE, INFINITE);
+            // An unfinished iteration is only used when none finished, and it found a move
+            if search.aborted && (result.depth > 0 || search.pv[0].is_empty()) {
+                break;
+            }
+            result.score = score;
+            result.pv = search.pv[0].clone();
+            result.best_move = result.pv.first().copied();
+            result.depth = if search.aborted { depth - 1 } else { depth };
+            search.previous = result.pv.clone();
+            if search.aborted || score.abs() > MATE - MAX_PLY as i32 {
+                break;
+            }
+        }
+    }
+    result.nodes = search.nodes;
+    result.time = start.elapsed();
+    result
+}
+
+struct Search<'a> {
+    board: Board,
+    algorithm: Bot,
+    limits: Limits,
+    stop: &'a AtomicBool,
+    start: Instant,
+    nodes: u64,
+    aborted: bool,
+    // The key of every position from the start of the game to the current node
+    keys: Vec<u64>,
+    // pv[ply] is the best line found from the node at `ply`
+    pv: Vec<Vec<BitMove>>,
+    // The principal variation of the last iteration, searched first by the next
+    previous: Vec<BitMove>,
+    table: Option<&'a TranspositionTable>,
+}
+
+impl<'a> Search<'a> {
+    // Counts a node and checks the limits
+    fn visit(&mut self) -> bool {
+        if self.limits.nodes.is_some_and(|nodes| self.nodes >= nodes) {
+            self.aborted = true;
+            return false;
+        }
+        self.nodes += 1;
+        if self.stop.load(Ordering::Relaxed)
+            || (self.nodes.is_multiple_of(TIME_CHECK_INTERVAL)
+                && self
+                    .limits
+                    .movetime
+                    .is_some_and(|movetime| self.start.elapsed() >= movetime))
+        {
+            self.aborted = true;
+        }
+        !self.aborted
+    }
+
+    fn is_draw(&self) -> bool {
+        if self.board.rule_50() >= 100 {
+            return true;
+        }
+        // Only positions since the last capture or pawn move can repeat
+        let key = self.board.zobrist();
+        self.keys
+            .iter()
+            .rev()
+            .skip(1)
+            .take(self.board.rule_50().max(0) as usize)
+            .any(|k| *k == key)
+    }
+
+    fn apply(&mut self, bit_move: BitMove) {
+        self.board.apply_move(bit_move);
+        self.keys.push(self.board.zobrist());
+    }
+
+    fn undo(&mut self) {
+        self.keys.pop();
+        self.board.undo_move();
+    }
+
+    fn negamax(&mut self, depth: u16, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.pv[ply].clear();
+        if !self.visit() {
+            return 0;
+        }
+        if ply > 0 && self.is_draw() {
+            return 0;
+        }
+        let mut moves = self.board.generate_moves().to_vec();
+        if moves.is_empty() {
+            return if self.board.in_check() {
+                -(MATE - ply as i32)
+            } else {
+                0
+            };
+        }
+        if depth == 0 || ply >= MAX_PLY {
+            return match self.algorithm {
+                Bot::Iterative => self.quiescence(ply, alpha, beta),
+                _ => evaluate(&self.board),
+            };
+        }
+
+        let key = self.board.zobrist();
+        let mut hash_move = None;
+        if let Some(entry) = self.table.and_then(|table| table.get(key)) {
+            hash_move = entry.best_move;
+            if ply > 0 && entry.depth >= 0 && entry.depth as u16 >= depth {
+                let score = from_table(entry.score as i32, ply);
+                let cutoff = match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => score >= beta,
+                    Bound::Upper => score <= alpha,
+                };
+                if cutoff {
+                    self.pv[ply] = self.table_line(depth);
+                    return score;
+                }
+            }
+        }
+        match self.algorithm {
+            Bot::Iterative => self.order(&mut moves, ply, hash_move),
+            // The other algorithms keep pleco's move order, save for the table's move
+            _ => {
+                if let Some(idx) = moves.iter().position(|m| Some(*m) == hash_move) {
+                    moves[..=idx].rotate_right(1);
+                }
+            }
+        }
+
+        let prune = self.algorithm != Bot::Minimax;
+        let original_alpha = alpha;
+        let mut best = -INFINITE;
+        for bit_move in moves {
+            self.apply(bit_move);
+            let score = if prune {
+                -self.negamax(depth - 1, ply + 1, -beta, -alpha)
+            } else {
+                -self.negamax(depth - 1, ply + 1, -INFINITE, INFINITE)
+            };
+            self.undo();
+            if self.aborted {
+                return best.max(alpha);
+            }
+            if score > best {
+                best = score;
+                let mut line = vec![bit_move];
+                line.extend_from_slice(&self.pv[ply + 1]);
+                self.pv[ply] = line;
+                if score > alpha {
+                    alpha = score;
+                    if prune && alpha >= beta {
+                        break;
+                    }
+                }
+            }
+        }
+
+        if let Some(table) = self.table {
+            let bound = if best <= original_alpha {
+                Bound::Upper
+            } else if prune && best >= beta {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+            table.put(
+                key,
+                depth.min(i8::MAX as u16) as i8,
+                to_table(best, ply) as i16,
+                bound,
+                // A move that failed low is no better than the others
+                match bound {
+                    Bound::Upper => hash_move,
+                    _ => self.pv[ply].first().copied(),
+                },
+            );
+        }
+        best
+    }
+
+    // The line of best moves stored in the table from the current position, which stands in for
+    // the principal variation of a node cut off by the table
+    fn table_line(&mut self, depth: u16) -> Vec<BitMove> {
+        let table = match self.table {
+            Some(table) => table,
+            None => return vec![],
+        };
+        let mut line = vec![];
+        while line.len() < depth as usize {
+            let bit_move = match table.peek(self.board.zobrist()).and_then(|e| e.best_move) {
+                Some(bit_move) => bit_move,
+                None => break,
+            };
+            if !self.board.pseudo_legal_move(bit_move) || !self.board.legal_move(bit_move) {
+                break;
+            }
+            self.board.apply_move(bit_move);
+            line.push(bit_move);
+        }
+        for _ in 0..line.len() {
+            self.board.undo_move();
+        }
+        line
+    }
+
+    // Searches captures and promotions until the position is quiet, and every evasion in check
+    fn quiescence(&mut self, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        let in_check = self.board.in_check();
+        let mut moves = if in_check {
+            self.board.generate_moves().to_vec()
+        } else {
+            self.board
+                .generate_moves_of_type(GenTypes::Captures)
+                .to_vec()
+        };
+        if in_check && moves.is_empty() {
+            return -(MATE - ply as i32);
+        }
+        // Out of check the side to move may decline every capture
+        let mut best = -INFINITE;
+        if !in_check {
+            best = evaluate(&self.board);
+            if best >= beta || ply >= MAX_PLY {
+                return best;
+            }
+            alpha = alpha.max(best);
+        } else if ply >= MAX_PLY {
+            return evaluate(&self.board);
+        }
+
+        self.order(&mut moves, ply, None);
+        for bit_move in moves {
+            if !self.visit() {
+                return best.max(alpha);
+            }
+            self.board.apply_move(bit_move);
+            let score = -self.quiescence(ply + 1, -beta, -alpha);
+            self.board.undo_move();
+            best = best.max(score);
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+        best
+    }
+
+    // The table's move or else the previous iteration's first, then captures by most valuable
+    // victim and least valuable attacker, then the rest
+    fn order(&self, moves: &mut [BitMove], ply: usize, hash_move: Option<BitMove>) {
+        let previous = hash_move.or_else(|| self.previous.get(ply).copied());
+        let board = &self.board;
+        moves.sort_by_cached_key(|bit_move| {
+            if Some(*bit_move) == previous {
+                return i32::MIN;
+            }
+            let mut key = 0;
+            if board.is_capture(*bit_move) {
+                let victim = board.captured_piece(*bit_move) as i32;
+                let attacker = board.piece_at_sq(bit_move.get_src()).type_of() as i32;
+                key -= 8 * victim - attacker + 64;
+            }
+            if bit_move.is_promo() && bit_move.promo_piece() == PieceType::Q {
+                key -= 128;
+            }
+            key
+        });
+    }
+}
+
+// Mate scores are stored relative to the node rather than the root
+fn to_table(score: i32, ply: usize) -> i32 {
+    if score > MATE - MAX_PLY as i32 {
+        score + ply as i32
+    } else if score < -(MATE - MAX_PLY as i32) {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn from_table(score: i32, ply: usize) -> i32 {
+    if score > MATE - MAX_PLY as i32 {
+        score - ply as i32
+    } else if score < -(MATE - MAX_PLY as i32) {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn run(fen: &str, algorithm: Bot, limits: Limits) -> SearchResult {
+        let board = Board::from_fen(fen).unwrap();
+        search(
+            &board,
+            &[],
+            algorithm,
+            limits,
+            &AtomicBool::new(false),
+            None,
+        )
+    }
+
+    fn depth(depth: u16) -> Limits {
+        Limits {
+            depth: Some(depth),
+            ..Limits::default()
+        }
+    }
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    const BACK_RANK: &str = "6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1";
+
+    #[test]
+    fn stops_at_the_depth_limit() {
+        for algorithm in [Bot::Minimax, Bot::AlphaBeta, Bot::Iterative] {
+            let result = run(START, algorithm, depth(3));
+            assert_eq!(result.depth, 3, "{:?}", algorithm);
+            assert_eq!(result.pv.len(), 3, "{:?}", algorithm);
+            assert_eq!(result.best_move, result.pv.first().copied());
+        }
+        assert_eq!(
+            run(START, Bot::AlphaBeta, Limits::default()).depth,
+            DEFAULT_DEPTH
+        );
+    }
+
+    #[test]
+    fn searches_every_bot_but_jamboree() {
+        for bot in Bot::ALL {
+            assert_eq!(parse_algorithm(bot.key()).is_ok(), bot != Bot::Jamboree);
+        }
+        assert_eq!(parse_algorithm("AlphaBeta"), Ok(Bot::AlphaBeta));
+        assert!(parse_algorithm("jamboree")
+            .unwrap_err()
+            .starts_with("jamboree has no limited search"));
+        assert!(parse_algorithm("mcts").is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "jamboree has no limited search")]
+    fn refuses_to_search_jamboree() {
+        run(START, Bot::Jamboree, depth(1));
+    }
+
+    #[test]
+    fn stops_at_the_node_limit() {
+        let limits = Limits {
+            nodes: Some(2000),
+            ..Limits::default()
+        };
+        let result = run(START, Bot::AlphaBeta, limits);
+        assert_eq!(result.nodes, 2000);
+        assert!(result.depth >= 1 && result.best_move.is_some());
+    }
+
+    #[test]
+    fn stops_at_the_movetime_limit() {
+        let limits = Limits {
+            movetime: Some(Duration::from_millis(100)),
+            ..Limits::default()
+        };
+        let result = run(START, Bot::Minimax, limits);
+        assert!(result.time >= Duration::from_millis(100));
+        assert!(result.time < Duration::from_secs(2), "{:?}", result.time);
+        assert!(result.depth >= 1 && result.best_move.is_some());
+    }
+
+    #[test]
+    fn stops_from_another_thread() {
+        let board = Board::from_fen(START).unwrap();
+        let handle = SearchHandle::new();
+        let start = Instant::now();
+        let result = thread::scope(|scope| {
+            let searcher = scope.spawn(|| {
+                search(
+                    &board,
+                    &[],
+                    Bot::Minimax,
+                    depth(64),
+                    handle.stop_flag(),
+                    None,
+                )
+            });
+            thread::sleep(Duration::from_millis(100));
+            handle.stop();
+            searcher.join().unwrap()
+        });
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(handle.stopped());
+        assert!(result.depth >= 1 && result.depth < 64);
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn reports_mate_scores() {
+        for algorithm in [Bot::Minimax, Bot::AlphaBeta, Bot::Iterative] {
+            let result = run(BACK_RANK, algorithm, depth(3));
+            assert_eq!(result.best_move.unwrap().stringify(), "a1a8");
+            assert_eq!(result.score, MATE - 1, "{:?}", algorithm);
+            assert_eq!(result.mate(), Some(1));
+            // The search ends once a mate is found
+            assert_eq!(result.depth, 1);
+        }
+
+        // Black's only move, Kb8, runs into Rh8
+        let mated = run("k7/8/1K6/8/8/8/8/7R b - - 0 1", Bot::AlphaBeta, depth(3));
+        assert_eq!(mated.score, -(MATE - 2));
+        assert_eq!(mated.mate(), Some(-1));
+
+        let checkmated = run(
+            "R5k1/5ppp/8/8/8/8/5PPP/6K1 b - - 1 1",
+            Bot::AlphaBeta,
+            depth(3),
+        );
+        assert_eq!(checkmated.best_move, None);
+        assert_eq!(checkmated.score, -MATE);
+        assert_eq!(checkmated.mate(), Some(0));
+
+        assert_eq!(run(START, Bot::AlphaBeta, depth(2)).mate(), None);
+    }
+}
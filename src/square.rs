@@ -1,7 +1,11 @@
+use pyo3::basic::CompareOp;
 use pyo3::prelude::*;
 
+use crate::bitboard::BitBoard;
+use crate::error::InvalidSquareError;
+
 #[pyclass]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Square(pleco::SQ);
 
 #[pymethods]
@@ -10,6 +14,90 @@ impl Square {
         self.0.to_string()
     }
 
+    /// Parses a square name such as `"e4"`.
+    #[staticmethod]
+    pub fn from_str(name: &str) -> PyResult<Square> {
+        let invalid = || InvalidSquareError::new_err(format!("invalid square: {:?}", name));
+        match name.as_bytes() {
+            [file @ b'a'..=b'h', rank @ b'1'..=b'8'] => Ok(Self::at(file - b'a', rank - b'1')),
+            [file @ b'A'..=b'H', rank @ b'1'..=b'8'] => Ok(Self::at(file - b'A', rank - b'1')),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Builds a square from its index, a1 = 0 .. h8 = 63.
+    #[staticmethod]
+    pub fn from_index(index: u8) -> PyResult<Square> {
+        if index >= 64 {
+            return Err(InvalidSquareError::new_err(format!(
+                "square index must be in 0..64, got {}",
+                index
+            )));
+        }
+        Ok(Square(pleco::SQ(index)))
+    }
+
+    /// Builds a square from zero-based file and rank, so `(4, 3)` is e4.
+    #[staticmethod]
+    pub fn from_coords(file: u8, rank: u8) -> PyResult<Square> {
+        if file >= 8 || rank >= 8 {
+            return Err(InvalidSquareError::new_err(format!(
+                "file and rank must be in 0..8, got ({}, {})",
+                file, rank
+            )));
+        }
+        Ok(Self::at(file, rank))
+    }
+
+    /// Every square, from a1 to h8.
+    #[staticmethod]
+    pub fn all() -> Vec<Square> {
+        (0..64).map(|idx| Square(pleco::SQ(idx))).collect()
+    }
+
+    pub fn file(&self) -> u8 {
+        self.0.file_idx_of_sq()
+    }
+
+    pub fn rank(&self) -> u8 {
+        self.0.rank_idx_of_sq()
+    }
+
+    pub fn index(&self) -> u8 {
+        self.0 .0
+    }
+
+    fn __int__(&self) -> u8 {
+        self.0 .0
+    }
+
+    fn __hash__(&self) -> u64 {
+        self.0 .0 as u64
+    }
+
+    fn __richcmp__(&self, other: &Self, op: CompareOp, py: Python<'_>) -> PyObject {
+        match op {
+            CompareOp::Eq => (self.0 == other.0).into_py(py),
+            CompareOp::Ne => (self.0 != other.0).into_py(py),
+            _ => py.NotImplemented(),
+        }
+    }
+
+    /// The same square seen from the other side of the board, so a1 becomes a8.
+    pub fn mirror(&self) -> Square {
+        Square(self.0.flip())
+    }
+
+    /// The number of king moves between the two squares.
+    pub fn distance(&self, other: Square) -> u8 {
+        self.0.distance(other.0)
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_bitboard(&self) -> BitBoard {
+        self.0.to_bb().into()
+    }
+
     #[classattr]
     pub const A1: Square = Square(pleco::SQ::A1);
     #[classattr]
@@ -140,6 +228,12 @@ impl Square {
     pub const H8: Square = Square(pleco::SQ::H8);
 }
 
+impl Square {
+    fn at(file: u8, rank: u8) -> Square {
+        Square(pleco::SQ(rank * 8 + file))
+    }
+}
+
 impl From<pleco::SQ> for Square {
     fn from(sq: pleco::SQ) -> Square {
         Square(sq)
@@ -151,3 +245,53 @@ impl From<Square> for pleco::SQ {
         sq.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for sq in Square::all() {
+            let name = sq.__repr__();
+            assert_eq!(Square::from_str(&name).unwrap(), sq);
+            assert_eq!(Square::from_str(&name.to_uppercase()).unwrap(), sq);
+        }
+        assert_eq!(Square::from_str("e4").unwrap().__repr__(), "e4");
+        assert_eq!(Square::all().len(), 64);
+        for name in ["", "e", "e9", "i1", "e0", "e44", "4e", " e4"] {
+            assert!(Square::from_str(name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn converts_indices_and_coordinates() {
+        let e4 = Square::from_coords(4, 3).unwrap();
+        assert_eq!(e4, Square::E4);
+        assert_eq!(
+            (e4.file(), e4.rank(), e4.index(), e4.__int__()),
+            (4, 3, 28, 28)
+        );
+        assert_eq!(Square::from_index(28).unwrap(), e4);
+        assert_eq!(Square::from_index(63).unwrap(), Square::H8);
+        assert!(Square::from_index(64).is_err());
+        assert!(Square::from_coords(8, 0).is_err());
+        assert!(Square::from_coords(0, 8).is_err());
+        assert_eq!(pleco::SQ::from(e4), pleco::SQ::E4);
+        assert_eq!(Square::from(pleco::SQ::E4), e4);
+        assert_eq!(e4.__hash__(), 28);
+    }
+
+    #[test]
+    fn measures_and_mirrors() {
+        assert_eq!(Square::A1.mirror(), Square::A8);
+        assert_eq!(Square::E4.mirror(), Square::E5);
+        for sq in Square::all() {
+            assert_eq!(sq.mirror().mirror(), sq);
+            assert_eq!(sq.distance(sq), 0);
+        }
+        assert_eq!(Square::A1.distance(Square::H8), 7);
+        assert_eq!(Square::E4.distance(Square::F6), 2);
+        assert_eq!(pleco::BitBoard::from(Square::C3.to_bitboard()).0, 1 << 18);
+    }
+}
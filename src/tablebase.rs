@@ -0,0 +1,197 @@
+use pyo3::exceptions::PyIOError;
+use pyo3::prelude::*;
+use shakmaty::fen::Fen;
+use shakmaty::{CastlingMode, Chess};
+use std::io;
+use std::sync::Arc;
+
+use crate::board::Board;
+use crate::error::TablebaseError;
+
+/// Win/draw/loss from the point of view of the side to move, as if the halfmove clock were zero.
+///
+/// Cursed wins and blessed losses are decided by the 50-move rule, which turns them into draws.
+#[pyclass]
+#[repr(i8)]
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Wdl {
+    Loss = -2,
+    BlessedLoss = -1,
+    Draw = 0,
+    CursedWin = 1,
+    Win = 2,
+}
+
+#[pymethods]
+impl Wdl {
+    fn __repr__(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    /// -1 for losses, 0 for draws (including cursed wins and blessed losses) and 1 for wins.
+    pub fn signum(&self) -> i8 {
+        match self {
+            Wdl::Loss => -1,
+            Wdl::Win => 1,
+            _ => 0,
+        }
+    }
+}
+
+impl From<shakmaty_syzygy::Wdl> for Wdl {
+    fn from(wdl: shakmaty_syzygy::Wdl) -> Wdl {
+        match wdl {
+            shakmaty_syzygy::Wdl::Loss => Wdl::Loss,
+            shakmaty_syzygy::Wdl::BlessedLoss => Wdl::BlessedLoss,
+            shakmaty_syzygy::Wdl::Draw => Wdl::Draw,
+            shakmaty_syzygy::Wdl::CursedWin => Wdl::CursedWin,
+            shakmaty_syzygy::Wdl::Win => Wdl::Win,
+        }
+    }
+}
+
+/// Syzygy endgame tablebases loaded from `.rtbw`/`.rtbz` files on local disk.
+///
+/// Tables are opened lazily on first probe; cloning shares the open tables.
+#[pyclass]
+#[derive(Clone)]
+pub struct Tablebase {
+    inner: Arc<shakmaty_syzygy::Tablebase<Chess>>,
+}
+
+#[pymethods]
+impl Tablebase {
+    #[new]
+    pub fn new(path: &str) -> PyResult<Self> {
+        Tablebase::open(path).map_err(|err| PyIOError::new_err(err.to_string()))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Tablebase(max_pieces={})", self.max_pieces())
+    }
+
+    /// The largest number of pieces, kings included, covered by the loaded tables.
+    pub fn max_pieces(&self) -> usize {
+        self.inner.max_pieces()
+    }
+
+    pub fn probe_wdl(&self, board: &Board) -> PyResult<Wdl> {
+        self.wdl(board.inner()).map_err(TablebaseError::new_err)
+    }
+
+    pub fn probe_dtz(&self, board: &Board) -> PyResult<i32> {
+        self.dtz(board.inner()).map_err(TablebaseError::new_err)
+    }
+}
+
+impl Tablebase {
+    /// Opens the tables in the directory `path`.
+    pub fn open(path: &str) -> io::Result<Self> {
+        let mut tablebase = shakmaty_syzygy::Tablebase::new();
+        tablebase
+            .add_directory(path)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path, err)))?;
+        Ok(Self {
+            inner: Arc::new(tablebase),
+        })
+    }
+
+    /// Probes the WDL tables, assuming the position was reached by a capture or pawn move.
+    ///
+    /// Only `.rtbw` files are needed; the halfmove clock is ignored.
+    pub fn wdl(&self, board: &pleco::Board) -> Result<Wdl, String> {
+        let pos = to_position(board)?;
+        self.inner
+            .probe_wdl_after_zeroing(&pos)
+            .map(|wdl| wdl.into())
+            .map_err(|err| err.to_string())
+    }
+
+    /// Probes the DTZ tables for the distance to the next capture or pawn move, signed from the
+    /// point of view of the side to move. Needs the `.rtbz` files as well.
+    pub fn dtz(&self, board: &pleco::Board) -> Result<i32, String> {
+        let pos = to_position(board)?;
+        self.inner
+            .probe_dtz(&pos)
+            .map(|dtz| dtz.ignore_rounding().0)
+            .map_err(|err| err.to_string())
+    }
+}
+
+fn to_position(board: &pleco::Board) -> Result<Chess, String> {
+    let fen = board.fen();
+    let setup = fen.parse::<Fen>().map_err(|err| err.to_string())?;
+    match setup.into_position(CastlingMode::Standard) {
+        Ok(pos) => Ok(pos),
+        Err(err) => err
+            .ignore_invalid_ep_square()
+            .map_err(|err| format!("{}: {}", fen, err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // KPvK, KQvK, KRvK and the single-valued KBvK and KNvK
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/syzygy");
+
+    fn board(fen: &str) -> pleco::Board {
+        pleco::Board::from_fen(fen).unwrap()
+    }
+
+    #[test]
+    fn missing_tables_are_errors() {
+        let empty = std::env::temp_dir().join("gym_chess_pleco_no_tables");
+        std::fs::create_dir_all(&empty).unwrap();
+        let tablebase = Tablebase::open(empty.to_str().unwrap()).unwrap();
+        assert_eq!(tablebase.max_pieces(), 0);
+        let krk = board("4k3/8/8/8/8/8/8/R3K3 b - - 0 1");
+        assert!(tablebase.wdl(&krk).is_err());
+        assert!(tablebase.dtz(&krk).is_err());
+
+        let err = Tablebase::open("/nonexistent/syzygy").err().unwrap();
+        assert!(err.to_string().starts_with("/nonexistent/syzygy: "));
+    }
+
+    #[test]
+    fn converts_positions_with_an_en_passant_square() {
+        let mut board = pleco::Board::start_pos();
+        board.apply_uci_move("e2e4");
+        assert!(to_position(&board).is_ok());
+    }
+
+    #[test]
+    fn probes_fixture_tables() {
+        let tablebase = Tablebase::open(FIXTURES).unwrap();
+        assert_eq!(tablebase.max_pieces(), 3);
+
+        // KPvK: a pawn about to promote wins, a rook pawn against a king in the corner draws
+        let promotes = board("8/4P3/8/8/8/8/k7/4K3 w - - 0 1");
+        assert_eq!(tablebase.wdl(&promotes), Ok(Wdl::Win));
+        assert_eq!(tablebase.dtz(&promotes), Ok(1));
+        let corner = board("k7/8/8/8/8/8/P7/K7 w - - 0 1");
+        assert_eq!(tablebase.wdl(&corner), Ok(Wdl::Draw));
+        assert_eq!(tablebase.dtz(&corner), Ok(0));
+
+        // KRvK from the defending side, and once mated
+        let krk = board("4k3/8/8/8/8/8/8/R3K3 b - - 0 1");
+        assert_eq!(tablebase.wdl(&krk), Ok(Wdl::Loss));
+        assert!(tablebase.dtz(&krk).unwrap() < 0);
+        let mated = board("R3k3/8/4K3/8/8/8/8/8 b - - 0 1");
+        assert_eq!(tablebase.wdl(&mated), Ok(Wdl::Loss));
+        assert_eq!(tablebase.dtz(&mated), Ok(-1));
+
+        // KQvK with a mate in one, and with the king taking an undefended queen
+        let mates = board("k7/8/1K6/8/8/8/7Q/8 w - - 0 1");
+        assert_eq!(tablebase.wdl(&mates), Ok(Wdl::Win));
+        assert_eq!(tablebase.dtz(&mates), Ok(1));
+        let trade = board("k7/1Q6/8/8/8/8/8/7K b - - 0 1");
+        assert_eq!(tablebase.wdl(&trade), Ok(Wdl::Draw));
+        assert_eq!(tablebase.dtz(&trade), Ok(0));
+
+        // A lone bishop cannot win
+        let kbk = board("4k3/8/8/8/8/8/8/B3K3 b - - 0 1");
+        assert_eq!(tablebase.wdl(&kbk), Ok(Wdl::Draw));
+    }
+}
@@ -0,0 +1,229 @@
+//! Ratings from game results, after the model used by BayesElo.
+//!
+//! The expected result of a game depends on the rating difference, an advantage for white and a
+//! draw width: with `f(x) = 1 / (1 + 10^(-x / 400))` and `x` white's rating minus black's, white
+//! wins with probability `f(x + ADVANTAGE - DRAW_ELO)`, black with `f(-x - ADVANTAGE - DRAW_ELO)`,
+//! and the rest is a draw. Every player also gets `PRIOR_DRAWS` virtual draws against an average
+//! opponent, which keeps ratings finite for perfect scores.
+
+use crate::dataset::Outcome;
+
+// BayesElo's defaults
+const ADVANTAGE: f64 = 32.8;
+const DRAW_ELO: f64 = 97.3;
+const PRIOR_DRAWS: f64 = 2.0;
+
+// Half-width of a 95% confidence interval in standard deviations
+const Z_95: f64 = 1.96;
+const MAX_ITERATIONS: usize = 100;
+
+/// A rating relative to the average of the field, with the half-width of its 95% interval.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rating {
+    pub elo: f64,
+    pub error: f64,
+}
+
+/// Rates `players` players from games given as `(white, black, outcome)`.
+///
+/// Ratings are found by Newton's method on the log-likelihood; errors come from its curvature.
+pub fn ratings(players: usize, games: &[(usize, usize, Outcome)]) -> Vec<Rating> {
+    let (elo, covariance) = maximize(players, games);
+    let mean = elo.iter().sum::<f64>() / players.max(1) as f64;
+    elo.iter()
+        .enumerate()
+        .map(|(i, rating)| Rating {
+            elo: rating - mean,
+            error: Z_95 * covariance[i][i].max(0.0).sqrt(),
+        })
+        .collect()
+}
+
+// The ratings of highest likelihood and the inverse of the negated Hessian there
+fn maximize(players: usize, games: &[(usize, usize, Outcome)]) -> (Vec<f64>, Vec<Vec<f64>>) {
+    let mut elo = vec![0.0; players];
+    let mut covariance = vec![vec![0.0; players]; players];
+    for _ in 0..MAX_ITERATIONS {
+        let (gradient, hessian) = derivatives(&elo, games);
+        // The prior makes the log-likelihood strictly concave, so the Hessian is invertible
+        covariance = match invert(negate(hessian)) {
+            Some(inverse) => inverse,
+            None => break,
+        };
+        let mut largest_step: f64 = 0.0;
+        for (i, rating) in elo.iter_mut().enumerate() {
+            let step = (0..players)
+                .map(|j| covariance[i][j] * gradient[j])
+                .sum::<f64>();
+            *rating += step;
+            largest_step = largest_step.max(step.abs());
+        }
+        if largest_step < 1e-6 {
+            break;
+        }
+    }
+    (elo, covariance)
+}
+
+fn expected(x: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-x / 400.0))
+}
+
+// The log-likelihood of one game scored `result` (1, 0.5 or 0 for the first player) given the
+// rating difference `x` and a colour advantage
+fn log_likelihood(x: f64, advantage: f64, result: f64) -> f64 {
+    let win = expected(x + advantage - DRAW_ELO);
+    let loss = expected(-x - advantage - DRAW_ELO);
+    let p = if result == 1.0 {
+        win
+    } else if result == 0.0 {
+        loss
+    } else {
+        1.0 - win - loss
+    };
+    p.max(f64::MIN_POSITIVE).ln()
+}
+
+// First and second derivatives of `log_likelihood` in `x`, by central differences
+fn slopes(x: f64, advantage: f64, result: f64) -> (f64, f64) {
+    let h = 1.0;
+    let (below, at, above) = (
+        log_likelihood(x - h, advantage, result),
+        log_likelihood(x, advantage, result),
+        log_likelihood(x + h, advantage, result),
+    );
+    (
+        (above - below) / (2.0 * h),
+        (above - 2.0 * at + below) / (h * h),
+    )
+}
+
+fn derivatives(elo: &[f64], games: &[(usize, usize, Outcome)]) -> (Vec<f64>, Vec<Vec<f64>>) {
+    let players = elo.len();
+    let mut gradient = vec![0.0; players];
+    let mut hessian = vec![vec![0.0; players]; players];
+
+    for (white, black, outcome) in games {
+        let result = match outcome {
+            Outcome::WhiteWins => 1.0,
+            Outcome::BlackWins => 0.0,
+            Outcome::Draw => 0.5,
+        };
+        let (g, h) = slopes(elo[*white] - elo[*black], ADVANTAGE, result);
+        gradient[*white] += g;
+        gradient[*black] -= g;
+        hessian[*white][*white] += h;
+        hessian[*black][*black] += h;
+        hessian[*white][*black] -= h;
+        hessian[*black][*white] -= h;
+    }
+    for (i, rating) in elo.iter().enumerate() {
+        let (g, h) = slopes(*rating, 0.0, 0.5);
+        gradient[i] += PRIOR_DRAWS * g;
+        hessian[i][i] += PRIOR_DRAWS * h;
+    }
+    (gradient, hessian)
+}
+
+fn negate(mut matrix: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+    for row in matrix.iter_mut() {
+        for value in row.iter_mut() {
+            *value = -*value;
+        }
+    }
+    matrix
+}
+
+// Gauss-Jordan elimination with partial pivoting
+fn invert(mut matrix: Vec<Vec<f64>>) -> Option<Vec<Vec<f64>>> {
+    let n = matrix.len();
+    let mut inverse = (0..n)
+        .map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+        .collect::<Vec<Vec<f64>>>();
+    for col in 0..n {
+        let pivot =
+            (col..n).max_by(|a, b| matrix[*a][col].abs().total_cmp(&matrix[*b][col].abs()))?;
+        if matrix[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        matrix.swap(col, pivot);
+        inverse.swap(col, pivot);
+        let scale = matrix[col][col];
+        for j in 0..n {
+            matrix[col][j] /= scale;
+            inverse[col][j] /= scale;
+        }
+        for row in 0..n {
+            if row != col {
+                let factor = matrix[row][col];
+                for j in 0..n {
+                    matrix[row][j] -= factor * matrix[col][j];
+                    inverse[row][j] -= factor * inverse[col][j];
+                }
+            }
+        }
+    }
+    Some(inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Outcome::{BlackWins, Draw, WhiteWins};
+
+    fn repeat(games: &[(usize, usize, Outcome)], times: usize) -> Vec<(usize, usize, Outcome)> {
+        games
+            .iter()
+            .copied()
+            .cycle()
+            .take(games.len() * times)
+            .collect()
+    }
+
+    #[test]
+    fn finds_the_maximum_likelihood() {
+        let games = repeat(
+            &[
+                (0, 1, WhiteWins),
+                (1, 0, Draw),
+                (0, 2, WhiteWins),
+                (2, 0, BlackWins),
+                (1, 2, Draw),
+                (2, 1, WhiteWins),
+            ],
+            5,
+        );
+        let (elo, covariance) = maximize(3, &games);
+        let (gradient, hessian) = derivatives(&elo, &games);
+        assert!(gradient.iter().all(|g| g.abs() < 1e-6), "{:?}", gradient);
+        // The covariance is the inverse of the negated Hessian
+        for (i, row) in hessian.iter().enumerate() {
+            for j in 0..3 {
+                let product = row.iter().zip(&covariance).map(|(h, c)| -h * c[j]);
+                let product = product.sum::<f64>();
+                assert!((product - f64::from(i == j)).abs() < 1e-9);
+            }
+        }
+
+        let ratings = ratings(3, &games);
+        assert!(ratings.iter().map(|r| r.elo).sum::<f64>().abs() < 1e-9);
+        assert!(ratings[0].elo > ratings[2].elo && ratings[2].elo > ratings[1].elo);
+    }
+
+    #[test]
+    fn colour_advantage_cancels_out() {
+        // Both players win every game they play as white
+        let ratings = ratings(2, &repeat(&[(0, 1, WhiteWins), (1, 0, WhiteWins)], 10));
+        assert!(ratings[0].elo.abs() < 1e-9 && ratings[1].elo.abs() < 1e-9);
+        assert!((ratings[0].error - ratings[1].error).abs() < 1e-9);
+    }
+
+    #[test]
+    fn perfect_scores_stay_finite() {
+        let few = ratings(2, &repeat(&[(0, 1, WhiteWins), (1, 0, BlackWins)], 2));
+        let many = ratings(2, &repeat(&[(0, 1, WhiteWins), (1, 0, BlackWins)], 20));
+        assert!(few[0].elo.is_finite() && many[0].elo.is_finite());
+        assert!((few[0].elo + few[1].elo).abs() < 1e-9);
+        assert!(many[0].elo > few[0].elo && few[0].elo > 0.0);
+    }
+}
@@ -0,0 +1,483 @@
+use pyo3::exceptions::PyIOError;
+use pyo3::prelude::*;
+use std::io;
+use std::path::PathBuf;
+
+use crate::dataset::Outcome;
+use crate::engine::{Bot, EngineSpec};
+use crate::error::InvalidArgumentError;
+use crate::game::{load_openings, play_game, today, Adjudication, Opening};
+use crate::pgn::{PgnGame, PgnWriter};
+use crate::tablebase::Tablebase;
+
+pub mod elo;
+pub mod sprt;
+
+use elo::Rating;
+
+/// A named engine taking part in a tournament.
+#[pyclass]
+#[derive(Clone)]
+pub struct Participant {
+    #[pyo3(get)]
+    pub name: String,
+    pub spec: EngineSpec,
+}
+
+#[pymethods]
+impl Participant {
+    fn __repr__(&self) -> String {
+        format!("Participant({})", self.name)
+    }
+
+    /// One of pleco's searchers: `random`, `minimax`, `alphabeta`, `jamboree` or `iterative`.
+    #[staticmethod]
+    #[pyo3(signature = (bot="alphabeta", depth=4, name=None))]
+    pub fn searcher(bot: &str, depth: u16, name: Option<String>) -> PyResult<Participant> {
+        let bot = bot.parse::<Bot>().map_err(InvalidArgumentError::new_err)?;
+        Ok(Participant::new(EngineSpec::Bot { bot, depth }, name))
+    }
+
+    /// A UCI engine started from `path`, given `movetime` milliseconds per move.
+    #[staticmethod]
+    #[pyo3(signature = (path, movetime=100, name=None))]
+    pub fn uci(path: PathBuf, movetime: u64, name: Option<String>) -> Participant {
+        Participant::new(EngineSpec::Uci { path, movetime }, name)
+    }
+
+    /// A Python callable taking a `Board` and returning a `BitMove` or a move in UCI notation.
+    #[staticmethod]
+    pub fn policy(name: String, policy: PyObject) -> Participant {
+        Participant::new(
+            EngineSpec::Policy {
+                name: name.clone(),
+                policy,
+            },
+            Some(name),
+        )
+    }
+}
+
+impl Participant {
+    pub fn new(spec: EngineSpec, name: Option<String>) -> Participant {
+        Participant {
+            name: name.unwrap_or_else(|| spec.to_string()),
+            spec,
+        }
+    }
+}
+
+// Names tell participants apart in the crosstable and the PGN, so repeats get a suffix
+pub(crate) fn unique_names(participants: &mut [Participant]) {
+    for idx in 1..participants.len() {
+        let base = participants[idx].name.clone();
+        let mut suffix = 2;
+        while participants[..idx]
+            .iter()
+            .any(|p| p.name == participants[idx].name)
+        {
+            participants[idx].name = format!("{} #{}", base, suffix);
+            suffix += 1;
+        }
+    }
+}
+
+/// Loads the openings, the start position if there are none, and the adjudication rules shared
+/// by tournaments and SPRT runs. Games are decided by the tablebase once they are down to
+/// `adjudication_pieces` pieces, which defaults to the largest tables loaded.
+pub fn setup(
+    openings: Option<&str>,
+    book_plies: usize,
+    max_plies: usize,
+    tablebase: Option<Tablebase>,
+    adjudication_pieces: Option<usize>,
+) -> io::Result<(Vec<Opening>, Adjudication)> {
+    let openings = match openings {
+        Some(path) => load_openings(path, book_plies)?,
+        None => vec![Opening::default()],
+    };
+    let tablebase_pieces = adjudication_pieces
+        .or_else(|| tablebase.as_ref().map(|tb| tb.max_pieces()))
+        .unwrap_or(0);
+    let adjudication = Adjudication {
+        max_plies,
+        tablebase,
+        tablebase_pieces,
+    };
+    Ok((openings, adjudication))
+}
+
+/// Plays one game between freshly started engines, returning it as PGN along with the result
+/// and the reason for it. An engine that does not start loses the game.
+pub fn play_one(
+    event: &str,
+    round: String,
+    white: &Participant,
+    black: &Participant,
+    opening: &Opening,
+    adjudication: &Adjudication,
+) -> (PgnGame, Outcome, String) {
+    let (state, outcome, reason) = match (white.spec.spawn(), black.spec.spawn()) {
+        (Ok(mut white_engine), Ok(mut black_engine)) => {
+            let game = play_game(
+                white_engine.as_mut(),
+                black_engine.as_mut(),
+                opening,
+                adjudication,
+            );
+            (Some(game.state), game.outcome, game.reason)
+        }
+        (Err(err), _) => (None, Outcome::BlackWins, format!("forfeit: {}", err)),
+        (_, Err(err)) => (None, Outcome::WhiteWins, format!("forfeit: {}", err)),
+    };
+
+    let tags = vec![
+        ("Event".to_string(), event.to_string()),
+        ("Site".to_string(), "gym-chess-pleco".to_string()),
+        ("Date".to_string(), today()),
+        ("Round".to_string(), round),
+        ("White".to_string(), white.name.clone()),
+        ("Black".to_string(), black.name.clone()),
+        ("Result".to_string(), outcome.to_pgn().to_string()),
+        ("Termination".to_string(), reason.clone()),
+    ];
+    let pgn = match state {
+        Some(state) => state.to_pgn(tags),
+        None => PgnGame {
+            tags,
+            moves: vec![],
+        },
+    };
+    (pgn, outcome, reason)
+}
+
+/// One game of a tournament, by participant index.
+#[derive(Clone, Debug)]
+pub struct GameResult {
+    pub round: usize,
+    pub white: usize,
+    pub black: usize,
+    pub outcome: Outcome,
+    pub reason: String,
+}
+
+/// Plays every pairing from every opening twice, colours reversed, then rates the field.
+///
+/// In a round robin everybody meets everybody; in a gauntlet the first participant meets each
+/// of the others.
+#[pyclass]
+pub struct Tournament {
+    participants: Vec<Participant>,
+    openings: Vec<Opening>,
+    rounds: usize,
+    gauntlet: bool,
+    adjudication: Adjudication,
+}
+
+#[pymethods]
+impl Tournament {
+    #[new]
+    #[pyo3(signature = (
+        participants,
+        openings=None,
+        book_plies=8,
+        rounds=1,
+        gauntlet=false,
+        max_plies=400,
+        tablebase=None,
+        adjudication_pieces=None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn py_new(
+        participants: Vec<Participant>,
+        openings: Option<&str>,
+        book_plies: usize,
+        rounds: usize,
+        gauntlet: bool,
+        max_plies: usize,
+        tablebase: Option<Tablebase>,
+        adjudication_pieces: Option<usize>,
+    ) -> PyResult<Self> {
+        let (openings, adjudication) = setup(
+            openings,
+            book_plies,
+            max_plies,
+            tablebase,
+            adjudication_pieces,
+        )
+        .map_err(|err| PyIOError::new_err(err.to_string()))?;
+        Tournament::new(participants, openings, rounds, gauntlet, adjudication)
+            .map_err(InvalidArgumentError::new_err)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Tournament({} participants, {} games)",
+            self.participants.len(),
+            self.schedule().len()
+        )
+    }
+
+    /// Plays every game, appending each to the file `pgn` as it finishes if given.
+    #[pyo3(name = "run", signature = (pgn=None))]
+    pub fn py_run(&self, py: Python, pgn: Option<&str>) -> PyResult<TournamentResult> {
+        let io_error = |err: io::Error| PyIOError::new_err(err.to_string());
+        let mut writer = pgn.map(PgnWriter::create).transpose().map_err(io_error)?;
+        py.allow_threads(|| {
+            self.run(|_, game| match writer.as_mut() {
+                Some(writer) => writer.write(game),
+                None => Ok(()),
+            })
+        })
+        .map_err(io_error)
+    }
+}
+
+impl Tournament {
+    pub fn new(
+        mut participants: Vec<Participant>,
+        openings: Vec<Opening>,
+        rounds: usize,
+        gauntlet: bool,
+        adjudication: Adjudication,
+    ) -> Result<Self, String> {
+        if participants.len() < 2 {
+            return Err("a tournament needs at least two participants".to_string());
+        }
+        unique_names(&mut participants);
+        Ok(Tournament {
+            participants,
+            openings,
+            rounds,
+            gauntlet,
+            adjudication,
+        })
+    }
+
+    /// Participant names, made unique.
+    pub fn names(&self) -> Vec<String> {
+        self.participants.iter().map(|p| p.name.clone()).collect()
+    }
+
+    /// Every game to play as `(round, white, black, opening)`.
+    pub fn schedule(&self) -> Vec<(usize, usize, usize, &Opening)> {
+        let players = self.participants.len();
+        let pairings = if self.gauntlet {
+            (1..players).map(|other| (0, other)).collect::<Vec<_>>()
+        } else {
+            (0..players)
+                .flat_map(|a| (a + 1..players).map(move |b| (a, b)))
+                .collect()
+        };
+        let mut schedule = vec![];
+        for round in 1..=self.rounds {
+            for &(a, b) in &pairings {
+                for opening in &self.openings {
+                    schedule.push((round, a, b, opening));
+                    schedule.push((round, b, a, opening));
+                }
+            }
+        }
+        schedule
+    }
+
+    /// Plays the schedule in order, calling `progress` with every game once it is over. Stops
+    /// at the first error `progress` returns.
+    pub fn run<F>(&self, mut progress: F) -> io::Result<TournamentResult>
+    where
+        F: FnMut(&GameResult, &PgnGame) -> io::Result<()>,
+    {
+        let names = self.names();
+        let mut results = vec![];
+        let mut pgns = vec![];
+        for (idx, (round, white, black, opening)) in self.schedule().into_iter().enumerate() {
+            let (pgn, outcome, reason) = play_one(
+                "Tournament",
+                format!("{}.{}", round, idx + 1),
+                &self.participants[white],
+                &self.participants[black],
+                opening,
+                &self.adjudication,
+            );
+            let result = GameResult {
+                round,
+                white,
+                black,
+                outcome,
+                reason,
+            };
+            progress(&result, &pgn)?;
+            results.push(result);
+            pgns.push(pgn);
+        }
+        Ok(TournamentResult::new(names, results, pgns))
+    }
+}
+
+/// The games of a finished tournament, the ratings they imply and the crosstable.
+#[pyclass]
+pub struct TournamentResult {
+    names: Vec<String>,
+    games: Vec<GameResult>,
+    pgns: Vec<PgnGame>,
+    ratings: Vec<Rating>,
+}
+
+#[pymethods]
+impl TournamentResult {
+    fn __repr__(&self) -> String {
+        format!("TournamentResult({} games)", self.games.len())
+    }
+
+    /// `(name, elo, error)` for every participant, best first. Ratings are relative to the
+    /// field's average and `error` is the half-width of the 95% interval.
+    pub fn ratings(&self) -> Vec<(String, f64, f64)> {
+        self.ranking()
+            .into_iter()
+            .map(|idx| {
+                let rating = self.ratings[idx];
+                (self.names[idx].clone(), rating.elo, rating.error)
+            })
+            .collect()
+    }
+
+    /// Points scored by every participant, in the order they were given.
+    pub fn scores(&self) -> Vec<(String, f64)> {
+        (0..self.names.len())
+            .map(|idx| (self.names[idx].clone(), self.score(idx, None)))
+            .collect()
+    }
+
+    /// `(white, black, result, reason)` for every game, in the order played.
+    pub fn games(&self) -> Vec<(String, String, String, String)> {
+        self.games
+            .iter()
+            .map(|game| {
+                (
+                    self.names[game.white].clone(),
+                    self.names[game.black].clone(),
+                    game.outcome.to_pgn().to_string(),
+                    game.reason.clone(),
+                )
+            })
+            .collect()
+    }
+
+    /// Every game as PGN.
+    pub fn pgn(&self) -> String {
+        self.pgns
+            .iter()
+            .map(|game| game.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// A table of ratings, scores and results between every pair of participants, best first.
+    pub fn crosstable(&self) -> String {
+        let ranking = self.ranking();
+        let width = self
+            .names
+            .iter()
+            .map(|name| name.len())
+            .max()
+            .unwrap_or(4)
+            .max(4);
+        let mut table = format!(
+            "{:>3}  {:<width$}  {:>6}  {:>5}  {:>5}  {:>6}",
+            "#",
+            "Name",
+            "Elo",
+            "+/-",
+            "Games",
+            "Score",
+            width = width
+        );
+        for rank in 1..=ranking.len() {
+            table.push_str(&format!("  {:>7}", rank));
+        }
+        table.push('\n');
+
+        for (rank, &idx) in ranking.iter().enumerate() {
+            let rating = self.ratings[idx];
+            let games = self
+                .games
+                .iter()
+                .filter(|game| game.white == idx || game.black == idx)
+                .count();
+            table.push_str(&format!(
+                "{:>3}  {:<width$}  {:>+6.0}  {:>5.0}  {:>5}  {:>6.1}",
+                rank + 1,
+                self.names[idx],
+                rating.elo,
+                rating.error,
+                games,
+                self.score(idx, None),
+                width = width
+            ));
+            for &opponent in &ranking {
+                let played = self
+                    .games
+                    .iter()
+                    .filter(|game| {
+                        (game.white, game.black) == (idx, opponent)
+                            || (game.white, game.black) == (opponent, idx)
+                    })
+                    .count();
+                let cell = if opponent == idx {
+                    "---".to_string()
+                } else if played == 0 {
+                    String::new()
+                } else {
+                    format!("{:.1}/{}", self.score(idx, Some(opponent)), played)
+                };
+                table.push_str(&format!("  {:>7}", cell));
+            }
+            table.push('\n');
+        }
+        table
+    }
+}
+
+impl TournamentResult {
+    pub fn new(names: Vec<String>, games: Vec<GameResult>, pgns: Vec<PgnGame>) -> Self {
+        let rated = games
+            .iter()
+            .map(|game| (game.white, game.black, game.outcome))
+            .collect::<Vec<_>>();
+        let ratings = elo::ratings(names.len(), &rated);
+        TournamentResult {
+            names,
+            games,
+            pgns,
+            ratings,
+        }
+    }
+
+    // Participant indices from the highest rating down
+    fn ranking(&self) -> Vec<usize> {
+        let mut ranking = (0..self.names.len()).collect::<Vec<_>>();
+        ranking.sort_by(|a, b| self.ratings[*b].elo.total_cmp(&self.ratings[*a].elo));
+        ranking
+    }
+
+    // Points scored by `idx`, against `opponent` only if given
+    fn score(&self, idx: usize, opponent: Option<usize>) -> f64 {
+        self.games
+            .iter()
+            .filter_map(|game| {
+                let (colour, other) = if game.white == idx {
+                    (pleco::Player::White, game.black)
+                } else if game.black == idx {
+                    (pleco::Player::Black, game.white)
+                } else {
+                    return None;
+                };
+                if opponent.is_some_and(|opponent| opponent != other) {
+                    return None;
+                }
+                // `value_for` gives 1, 0 or -1
+                Some((game.outcome.value_for(colour) as f64 + 1.0) / 2.0)
+            })
+            .sum()
+    }
+}
@@ -0,0 +1,554 @@
+//! Sequential probability ratio tests between two engines.
+//!
+//! Games are played in pairs from the same opening with colours reversed, and the test works on
+//! the candidate's score per pair, which takes one of five values (the pentanomial model). The
+//! log-likelihood ratio between the hypotheses `elo = elo1` and `elo = elo0` uses the normal
+//! approximation of the generalized SPRT with logistic Elo, as in fishtest.
+
+use pyo3::exceptions::PyIOError;
+use pyo3::prelude::*;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::dataset::Outcome;
+use crate::error::InvalidArgumentError;
+use crate::game::{Adjudication, Opening};
+use crate::pgn::{PgnGame, PgnWriter};
+use crate::tablebase::Tablebase;
+use crate::tournament::{play_one, setup, unique_names, Participant};
+
+// Stands in for empty pentanomial bins so the variance stays positive
+const EMPTY_BIN: f64 = 1e-3;
+const Z_95: f64 = 1.96;
+
+/// Whether the test has stopped, and on which side.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// The candidate is at least `elo1` stronger: H1 is accepted.
+    Accept,
+    /// The candidate is at most `elo0` stronger: H1 is rejected.
+    Reject,
+    /// Neither bound was reached before the game limit.
+    Inconclusive,
+}
+
+impl Decision {
+    pub fn name(self) -> &'static str {
+        match self {
+            Decision::Accept => "accept",
+            Decision::Reject => "reject",
+            Decision::Inconclusive => "inconclusive",
+        }
+    }
+}
+
+/// The state of a test after some number of game pairs.
+#[derive(Clone, Debug, Default)]
+pub struct SprtStatus {
+    /// Pairs scoring 0, 1/2, 1, 3/2 and 2 points for the candidate.
+    pub pentanomial: [u32; 5],
+    /// Wins, draws and losses of the candidate.
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub llr: f64,
+}
+
+impl SprtStatus {
+    pub fn pairs(&self) -> u32 {
+        self.pentanomial.iter().sum()
+    }
+
+    // Mean and variance of the pair score, scaled to [0, 1]
+    fn moments(&self) -> Option<(f64, f64)> {
+        if self.pairs() == 0 {
+            return None;
+        }
+        let counts = self
+            .pentanomial
+            .map(|n| if n == 0 { EMPTY_BIN } else { n as f64 });
+        let total = counts.iter().sum::<f64>();
+        let score = |idx: usize| idx as f64 / 4.0;
+        let mean = (0..5).map(|i| counts[i] * score(i)).sum::<f64>() / total;
+        let variance = (0..5)
+            .map(|i| counts[i] * (score(i) - mean).powi(2))
+            .sum::<f64>()
+            / total;
+        Some((mean, variance))
+    }
+
+    fn update_llr(&mut self, elo0: f64, elo1: f64) {
+        let (mean, variance) = match self.moments() {
+            Some(moments) => moments,
+            None => return,
+        };
+        let (s0, s1) = (expected(elo0), expected(elo1));
+        self.llr = self.pairs() as f64 * (s1 - s0) * (2.0 * mean - s0 - s1) / (2.0 * variance);
+    }
+
+    /// The candidate's Elo difference to the baseline and the half-width of its 95% interval.
+    pub fn elo(&self) -> (f64, f64) {
+        let (mean, variance) = match self.moments() {
+            Some(moments) => moments,
+            None => return (0.0, f64::INFINITY),
+        };
+        let margin = Z_95 * (variance / self.pairs() as f64).sqrt();
+        let (low, high) = (elo_of(mean - margin), elo_of(mean + margin));
+        (elo_of(mean), (high - low) / 2.0)
+    }
+}
+
+fn expected(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+fn elo_of(score: f64) -> f64 {
+    let score = score.clamp(1e-6, 1.0 - 1e-6);
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// Plays a candidate against a baseline in pairs of games until the log-likelihood ratio
+/// crosses one of its bounds.
+///
+/// H0 is that the candidate is `elo0` stronger and H1 that it is `elo1` stronger; `alpha` and
+/// `beta` are the false positive and false negative rates. Pairs are played on `threads`
+/// threads, cycling through the openings.
+#[pyclass]
+pub struct Sprt {
+    candidate: Participant,
+    baseline: Participant,
+    elo0: f64,
+    elo1: f64,
+    alpha: f64,
+    beta: f64,
+    openings: Vec<Opening>,
+    threads: usize,
+    max_pairs: Option<usize>,
+    adjudication: Adjudication,
+}
+
+#[pymethods]
+impl Sprt {
+    #[new]
+    #[pyo3(signature = (
+        candidate,
+        baseline,
+        elo0=0.0,
+        elo1=5.0,
+        alpha=0.05,
+        beta=0.05,
+        openings=None,
+        book_plies=8,
+        threads=1,
+        max_pairs=None,
+        max_plies=400,
+        tablebase=None,
+        adjudication_pieces=None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn py_new(
+        candidate: Participant,
+        baseline: Participant,
+        elo0: f64,
+        elo1: f64,
+        alpha: f64,
+        beta: f64,
+        openings: Option<&str>,
+        book_plies: usize,
+        threads: usize,
+        max_pairs: Option<usize>,
+        max_plies: usize,
+        tablebase: Option<Tablebase>,
+        adjudication_pieces: Option<usize>,
+    ) -> PyResult<Self> {
+        let (openings, adjudication) = setup(
+            openings,
+            book_plies,
+            max_plies,
+            tablebase,
+            adjudication_pieces,
+        )
+        .map_err(|err| PyIOError::new_err(err.to_string()))?;
+        Sprt::new(
+            candidate,
+            baseline,
+            (elo0, elo1),
+            (alpha, beta),
+            openings,
+            threads,
+            max_pairs,
+            adjudication,
+        )
+        .map_err(InvalidArgumentError::new_err)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Sprt({} vs {}, elo0={}, elo1={}, alpha={}, beta={})",
+            self.candidate.name, self.baseline.name, self.elo0, self.elo1, self.alpha, self.beta
+        )
+    }
+
+    /// The LLR below which H1 is rejected and above which it is accepted.
+    #[getter]
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// Plays until a decision is reached, appending each pair of games to the file `pgn` as it
+    /// finishes if given.
+    #[pyo3(name = "run", signature = (pgn=None))]
+    pub fn py_run(&self, py: Python, pgn: Option<&str>) -> PyResult<SprtResult> {
+        let io_error = |err: io::Error| PyIOError::new_err(err.to_string());
+        let mut writer = pgn.map(PgnWriter::create).transpose().map_err(io_error)?;
+        py.allow_threads(|| {
+            self.run(|_, games| match writer.as_mut() {
+                Some(writer) => games.iter().try_for_each(|game| writer.write(game)),
+                None => Ok(()),
+            })
+        })
+        .map_err(io_error)
+    }
+}
+
+impl Sprt {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        candidate: Participant,
+        baseline: Participant,
+        (elo0, elo1): (f64, f64),
+        (alpha, beta): (f64, f64),
+        openings: Vec<Opening>,
+        threads: usize,
+        max_pairs: Option<usize>,
+        adjudication: Adjudication,
+    ) -> Result<Self, String> {
+        if elo1 <= elo0 {
+            return Err(format!("elo1 ({}) must be above elo0 ({})", elo1, elo0));
+        }
+        if !(0.0 < alpha && alpha < 1.0 && 0.0 < beta && beta < 1.0) {
+            return Err("alpha and beta must be between 0 and 1".to_string());
+        }
+        if threads == 0 {
+            return Err("at least one thread is needed".to_string());
+        }
+        let mut participants = [candidate, baseline];
+        unique_names(&mut participants);
+        let [candidate, baseline] = participants;
+        Ok(Sprt {
+            candidate,
+            baseline,
+            elo0,
+            elo1,
+            alpha,
+            beta,
+            openings,
+            threads,
+            max_pairs,
+            adjudication,
+        })
+    }
+
+    /// Plays pairs of games until the LLR leaves its bounds or `max_pairs` pairs are played,
+    /// calling `progress` with the status and the two games after every pair. Pairs already
+    /// started when the test stops are finished and counted. An error from `progress` stops the
+    /// test and is returned once the running pairs finish.
+    pub fn run<F>(&self, progress: F) -> io::Result<SprtResult>
+    where
+        F: FnMut(&SprtStatus, &[PgnGame]) -> io::Result<()> + Send,
+    {
+        let (lower, upper) = self.bounds();
+        let next = AtomicUsize::new(0);
+        let stop = AtomicBool::new(false);
+        let shared = Mutex::new((SprtStatus::default(), vec![], progress, None));
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                scope.spawn(|| loop {
+                    if stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let pair = next.fetch_add(1, Ordering::SeqCst);
+                    if self.max_pairs.is_some_and(|max| pair >= max) {
+                        break;
+                    }
+                    let games = self.play_pair(pair);
+
+                    // Engines that panic forfeit inside play_game, so no worker unwinds here
+                    let mut shared = shared.lock().unwrap_or_else(|err| err.into_inner());
+                    let (status, pgns, progress, error) = &mut *shared;
+                    let mut points = 0;
+                    let mut finished = Vec::with_capacity(games.len());
+                    for (pgn, outcome, candidate) in games {
+                        match outcome.value_for(candidate) as i8 {
+                            1 => {
+                                status.wins += 1;
+                                points += 2;
+                            }
+                            0 => {
+                                status.draws += 1;
+                                points += 1;
+                            }
+                            _ => status.losses += 1,
+                        }
+                        finished.push(pgn);
+                    }
+                    status.pentanomial[points] += 1;
+                    status.update_llr(self.elo0, self.elo1);
+                    if status.llr <= lower || status.llr >= upper {
+                        stop.store(true, Ordering::SeqCst);
+                    }
+                    if error.is_none() {
+                        if let Err(err) = progress(status, &finished) {
+                            *error = Some(err);
+                            stop.store(true, Ordering::SeqCst);
+                        }
+                    }
+                    pgns.extend(finished.into_iter().map(|pgn| (pair, pgn)));
+                });
+            }
+        });
+
+        let (status, mut pgns, _, error) =
+            shared.into_inner().unwrap_or_else(|err| err.into_inner());
+        if let Some(err) = error {
+            return Err(err);
+        }
+        // Pairs finish out of order on several threads
+        pgns.sort_by_key(|(pair, _)| *pair);
+        let decision = if status.llr >= upper {
+            Decision::Accept
+        } else if status.llr <= lower {
+            Decision::Reject
+        } else {
+            Decision::Inconclusive
+        };
+        Ok(SprtResult {
+            status,
+            decision,
+            bounds: (lower, upper),
+            pgns: pgns.into_iter().map(|(_, pgn)| pgn).collect(),
+        })
+    }
+
+    // Both games of a pair with the colour the candidate played
+    fn play_pair(&self, pair: usize) -> [(PgnGame, Outcome, pleco::Player); 2] {
+        let opening = &self.openings[pair % self.openings.len()];
+        let (first, first_outcome, _) = play_one(
+            "SPRT",
+            format!("{}.1", pair + 1),
+            &self.candidate,
+            &self.baseline,
+            opening,
+            &self.adjudication,
+        );
+        let (second, second_outcome, _) = play_one(
+            "SPRT",
+            format!("{}.2", pair + 1),
+            &self.baseline,
+            &self.candidate,
+            opening,
+            &self.adjudication,
+        );
+        [
+            (first, first_outcome, pleco::Player::White),
+            (second, second_outcome, pleco::Player::Black),
+        ]
+    }
+}
+
+/// The outcome of a test: the decision, the LLR and the statistics behind it.
+#[pyclass]
+pub struct SprtResult {
+    pub status: SprtStatus,
+    pub decision: Decision,
+    pub bounds: (f64, f64),
+    pgns: Vec<PgnGame>,
+}
+
+#[pymethods]
+impl SprtResult {
+    fn __repr__(&self) -> String {
+        format!(
+            "SprtResult(decision={}, llr={:.2}, bounds=({:.2}, {:.2}), pairs={})",
+            self.decision.name(),
+            self.status.llr,
+            self.bounds.0,
+            self.bounds.1,
+            self.status.pairs()
+        )
+    }
+
+    /// `accept` if the candidate is at least `elo1` stronger, `reject` if it is at most `elo0`
+    /// stronger, or `inconclusive` if the game limit came first.
+    #[getter(decision)]
+    fn py_decision(&self) -> &'static str {
+        self.decision.name()
+    }
+
+    #[getter]
+    fn llr(&self) -> f64 {
+        self.status.llr
+    }
+
+    #[getter(bounds)]
+    fn py_bounds(&self) -> (f64, f64) {
+        self.bounds
+    }
+
+    /// Game pairs scoring 0, 1/2, 1, 3/2 and 2 points for the candidate.
+    #[getter]
+    fn pentanomial(&self) -> [u32; 5] {
+        self.status.pentanomial
+    }
+
+    /// Wins, draws and losses of the candidate.
+    #[getter]
+    fn wdl(&self) -> (u32, u32, u32) {
+        (self.status.wins, self.status.draws, self.status.losses)
+    }
+
+    #[getter]
+    fn pairs(&self) -> u32 {
+        self.status.pairs()
+    }
+
+    /// The candidate's Elo difference to the baseline and the half-width of its 95% interval.
+    #[pyo3(name = "elo")]
+    fn py_elo(&self) -> (f64, f64) {
+        self.status.elo()
+    }
+
+    /// Every game as PGN.
+    pub fn pgn(&self) -> String {
+        self.pgns
+            .iter()
+            .map(|game| game.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Bot, EngineSpec};
+
+    fn status(pentanomial: [u32; 5], elo0: f64, elo1: f64) -> SprtStatus {
+        let mut status = SprtStatus {
+            pentanomial,
+            ..SprtStatus::default()
+        };
+        status.update_llr(elo0, elo1);
+        status
+    }
+
+    #[test]
+    fn llr_matches_fishtest() {
+        // LLR_logistic from fishtest's stat_util.py on the same pentanomial counts
+        for (pentanomial, (elo0, elo1), llr) in [
+            ([10, 20, 40, 20, 10], (0.0, 5.0), -0.03451280053328368),
+            ([100, 1500, 4000, 2000, 150], (0.0, 5.0), 24.464406655973757),
+            (
+                [400, 2500, 6000, 2600, 500],
+                (-1.75, 0.25),
+                5.591448146210417,
+            ),
+            ([0, 3, 10, 5, 2], (0.0, 10.0), 0.43935336973202976),
+        ] {
+            let status = status(pentanomial, elo0, elo1);
+            assert!(
+                (status.llr - llr).abs() < 1e-9,
+                "{:?}: {}",
+                pentanomial,
+                status.llr
+            );
+        }
+        assert_eq!(status([0; 5], 0.0, 5.0).llr, 0.0);
+    }
+
+    #[test]
+    fn estimates_elo_from_the_pair_scores() {
+        let (elo, error) = status([10, 20, 40, 20, 10], 0.0, 5.0).elo();
+        assert!(elo.abs() < 1e-9 && error > 0.0);
+        // Every pair won 2-0 against the baseline
+        let (elo, _) = status([0, 0, 0, 0, 50], 0.0, 5.0).elo();
+        assert!(elo > 800.0);
+        assert_eq!(SprtStatus::default().elo(), (0.0, f64::INFINITY));
+    }
+
+    fn random_sprt(threads: usize, max_pairs: usize) -> Sprt {
+        let bot = || {
+            Participant::new(
+                EngineSpec::Bot {
+                    bot: Bot::Random,
+                    depth: 1,
+                },
+                None,
+            )
+        };
+        Sprt::new(
+            bot(),
+            bot(),
+            (0.0, 5.0),
+            (0.05, 0.05),
+            vec![Opening::default()],
+            threads,
+            Some(max_pairs),
+            Adjudication {
+                max_plies: 20,
+                ..Adjudication::default()
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn bounds_follow_the_error_rates() {
+        let sprt = random_sprt(1, 1);
+        let (lower, upper) = sprt.bounds();
+        assert!((lower + 2.9444389791664403).abs() < 1e-12);
+        assert!((upper - 2.9444389791664403).abs() < 1e-12);
+        assert_eq!(sprt.baseline.name, format!("{} #2", sprt.candidate.name));
+    }
+
+    #[test]
+    fn hands_each_pair_to_progress_as_it_finishes() {
+        let sprt = random_sprt(2, 3);
+        let mut seen = vec![];
+        let result = sprt
+            .run(|status, games| {
+                assert_eq!(games.len(), 2);
+                assert_eq!(status.pairs() as usize, seen.len() / 2 + 1);
+                seen.extend(games.iter().map(|game| game.to_string()));
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(result.status.pairs(), 3);
+        assert_eq!(seen.len(), 6);
+        seen.sort();
+        let mut games: Vec<_> = result.pgns.iter().map(|game| game.to_string()).collect();
+        games.sort();
+        assert_eq!(seen, games);
+    }
+
+    #[test]
+    fn stops_on_a_progress_error() {
+        let sprt = random_sprt(1, 10);
+        let mut calls = 0;
+        let err = sprt
+            .run(|_, _| {
+                calls += 1;
+                Err(io::Error::other("disk full"))
+            })
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "disk full");
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,390 @@
+use pleco::tools::tt;
+use pyo3::prelude::*;
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crate::error::InvalidArgumentError;
+use crate::piece_move::BitMove;
+
+const BYTES_PER_MB: usize = 1024 * 1024;
+
+/// How a stored score relates to the true value of the position.
+#[pyclass]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Bound {
+    /// The score is at least the true value: the search failed high.
+    Lower = 1,
+    /// The score is at most the true value: the search failed low.
+    Upper = 2,
+    Exact = 3,
+}
+
+impl From<Bound> for tt::NodeBound {
+    fn from(bound: Bound) -> tt::NodeBound {
+        match bound {
+            Bound::Lower => tt::NodeBound::LowerBound,
+            Bound::Upper => tt::NodeBound::UpperBound,
+            Bound::Exact => tt::NodeBound::Exact,
+        }
+    }
+}
+
+/// What the table holds for a position.
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct TableEntry {
+    #[pyo3(get)]
+    pub depth: i8,
+    #[pyo3(get)]
+    pub score: i16,
+    #[pyo3(get)]
+    pub bound: Bound,
+    pub best_move: Option<pleco::BitMove>,
+}
+
+#[pymethods]
+impl TableEntry {
+    fn __repr__(&self) -> String {
+        let best_move = self
+            .best_move
+            .map_or_else(|| "None".to_string(), |bit_move| bit_move.stringify());
+        format!(
+            "TableEntry(depth={}, score={}, bound={:?}, best_move={})",
+            self.depth, self.score, self.bound, best_move
+        )
+    }
+
+    #[getter]
+    fn best_move(&self) -> Option<BitMove> {
+        self.best_move.map(BitMove::from)
+    }
+}
+
+// pleco's table owns its allocation but holds it through a raw pointer, which keeps it from being
+// `Send`. Its entries are reached through `&mut` references even by probes, so every access to
+// them goes through the write lock below
+struct Table(tt::TranspositionTable);
+
+unsafe impl Send for Table {}
+
+struct Shared {
+    // Probes, stores, new generations and clearing take the lock alone: pleco's probe hands out
+    // the entry as `&mut` and marks it as used by the current generation. Only the size and
+    // statistics of the table are read under a shared lock
+    table: RwLock<Table>,
+    probes: AtomicU64,
+    hits: AtomicU64,
+    stores: AtomicU64,
+}
+
+/// A hash table of search results keyed by zobrist key, backed by pleco's transposition table.
+///
+/// Entries are grouped in clusters of three; a full cluster replaces the entry that is
+/// shallowest and from the oldest search generation. Copies share the same table, so one table
+/// can serve several boards, environments and threads.
+#[pyclass]
+#[derive(Clone)]
+pub struct TranspositionTable {
+    shared: Arc<Shared>,
+}
+
+#[pymethods]
+impl TranspositionTable {
+    /// A table of about `size_mb` megabytes, rounded down to a power of two number of clusters.
+    #[new]
+    #[pyo3(signature = (size_mb=16))]
+    pub fn new(size_mb: usize) -> PyResult<Self> {
+        if size_mb == 0 || size_mb > tt::TranspositionTable::MAX_SIZE_MB {
+            return Err(InvalidArgumentError::new_err(format!(
+                "size_mb must be between 1 and {}",
+                tt::TranspositionTable::MAX_SIZE_MB
+            )));
+        }
+        // pleco's own sizing halves sizes that are already a power of two
+        let clusters = size_mb * BYTES_PER_MB / mem::size_of::<tt::Cluster>();
+        let clusters = 1 << clusters.ilog2();
+        Ok(TranspositionTable {
+            shared: Arc::new(Shared {
+                table: RwLock::new(Table(tt::TranspositionTable::new_num_clusters(clusters))),
+                probes: AtomicU64::new(0),
+                hits: AtomicU64::new(0),
+                stores: AtomicU64::new(0),
+            }),
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "TranspositionTable(size_mb={}, entries={}, generation={})",
+            self.size_mb(),
+            self.entries(),
+            self.generation()
+        )
+    }
+
+    #[getter]
+    pub fn size_mb(&self) -> usize {
+        self.read(|table| table.num_clusters() * mem::size_of::<tt::Cluster>() / BYTES_PER_MB)
+    }
+
+    #[getter]
+    pub fn entries(&self) -> usize {
+        self.read(|table| table.num_entries())
+    }
+
+    /// Incremented by `new_search`; entries from older generations are replaced first.
+    #[getter]
+    pub fn generation(&self) -> u8 {
+        self.read(|table| table.time_age_cylces())
+    }
+
+    /// Starts a new generation. Call it before searching a new position.
+    pub fn new_search(&self) {
+        self.write(|table| table.new_search())
+    }
+
+    /// The entry stored for `key`, as given by `Board.zobrist()`, if there is one.
+    ///
+    /// Like a probe during a search, this marks the entry as belonging to the current generation,
+    /// so it is not replaced before entries of older ones.
+    pub fn probe(&self, key: u64) -> Option<TableEntry> {
+        self.get(key)
+    }
+
+    /// Stores a search result for `key`. An entry for the same position is only overwritten by
+    /// an exact score or one searched at least nearly as deep, and keeps its best move otherwise.
+    /// A result without a best move keeps the one stored before.
+    #[pyo3(signature = (key, depth, score, bound, best_move=None))]
+    pub fn store(&self, key: u64, depth: i8, score: i16, bound: Bound, best_move: Option<BitMove>) {
+        self.put(
+            key,
+            depth,
+            score,
+            bound,
+            best_move.map(pleco::BitMove::from),
+        );
+    }
+
+    /// Empties the table and resets the statistics.
+    pub fn clear(&self) {
+        let mut table = self
+            .shared
+            .table
+            .write()
+            .unwrap_or_else(|err| err.into_inner());
+        *table = Table(tt::TranspositionTable::new_num_clusters(
+            table.0.num_clusters(),
+        ));
+        self.shared.probes.store(0, Ordering::Relaxed);
+        self.shared.hits.store(0, Ordering::Relaxed);
+        self.shared.stores.store(0, Ordering::Relaxed);
+    }
+
+    #[getter]
+    pub fn probes(&self) -> u64 {
+        self.shared.probes.load(Ordering::Relaxed)
+    }
+
+    #[getter]
+    pub fn hits(&self) -> u64 {
+        self.shared.hits.load(Ordering::Relaxed)
+    }
+
+    #[getter]
+    pub fn stores(&self) -> u64 {
+        self.shared.stores.load(Ordering::Relaxed)
+    }
+
+    /// The fraction of probes that found an entry.
+    #[getter]
+    pub fn hit_rate(&self) -> f64 {
+        match self.probes() {
+            0 => 0.0,
+            probes => self.hits() as f64 / probes as f64,
+        }
+    }
+
+    /// The fraction of a sample of entries written during the current generation.
+    #[getter]
+    pub fn fill(&self) -> f64 {
+        self.read(|table| table.hash_percent() / 100.0)
+    }
+}
+
+impl TranspositionTable {
+    fn read<T>(&self, f: impl FnOnce(&tt::TranspositionTable) -> T) -> T {
+        let table = self
+            .shared
+            .table
+            .read()
+            .unwrap_or_else(|err| err.into_inner());
+        f(&table.0)
+    }
+
+    fn write<T>(&self, f: impl FnOnce(&tt::TranspositionTable) -> T) -> T {
+        let table = self
+            .shared
+            .table
+            .write()
+            .unwrap_or_else(|err| err.into_inner());
+        f(&table.0)
+    }
+
+    pub fn get(&self, key: u64) -> Option<TableEntry> {
+        self.shared.probes.fetch_add(1, Ordering::Relaxed);
+        let entry = self.peek(key)?;
+        self.shared.hits.fetch_add(1, Ordering::Relaxed);
+        Some(entry)
+    }
+
+    /// Like `get`, without counting towards the statistics.
+    pub fn peek(&self, key: u64) -> Option<TableEntry> {
+        self.write(|table| {
+            let (found, entry) = table.probe(key);
+            let bound = match entry.node_type() {
+                tt::NodeBound::LowerBound => Bound::Lower,
+                tt::NodeBound::UpperBound => Bound::Upper,
+                tt::NodeBound::Exact => Bound::Exact,
+                tt::NodeBound::NoBound => return None,
+            };
+            found.then(|| TableEntry {
+                depth: entry.depth,
+                score: entry.score,
+                bound,
+                best_move: Some(entry.best_move).filter(|bit_move| !bit_move.is_null()),
+            })
+        })
+    }
+
+    pub fn put(
+        &self,
+        key: u64,
+        depth: i8,
+        score: i16,
+        bound: Bound,
+        best_move: Option<pleco::BitMove>,
+    ) {
+        self.shared.stores.fetch_add(1, Ordering::Relaxed);
+        self.write(|table| {
+            let (_, entry) = table.probe(key);
+            // pleco only takes the move when the entry held another position; for the same
+            // position it follows the rest of the result, or fills in a missing move
+            let same_position = entry.partial_key == (key >> 48) as u16;
+            let replaced = bound == Bound::Exact || depth as i16 > entry.depth as i16 - 4;
+            let takes_move = same_position && (replaced || entry.best_move.is_null());
+            entry.place(
+                key,
+                best_move.unwrap_or_else(pleco::BitMove::null),
+                score,
+                0,
+                depth as i16,
+                bound.into(),
+                table.time_age(),
+            );
+            if let Some(bit_move) = best_move.filter(|_| takes_move) {
+                entry.best_move = bit_move;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn moves() -> Vec<pleco::BitMove> {
+        pleco::Board::start_pos().generate_moves().to_vec()
+    }
+
+    #[test]
+    fn keeps_the_move_of_deeper_results() {
+        let table = TranspositionTable::new(1).unwrap();
+        let moves = moves();
+        let key = 0x1234_5678_9abc_def0;
+
+        table.put(key, 8, 10, Bound::Lower, Some(moves[0]));
+        // Too shallow to replace a bound, so neither the score nor the move change
+        table.put(key, 3, -20, Bound::Upper, Some(moves[1]));
+        let entry = table.peek(key).unwrap();
+        assert_eq!(
+            (entry.depth, entry.score, entry.bound),
+            (8, 10, Bound::Lower)
+        );
+        assert_eq!(entry.best_move, Some(moves[0]));
+
+        // Nearly as deep replaces the result, but without a move keeps the stored one
+        table.put(key, 5, 30, Bound::Upper, None);
+        let entry = table.peek(key).unwrap();
+        assert_eq!(
+            (entry.depth, entry.score, entry.bound),
+            (5, 30, Bound::Upper)
+        );
+        assert_eq!(entry.best_move, Some(moves[0]));
+
+        // An exact score always replaces the result and its move
+        table.put(key, 1, 40, Bound::Exact, Some(moves[2]));
+        let entry = table.peek(key).unwrap();
+        assert_eq!(
+            (entry.depth, entry.score, entry.bound),
+            (1, 40, Bound::Exact)
+        );
+        assert_eq!(entry.best_move, Some(moves[2]));
+    }
+
+    #[test]
+    fn fills_in_a_missing_move() {
+        let table = TranspositionTable::new(1).unwrap();
+        let moves = moves();
+        let key = 0x0fed_cba9_8765_4321;
+
+        table.put(key, 8, 10, Bound::Lower, None);
+        table.put(key, 2, 0, Bound::Upper, Some(moves[3]));
+        let entry = table.peek(key).unwrap();
+        assert_eq!((entry.depth, entry.score), (8, 10));
+        assert_eq!(entry.best_move, Some(moves[3]));
+        assert_eq!(table.stores(), 2);
+    }
+
+    #[test]
+    fn probes_keep_entries_in_the_current_generation() {
+        let table = TranspositionTable::new(1).unwrap();
+        // Four positions in the same cluster of three
+        let keys = (1..=4u64).map(|i| i << 48 | 5).collect::<Vec<_>>();
+        for &key in &keys[..3] {
+            table.put(key, 1, 0, Bound::Exact, None);
+        }
+        table.new_search();
+        assert!(table.probe(keys[0]).is_some());
+        // The oldest entries are replaced first, and the probed one is no longer among them
+        table.put(keys[3], 1, 0, Bound::Exact, None);
+        assert!(table.peek(keys[0]).is_some());
+        assert!(table.peek(keys[1]).is_none());
+        assert!(table.peek(keys[3]).is_some());
+    }
+
+    #[test]
+    fn probes_and_stores_from_several_threads() {
+        let table = TranspositionTable::new(1).unwrap();
+        let moves = moves();
+        // One key per thread, each in its own cluster, stored over and over with the same move
+        let keys = (1..=8u64).map(|i| i << 48 | i).collect::<Vec<_>>();
+        thread::scope(|scope| {
+            for (i, &key) in keys.iter().enumerate() {
+                let (table, bit_move) = (&table, moves[i]);
+                scope.spawn(move || {
+                    for depth in 0..100 {
+                        table.put(key, depth % 10, depth as i16, Bound::Lower, Some(bit_move));
+                        assert_eq!(table.get(key).unwrap().best_move, Some(bit_move));
+                    }
+                });
+            }
+        });
+        for (i, &key) in keys.iter().enumerate() {
+            assert_eq!(table.peek(key).unwrap().best_move, Some(moves[i]));
+        }
+        assert_eq!(table.stores(), 800);
+        assert_eq!(table.hits(), 800);
+    }
+}
@@ -0,0 +1,6 @@
+#!/bin/sh
+# Replaces the checked-in tables with the official Syzygy files.
+# The checked-in tables were built by retrograde analysis and agree with the official ones on
+# every legal position, but are compressed differently. KBvK and KNvK are only reached by
+# underpromotion and hold a single draw value.
+cd "$(dirname "$0")" && xargs -n 1 curl -fsSLO < SOURCE.txt